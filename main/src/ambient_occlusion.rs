@@ -0,0 +1,453 @@
+use crate::{AmbientOcclusionSettings, DrawStats, UniformBinding};
+
+/// Screen-space ambient occlusion of the depth buffer, blurred without
+/// bleeding across depth discontinuities.
+pub(crate) struct AmbientOcclusionRenderer {
+    occlusion_pipeline: wgpu::RenderPipeline,
+    /// Horizontal and vertical blur
+    blur_pipelines: [wgpu::RenderPipeline; 2],
+    occlusion_layout: wgpu::BindGroupLayout,
+    blur_layout: wgpu::BindGroupLayout,
+    uniform_buffer: wgpu::Buffer,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct AmbientOcclusionUniform {
+    /// Offsets in the hemisphere around +z, denser towards the center
+    kernel: [[f32; 4]; AmbientOcclusionRenderer::KERNEL_SIZE],
+    /// Random directions the kernel is rotated by, tiled over the screen
+    noise: [[f32; 4]; AmbientOcclusionRenderer::NOISE_SIZE * AmbientOcclusionRenderer::NOISE_SIZE],
+    /// Radius in x, intensity in y
+    settings: [f32; 4],
+}
+
+impl AmbientOcclusionRenderer {
+    /// Written by the occlusion pass and blurred in two passes.
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+    const KERNEL_SIZE: usize = 16;
+    /// Width and height of the noise tile, in pixels.
+    const NOISE_SIZE: usize = 4;
+
+    /// Takes the bind group layout of the camera.
+    pub fn new(device: &wgpu::Device, camera_layout: &wgpu::BindGroupLayout) -> Self {
+        // Depth is read as a plain float texture, GL has no other way to load it
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let occlusion_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Ambient Occlusion Bind Group Layout"),
+            entries: &[
+                texture_entry(0),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let blur_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Ambient Occlusion Blur Bind Group Layout"),
+            entries: &[texture_entry(0), texture_entry(1)],
+        });
+
+        let occlusion_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Ambient Occlusion Shader"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Owned(format!(
+                "{SHADER_SOURCE}{OCCLUSION_SHADER_SOURCE}"
+            ))),
+        });
+        let blur_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Ambient Occlusion Blur Shader"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Owned(format!(
+                "{SHADER_SOURCE}{BLUR_SHADER_SOURCE}"
+            ))),
+        });
+        let create_pipeline = |label, layout, shader_module: &wgpu::ShaderModule, entry_point| {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: &[camera_layout, layout],
+                push_constant_ranges: &[],
+            });
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: shader_module,
+                    entry_point: "vertex_main",
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                fragment: Some(wgpu::FragmentState {
+                    module: shader_module,
+                    entry_point,
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: Self::FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                multiview: None,
+                cache: None,
+            })
+        };
+        let occlusion_pipeline = create_pipeline(
+            "Ambient Occlusion Pipeline",
+            &occlusion_layout,
+            &occlusion_module,
+            "fragment_occlusion",
+        );
+        let blur_pipelines = [
+            create_pipeline(
+                "Ambient Occlusion Horizontal Blur Pipeline",
+                &blur_layout,
+                &blur_module,
+                "fragment_blur_horizontal",
+            ),
+            create_pipeline(
+                "Ambient Occlusion Vertical Blur Pipeline",
+                &blur_layout,
+                &blur_module,
+                "fragment_blur_vertical",
+            ),
+        ];
+
+        let uniform_buffer = wgpu::util::DeviceExt::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Ambient Occlusion Uniform Buffer"),
+                contents: bytemuck::cast_slice(&[Self::uniform(
+                    AmbientOcclusionSettings::default(),
+                )]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            },
+        );
+
+        Self {
+            occlusion_pipeline,
+            blur_pipelines,
+            occlusion_layout,
+            blur_layout,
+            uniform_buffer,
+        }
+    }
+
+    /// The kernel and noise, which never change, along with `settings`.
+    fn uniform(settings: AmbientOcclusionSettings) -> AmbientOcclusionUniform {
+        let mut seed = 0;
+        let mut next_random = || {
+            seed += 1;
+            random(seed)
+        };
+        let kernel = std::array::from_fn(|index| {
+            let offset = nalgebra_glm::normalize(&nalgebra_glm::vec3(
+                next_random() * 2.0 - 1.0,
+                next_random() * 2.0 - 1.0,
+                next_random().max(0.05),
+            )) * next_random();
+            // Most samples close to the center, where occluders matter most
+            let fraction = index as f32 / Self::KERNEL_SIZE as f32;
+            let offset = offset * nalgebra_glm::lerp_scalar(0.1, 1.0, fraction * fraction);
+            [offset.x, offset.y, offset.z, 0.0]
+        });
+        let noise = std::array::from_fn(|_| {
+            [
+                next_random() * 2.0 - 1.0,
+                next_random() * 2.0 - 1.0,
+                next_random() * 2.0 - 1.0,
+                0.0,
+            ]
+        });
+        AmbientOcclusionUniform {
+            kernel,
+            noise,
+            settings: [settings.radius, settings.intensity, 0.0, 0.0],
+        }
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, settings: AmbientOcclusionSettings) {
+        queue.write_buffer(
+            &self.uniform_buffer,
+            std::mem::offset_of!(AmbientOcclusionUniform, settings) as wgpu::BufferAddress,
+            bytemuck::cast_slice(&[settings.radius, settings.intensity, 0.0, 0.0]),
+        );
+    }
+
+    /// Writes the occlusion of the depth in `depth_view` to `occlusion_view`,
+    /// using `scratch_view` of the same size between the blur passes.
+    /// `depth_view` must not be attached to any pass in flight.
+    pub fn render(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        camera: &UniformBinding,
+        depth_view: &wgpu::TextureView,
+        [occlusion_view, scratch_view]: [&wgpu::TextureView; 2],
+    ) -> DrawStats {
+        // The views are recreated on resize, so the bind groups are made per frame
+        let occlusion_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Ambient Occlusion Bind Group"),
+            layout: &self.occlusion_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(depth_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
+            ],
+        });
+        let blur_bind_group = |source| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Ambient Occlusion Blur Bind Group"),
+                layout: &self.blur_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(depth_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(source),
+                    },
+                ],
+            })
+        };
+
+        let passes = [
+            (
+                "Ambient Occlusion Pass",
+                &self.occlusion_pipeline,
+                occlusion_bind_group,
+                occlusion_view,
+            ),
+            (
+                "Ambient Occlusion Horizontal Blur Pass",
+                &self.blur_pipelines[0],
+                blur_bind_group(occlusion_view),
+                scratch_view,
+            ),
+            (
+                "Ambient Occlusion Vertical Blur Pass",
+                &self.blur_pipelines[1],
+                blur_bind_group(scratch_view),
+                occlusion_view,
+            ),
+        ];
+        let mut draw_stats = DrawStats::default();
+        for (label, pipeline, bind_group, view) in &passes {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(label),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &camera.bind_group, &[]);
+            render_pass.set_bind_group(1, bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+            draw_stats += DrawStats {
+                draw_calls: 1,
+                triangles: 1,
+                ..Default::default()
+            };
+        }
+        draw_stats
+    }
+}
+
+/// Deterministic pseudo-random number from 0 to 1 for `seed`, a PCG hash.
+fn random(seed: u32) -> f32 {
+    let state = seed.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    ((word >> 22) ^ word) as f32 / u32::MAX as f32
+}
+
+/// Fullscreen vertex stage and depth helpers shared by the passes.
+const SHADER_SOURCE: &str = "
+const KERNEL_SIZE: u32 = 16u;
+const NOISE_SIZE: u32 = 4u;
+const BLUR_RADIUS: i32 = 4;
+
+struct Camera {
+    view_projection: mat4x4<f32>,
+    position: vec4<f32>,
+    inverse_view_projection: mat4x4<f32>,
+    depth_range: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+@group(1) @binding(0)
+var depth_texture: texture_2d<f32>;
+
+@vertex
+fn vertex_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    // A triangle covering the whole screen
+    return vec4<f32>(
+        f32(vertex_index == 1u) * 4.0 - 1.0,
+        f32(vertex_index == 2u) * 4.0 - 1.0,
+        0.0,
+        1.0,
+    );
+}
+
+fn load_depth(coords: vec2<i32>) -> f32 {
+    let size = vec2<i32>(textureDimensions(depth_texture));
+    return textureLoad(depth_texture, clamp(coords, vec2<i32>(0), size - 1), 0).r;
+}
+
+// Distance along the view direction, inverting the 0 to 1 depth of the
+// perspective projection
+fn linear_depth(depth: f32) -> f32 {
+    let near = camera.depth_range.x;
+    let far = camera.depth_range.y;
+    return near * far / (far - depth * (far - near));
+}
+
+fn world_position(coords: vec2<i32>) -> vec3<f32> {
+    let size = vec2<f32>(textureDimensions(depth_texture));
+    let center = vec2<f32>(coords) + 0.5;
+    let ndc = vec2<f32>(center.x / size.x * 2.0 - 1.0, 1.0 - center.y / size.y * 2.0);
+    let position = camera.inverse_view_projection * vec4<f32>(ndc, load_depth(coords), 1.0);
+    return position.xyz / position.w;
+}
+";
+
+/// The occlusion pass, appended to [`SHADER_SOURCE`].
+const OCCLUSION_SHADER_SOURCE: &str = "
+struct AmbientOcclusion {
+    kernel: array<vec4<f32>, KERNEL_SIZE>,
+    noise: array<vec4<f32>, 16>,
+    settings: vec4<f32>,
+};
+
+@group(1) @binding(1)
+var<uniform> ambient_occlusion: AmbientOcclusion;
+
+// From the neighbors on the same surface, taking the side with the smaller
+// depth difference so edges don't blend into what is behind them
+fn reconstruct_normal(coords: vec2<i32>, center: vec3<f32>) -> vec3<f32> {
+    let depth = load_depth(coords);
+    var dx = world_position(coords + vec2<i32>(1, 0)) - center;
+    if abs(load_depth(coords - vec2<i32>(1, 0)) - depth)
+        < abs(load_depth(coords + vec2<i32>(1, 0)) - depth) {
+        dx = center - world_position(coords - vec2<i32>(1, 0));
+    }
+    var dy = world_position(coords + vec2<i32>(0, 1)) - center;
+    if abs(load_depth(coords - vec2<i32>(0, 1)) - depth)
+        < abs(load_depth(coords + vec2<i32>(0, 1)) - depth) {
+        dy = center - world_position(coords - vec2<i32>(0, 1));
+    }
+    var normal = normalize(cross(dx, dy));
+    if dot(normal, camera.position.xyz - center) < 0.0 {
+        normal = -normal;
+    }
+    return normal;
+}
+
+// 1 where the hemisphere above the surface is open, towards 0 where the
+// depth buffer has surfaces inside it
+@fragment
+fn fragment_occlusion(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let coords = vec2<i32>(position.xy);
+    let depth = load_depth(coords);
+    if depth >= 1.0 {
+        return vec4<f32>(1.0);
+    }
+    let center = world_position(coords);
+    let normal = reconstruct_normal(coords, center);
+    let radius = ambient_occlusion.settings.x;
+
+    // Rotates the kernel around the normal, differently within each noise tile
+    let noise_coords = vec2<u32>(coords) % NOISE_SIZE;
+    let random = ambient_occlusion.noise[noise_coords.y * NOISE_SIZE + noise_coords.x].xyz;
+    var tangent = random - normal * dot(random, normal);
+    if length(tangent) < 0.001 {
+        tangent = cross(normal, vec3<f32>(0.0, 0.0, 1.0));
+    }
+    tangent = normalize(tangent);
+    let frame = mat3x3<f32>(tangent, cross(normal, tangent), normal);
+
+    let size = vec2<f32>(textureDimensions(depth_texture));
+    let center_depth = linear_depth(depth);
+    var occlusion = 0.0;
+    for (var index = 0u; index < KERNEL_SIZE; index++) {
+        let sample = center + frame * ambient_occlusion.kernel[index].xyz * radius;
+        let clip = camera.view_projection * vec4<f32>(sample, 1.0);
+        if clip.w <= 0.0 {
+            continue;
+        }
+        let ndc = clip.xy / clip.w;
+        let sample_coords = vec2<i32>(vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5) * size);
+        let surface_depth = linear_depth(load_depth(sample_coords));
+        // Surfaces far in front of the sampled point don't shadow it
+        let range = smoothstep(0.0, 1.0, radius / abs(center_depth - surface_depth));
+        occlusion += select(0.0, range, surface_depth < clip.w - 0.02 * radius);
+    }
+    let open = 1.0 - occlusion / f32(KERNEL_SIZE);
+    return vec4<f32>(pow(open, ambient_occlusion.settings.y));
+}
+";
+
+/// The blur passes, appended to [`SHADER_SOURCE`].
+const BLUR_SHADER_SOURCE: &str = "
+@group(1) @binding(1)
+var occlusion_texture: texture_2d<f32>;
+
+// Gaussian weights that fall off with the relative depth difference, so
+// occlusion doesn't bleed across the edges of objects
+fn blur(position: vec4<f32>, direction: vec2<i32>) -> vec4<f32> {
+    let coords = vec2<i32>(position.xy);
+    let size = vec2<i32>(textureDimensions(occlusion_texture));
+    let center_depth = linear_depth(load_depth(coords));
+    var sum = 0.0;
+    var weights = 0.0;
+    for (var offset = -BLUR_RADIUS; offset <= BLUR_RADIUS; offset++) {
+        let sample_coords = clamp(coords + direction * offset, vec2<i32>(0), size - 1);
+        let depth = linear_depth(load_depth(sample_coords));
+        let distance = f32(offset) / f32(BLUR_RADIUS);
+        let weight = exp(-2.0 * distance * distance)
+            * exp(-abs(depth - center_depth) / (0.02 * center_depth));
+        sum += textureLoad(occlusion_texture, sample_coords, 0).r * weight;
+        weights += weight;
+    }
+    return vec4<f32>(sum / weights);
+}
+
+@fragment
+fn fragment_blur_horizontal(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    return blur(position, vec2<i32>(1, 0));
+}
+
+@fragment
+fn fragment_blur_vertical(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    return blur(position, vec2<i32>(0, 1));
+}
+";
@@ -0,0 +1,549 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Skeleton, Transform};
+
+/// Keyframed changes to a transform and the joints of its skeleton, referenced
+/// by name from [`AnimationPlayer`]s.
+///
+/// Channels without a track keep the value of the animated object's own
+/// transform, or the rest pose of the joint.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnimationClip {
+    pub name: String,
+    #[serde(default)]
+    pub repeat: Repeat,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub translation: Option<Track<nalgebra_glm::Vec3>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<Track<nalgebra_glm::Quat>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<Track<nalgebra_glm::Vec3>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub joints: Vec<JointAnimation>,
+}
+
+/// Keyframed local transform of a skeleton joint.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JointAnimation {
+    /// Name of the joint in the skeleton of the animated object
+    pub joint: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub translation: Option<Track<nalgebra_glm::Vec3>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<Track<nalgebra_glm::Quat>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<Track<nalgebra_glm::Vec3>>,
+}
+
+/// What happens when a clip reaches its end.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Repeat {
+    /// Starts over from the beginning
+    #[default]
+    Loop,
+    /// Stops, holding the last keyframe
+    Once,
+}
+
+/// Values of one channel over time, keyframes sorted by time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Track<T> {
+    #[serde(default)]
+    pub interpolation: Interpolation,
+    pub keyframes: Vec<Keyframe<T>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Keyframe<T> {
+    /// Time in seconds from the start of the clip
+    pub time: f32,
+    pub value: T,
+    /// Rate of change per second arriving at the keyframe, for cubic tracks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub in_tangent: Option<T>,
+    /// Rate of change per second leaving the keyframe, for cubic tracks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub out_tangent: Option<T>,
+}
+
+/// How values between two keyframes are computed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Interpolation {
+    /// Straight blend, spherical for rotations
+    #[default]
+    Linear,
+    /// Holds each keyframe until the next one
+    Step,
+    /// Hermite spline through the keyframes, using their tangents or
+    /// Catmull-Rom tangents for keyframes without
+    Cubic,
+}
+
+/// Plays a clip on the object it is attached to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnimationPlayer {
+    /// Name of the clip to play
+    pub clip: String,
+    /// Playback rate, negative values play backwards
+    #[serde(default = "AnimationPlayer::default_speed")]
+    pub speed: f32,
+    /// Position in the clip in seconds, the start time when loading
+    #[serde(default)]
+    pub time: f32,
+    #[serde(default = "AnimationPlayer::default_playing")]
+    pub playing: bool,
+}
+
+impl AnimationClip {
+    /// Time of the last keyframe of any track.
+    pub fn duration(&self) -> f32 {
+        self.joints
+            .iter()
+            .map(|joint| channels_duration(&joint.translation, &joint.rotation, &joint.scale))
+            .fold(
+                channels_duration(&self.translation, &self.rotation, &self.scale),
+                f32::max,
+            )
+    }
+
+    /// `base` with the animated channels replaced by their values at `time`.
+    pub fn sample(&self, time: f32, base: &Transform) -> Transform {
+        sample_channels(&self.translation, &self.rotation, &self.scale, time, base)
+    }
+
+    /// Local transforms of all joints of `skeleton` at `time`, joints the
+    /// clip doesn't animate are in their rest pose.
+    pub fn sample_pose(&self, time: f32, skeleton: &Skeleton) -> Vec<Transform> {
+        let mut pose = skeleton.rest_pose();
+        for animation in &self.joints {
+            if let Some(index) = skeleton.joint(&animation.joint) {
+                pose[index] = sample_channels(
+                    &animation.translation,
+                    &animation.rotation,
+                    &animation.scale,
+                    time,
+                    &pose[index],
+                );
+            }
+        }
+        pose
+    }
+
+    /// Describes the first track that is empty or not sorted by time.
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        validate_channels(&self.translation, &self.rotation, &self.scale)?;
+        for animation in &self.joints {
+            validate_channels(
+                &animation.translation,
+                &animation.rotation,
+                &animation.scale,
+            )
+            .map_err(|error| format!("joint '{}' {error}", animation.joint))?;
+        }
+        Ok(())
+    }
+}
+
+fn channels_duration(
+    translation: &Option<Track<nalgebra_glm::Vec3>>,
+    rotation: &Option<Track<nalgebra_glm::Quat>>,
+    scale: &Option<Track<nalgebra_glm::Vec3>>,
+) -> f32 {
+    [
+        translation.as_ref().map(Track::duration),
+        rotation.as_ref().map(Track::duration),
+        scale.as_ref().map(Track::duration),
+    ]
+    .into_iter()
+    .flatten()
+    .fold(0.0, f32::max)
+}
+
+fn sample_channels(
+    translation: &Option<Track<nalgebra_glm::Vec3>>,
+    rotation: &Option<Track<nalgebra_glm::Quat>>,
+    scale: &Option<Track<nalgebra_glm::Vec3>>,
+    time: f32,
+    base: &Transform,
+) -> Transform {
+    Transform {
+        translation: translation
+            .as_ref()
+            .map_or(base.translation, |track| track.sample(time)),
+        rotation: rotation
+            .as_ref()
+            .map_or(base.rotation, |track| track.sample(time)),
+        scale: scale
+            .as_ref()
+            .map_or(base.scale, |track| track.sample(time)),
+    }
+}
+
+fn validate_channels(
+    translation: &Option<Track<nalgebra_glm::Vec3>>,
+    rotation: &Option<Track<nalgebra_glm::Quat>>,
+    scale: &Option<Track<nalgebra_glm::Vec3>>,
+) -> Result<(), String> {
+    let check = |channel: &str, error: Option<String>| match error {
+        Some(error) => Err(format!("{channel} track {error}")),
+        None => Ok(()),
+    };
+    check("translation", translation.as_ref().and_then(Track::error))?;
+    check("rotation", rotation.as_ref().and_then(Track::error))?;
+    check("scale", scale.as_ref().and_then(Track::error))
+}
+
+impl<T: TrackValue> Track<T> {
+    /// Fails if there are no keyframes or they are not sorted by time.
+    pub fn new(interpolation: Interpolation, keyframes: Vec<Keyframe<T>>) -> Result<Self, String> {
+        let track = Self {
+            interpolation,
+            keyframes,
+        };
+        match track.error() {
+            Some(error) => Err(format!("track {error}")),
+            None => Ok(track),
+        }
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |keyframe| keyframe.time)
+    }
+
+    /// Value at `time`, holding the first and last keyframes outside of the track.
+    pub fn sample(&self, time: f32) -> T {
+        let keyframes = &self.keyframes;
+        let next = keyframes.partition_point(|keyframe| keyframe.time <= time);
+        if next == 0 {
+            return keyframes[0].value;
+        }
+        if next == keyframes.len() {
+            return keyframes[next - 1].value;
+        }
+
+        let (from, to) = (&keyframes[next - 1], &keyframes[next]);
+        let interval = to.time - from.time;
+        let t = if interval > 0.0 {
+            (time - from.time) / interval
+        } else {
+            1.0
+        };
+        match self.interpolation {
+            Interpolation::Step => from.value,
+            Interpolation::Linear => from.value.blend(&to.value, t),
+            Interpolation::Cubic => {
+                let from_vector = from.value.to_vector();
+                let to_vector = to.value.align(&from.value).to_vector();
+                // Tangents flip along with the value they belong to
+                let to_sign = if to_vector.dot(&to.value.to_vector()) < 0.0 {
+                    -1.0
+                } else {
+                    1.0
+                };
+                let out_tangent = from.out_tangent.map_or_else(
+                    || self.tangent(next - 1, &from.value),
+                    TrackValue::to_vector,
+                );
+                let in_tangent = to.in_tangent.map_or_else(
+                    || self.tangent(next, &from.value),
+                    |tangent| tangent.to_vector() * to_sign,
+                );
+                let (t2, t3) = (t * t, t * t * t);
+                T::from_vector(
+                    from_vector * (2.0 * t3 - 3.0 * t2 + 1.0)
+                        + out_tangent * (interval * (t3 - 2.0 * t2 + t))
+                        + to_vector * (-2.0 * t3 + 3.0 * t2)
+                        + in_tangent * (interval * (t3 - t2)),
+                )
+            }
+        }
+    }
+
+    /// Catmull-Rom tangent at a keyframe, one-sided at the ends of the track.
+    fn tangent(&self, index: usize, reference: &T) -> nalgebra_glm::Vec4 {
+        let previous = &self.keyframes[index.saturating_sub(1)];
+        let next = &self.keyframes[(index + 1).min(self.keyframes.len() - 1)];
+        let interval = next.time - previous.time;
+        if interval <= 0.0 {
+            return nalgebra_glm::Vec4::zeros();
+        }
+        (next.value.align(reference).to_vector() - previous.value.align(reference).to_vector())
+            / interval
+    }
+
+    /// What makes the keyframes unusable, if anything.
+    fn error(&self) -> Option<String> {
+        let keyframes = &self.keyframes;
+        if keyframes.is_empty() {
+            Some("has no keyframes".to_string())
+        } else if keyframes.iter().any(|keyframe| !keyframe.time.is_finite()) {
+            Some("has a keyframe at an invalid time".to_string())
+        } else if keyframes.windows(2).any(|pair| pair[0].time > pair[1].time) {
+            Some("keyframes are not sorted by time".to_string())
+        } else {
+            None
+        }
+    }
+}
+
+impl<T> Keyframe<T> {
+    pub fn new(time: f32, value: T) -> Self {
+        Self {
+            time,
+            value,
+            in_tangent: None,
+            out_tangent: None,
+        }
+    }
+}
+
+impl AnimationPlayer {
+    pub fn new(clip: impl Into<String>) -> Self {
+        Self {
+            clip: clip.into(),
+            speed: Self::default_speed(),
+            time: 0.0,
+            playing: Self::default_playing(),
+        }
+    }
+
+    /// Moves the playback position of `clip` forward by `delta_time` seconds.
+    pub fn advance(&mut self, clip: &AnimationClip, delta_time: f32) {
+        if !self.playing {
+            return;
+        }
+        let duration = clip.duration();
+        self.time += delta_time * self.speed;
+        match clip.repeat {
+            Repeat::Loop if duration > 0.0 => self.time = self.time.rem_euclid(duration),
+            Repeat::Loop => self.time = 0.0,
+            Repeat::Once => {
+                if !(0.0..=duration).contains(&self.time) {
+                    self.time = self.time.clamp(0.0, duration);
+                    self.playing = false;
+                }
+            }
+        }
+    }
+
+    fn default_speed() -> f32 {
+        1.0
+    }
+
+    fn default_playing() -> bool {
+        true
+    }
+}
+
+/// A value that can be keyframed.
+pub trait TrackValue: Copy {
+    fn blend(&self, other: &Self, t: f32) -> Self;
+    fn to_vector(self) -> nalgebra_glm::Vec4;
+    fn from_vector(vector: nalgebra_glm::Vec4) -> Self;
+
+    /// The equivalent value closest to `reference`, so splines take the short way.
+    fn align(&self, _reference: &Self) -> Self {
+        *self
+    }
+}
+
+impl TrackValue for nalgebra_glm::Vec3 {
+    fn blend(&self, other: &Self, t: f32) -> Self {
+        nalgebra_glm::lerp(self, other, t)
+    }
+
+    fn to_vector(self) -> nalgebra_glm::Vec4 {
+        nalgebra_glm::vec3_to_vec4(&self)
+    }
+
+    fn from_vector(vector: nalgebra_glm::Vec4) -> Self {
+        vector.xyz()
+    }
+}
+
+impl TrackValue for nalgebra_glm::Quat {
+    fn blend(&self, other: &Self, t: f32) -> Self {
+        nalgebra_glm::quat_slerp(self, &other.align(self), t)
+    }
+
+    fn to_vector(self) -> nalgebra_glm::Vec4 {
+        self.coords
+    }
+
+    fn from_vector(vector: nalgebra_glm::Vec4) -> Self {
+        nalgebra_glm::quat_normalize(&nalgebra_glm::Quat::from(vector))
+    }
+
+    fn align(&self, reference: &Self) -> Self {
+        if nalgebra_glm::quat_dot(self, reference) < 0.0 {
+            -*self
+        } else {
+            *self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra_glm::{vec3, Quat, Vec3};
+
+    use super::*;
+
+    fn assert_near(actual: Vec3, expected: Vec3) {
+        assert!(
+            nalgebra_glm::distance(&actual, &expected) < 1e-5,
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    /// The same rotation, either sign of the quaternion.
+    fn assert_same_rotation(actual: Quat, expected: Quat) {
+        let dot = nalgebra_glm::quat_dot(&actual, &expected).abs();
+        assert!((dot - 1.0).abs() < 1e-5, "{actual:?} != {expected:?}");
+    }
+
+    fn track(interpolation: Interpolation, keyframes: &[(f32, Vec3)]) -> Track<Vec3> {
+        let keyframes = keyframes
+            .iter()
+            .map(|&(time, value)| Keyframe::new(time, value))
+            .collect();
+        Track::new(interpolation, keyframes).unwrap()
+    }
+
+    fn rotation_y(degrees: f32) -> Quat {
+        nalgebra_glm::quat_angle_axis(degrees.to_radians(), &Vec3::y())
+    }
+
+    /// Moves along x from 0 to 2 over two seconds.
+    fn clip(repeat: Repeat) -> AnimationClip {
+        AnimationClip {
+            name: "slide".to_string(),
+            repeat,
+            translation: Some(track(
+                Interpolation::Linear,
+                &[(0.0, Vec3::zeros()), (2.0, vec3(2.0, 0.0, 0.0))],
+            )),
+            rotation: None,
+            scale: None,
+            joints: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn linear_tracks_blend_between_keyframes() {
+        let track = track(
+            Interpolation::Linear,
+            &[(0.0, Vec3::zeros()), (2.0, vec3(2.0, 4.0, 0.0))],
+        );
+        assert_near(track.sample(0.5), vec3(0.5, 1.0, 0.0));
+        assert_near(track.sample(2.0), vec3(2.0, 4.0, 0.0));
+    }
+
+    #[test]
+    fn step_tracks_hold_each_keyframe() {
+        let track = track(
+            Interpolation::Step,
+            &[(0.0, Vec3::zeros()), (1.0, Vec3::x()), (2.0, Vec3::y())],
+        );
+        assert_near(track.sample(0.99), Vec3::zeros());
+        assert_near(track.sample(1.0), Vec3::x());
+        assert_near(track.sample(1.5), Vec3::x());
+    }
+
+    #[test]
+    fn cubic_tracks_follow_the_tangents() {
+        let mut keyframes = vec![
+            Keyframe::new(0.0, Vec3::zeros()),
+            Keyframe::new(1.0, Vec3::x()),
+        ];
+        // Flat tangents ease in and out
+        keyframes[0].out_tangent = Some(Vec3::zeros());
+        keyframes[1].in_tangent = Some(Vec3::zeros());
+        let eased = Track::new(Interpolation::Cubic, keyframes.clone()).unwrap();
+        assert_near(eased.sample(0.25), vec3(0.15625, 0.0, 0.0));
+        assert_near(eased.sample(0.5), vec3(0.5, 0.0, 0.0));
+
+        // Tangents matching the slope give a straight line
+        keyframes[0].out_tangent = Some(Vec3::x());
+        keyframes[1].in_tangent = Some(Vec3::x());
+        let straight = Track::new(Interpolation::Cubic, keyframes).unwrap();
+        assert_near(straight.sample(0.25), vec3(0.25, 0.0, 0.0));
+
+        // Catmull-Rom tangents through evenly spaced keyframes do too
+        let catmull_rom = track(
+            Interpolation::Cubic,
+            &[
+                (0.0, Vec3::zeros()),
+                (1.0, Vec3::x()),
+                (2.0, vec3(2.0, 0.0, 0.0)),
+            ],
+        );
+        assert_near(catmull_rom.sample(0.25), vec3(0.25, 0.0, 0.0));
+        assert_near(catmull_rom.sample(1.5), vec3(1.5, 0.0, 0.0));
+    }
+
+    #[test]
+    fn rotations_slerp_the_short_way() {
+        let keyframes = vec![
+            Keyframe::new(0.0, Quat::identity()),
+            Keyframe::new(1.0, rotation_y(90.0)),
+        ];
+        let track = Track::new(Interpolation::Linear, keyframes).unwrap();
+        assert_same_rotation(track.sample(0.5), rotation_y(45.0));
+
+        // The same end rotation with the opposite sign
+        let keyframes = vec![
+            Keyframe::new(0.0, Quat::identity()),
+            Keyframe::new(1.0, -rotation_y(90.0)),
+        ];
+        let track = Track::new(Interpolation::Linear, keyframes).unwrap();
+        assert_same_rotation(track.sample(0.5), rotation_y(45.0));
+    }
+
+    #[test]
+    fn sampling_outside_the_track_holds_the_ends() {
+        let track = track(
+            Interpolation::Cubic,
+            &[(1.0, Vec3::x()), (2.0, Vec3::y()), (3.0, Vec3::z())],
+        );
+        assert_near(track.sample(-5.0), Vec3::x());
+        assert_near(track.sample(1.0), Vec3::x());
+        assert_near(track.sample(3.0), Vec3::z());
+        assert_near(track.sample(10.0), Vec3::z());
+    }
+
+    #[test]
+    fn looping_players_wrap_around() {
+        let clip = clip(Repeat::Loop);
+        let mut player = AnimationPlayer::new("slide");
+        player.advance(&clip, 2.5);
+        assert!((player.time - 0.5).abs() < 1e-6);
+        assert!(player.playing);
+
+        player.speed = -1.0;
+        player.advance(&clip, 1.0);
+        assert!((player.time - 1.5).abs() < 1e-6);
+        assert!(player.playing);
+    }
+
+    #[test]
+    fn players_playing_once_stop_at_the_end() {
+        let clip = clip(Repeat::Once);
+        let mut player = AnimationPlayer::new("slide");
+        player.advance(&clip, 1.0);
+        assert_eq!(player.time, 1.0);
+        assert!(player.playing);
+
+        player.advance(&clip, 5.0);
+        assert_eq!(player.time, 2.0);
+        assert!(!player.playing);
+        assert_near(
+            clip.sample(player.time, &Transform::default()).translation,
+            vec3(2.0, 0.0, 0.0),
+        );
+
+        // Stopped players stay put
+        player.advance(&clip, 1.0);
+        assert_eq!(player.time, 2.0);
+    }
+}
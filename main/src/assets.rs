@@ -0,0 +1,341 @@
+use std::{
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    path::PathBuf,
+};
+
+use crate::{GpuMesh, Material};
+
+/// Reference to an asset in an [`AssetStore`], only valid for the store it
+/// came from. Handles to unloaded assets are detected, not reused.
+pub(crate) struct Handle<T> {
+    index: u32,
+    /// Bumped every time the slot is reused
+    generation: u32,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index && self.generation == other.generation
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+impl<T> std::fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Handle({}v{})", self.index, self.generation)
+    }
+}
+
+/// What an asset was created from, assets with equal keys are shared.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum AssetKey {
+    /// Files, resolved against the scene's directory
+    Paths(Vec<PathBuf>),
+    /// Data without a file of its own, like an embedded mesh, compared in full
+    /// so different data never shares an asset
+    Content(Vec<u8>),
+}
+
+impl AssetKey {
+    /// Key of the data `write` feeds to a hasher.
+    pub fn content(write: impl FnOnce(&mut dyn Hasher)) -> Self {
+        let mut recorder = ContentRecorder::default();
+        write(&mut recorder);
+        Self::Content(recorder.0)
+    }
+}
+
+/// A hasher keeping everything written to it.
+#[derive(Default)]
+struct ContentRecorder(Vec<u8>);
+
+impl Hasher for ContentRecorder {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        hasher.write(&self.0);
+        hasher.finish()
+    }
+}
+
+struct Slot<T> {
+    generation: u32,
+    /// `None` once unloaded, until the slot is reused
+    asset: Option<T>,
+    /// `None` once unloaded
+    key: Option<AssetKey>,
+    /// Holders of a handle, unused at 0
+    references: u32,
+}
+
+/// Reference counted assets of one type. Assets stay loaded until they are
+/// unused and [`AssetStore::unload_unused`] is called, so releasing and
+/// loading the same key again in between reuses them.
+pub(crate) struct AssetStore<T> {
+    slots: Vec<Slot<T>>,
+    /// Slots of unloaded assets
+    free: Vec<u32>,
+    keys: HashMap<AssetKey, Handle<T>>,
+}
+
+impl<T> Default for AssetStore<T> {
+    fn default() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+            keys: HashMap::new(),
+        }
+    }
+}
+
+impl<T> AssetStore<T> {
+    /// The asset loaded for `key`, with another reference, or the one
+    /// `create` returns if there is none yet.
+    pub fn load(&mut self, key: AssetKey, create: impl FnOnce() -> T) -> Handle<T> {
+        if let Some(&handle) = self.keys.get(&key) {
+            self.retain(handle);
+            return handle;
+        }
+        let asset = create();
+        let index = match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.generation += 1;
+                slot.asset = Some(asset);
+                slot.key = Some(key.clone());
+                slot.references = 1;
+                index
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    asset: Some(asset),
+                    key: Some(key.clone()),
+                    references: 1,
+                });
+                self.slots.len() as u32 - 1
+            }
+        };
+        let handle = Handle {
+            index,
+            generation: self.slots[index as usize].generation,
+            marker: PhantomData,
+        };
+        self.keys.insert(key, handle);
+        handle
+    }
+
+    fn slot(&self, handle: Handle<T>) -> Option<&Slot<T>> {
+        self.slots
+            .get(handle.index as usize)
+            .filter(|slot| slot.generation == handle.generation && slot.asset.is_some())
+    }
+
+    fn slot_mut(&mut self, handle: Handle<T>) -> Option<&mut Slot<T>> {
+        self.slots
+            .get_mut(handle.index as usize)
+            .filter(|slot| slot.generation == handle.generation && slot.asset.is_some())
+    }
+
+    /// The asset, or `None` if it has been unloaded.
+    pub fn get(&self, handle: Handle<T>) -> Option<&T> {
+        self.slot(handle).and_then(|slot| slot.asset.as_ref())
+    }
+
+    pub fn get_mut(&mut self, handle: Handle<T>) -> Option<&mut T> {
+        self.slot_mut(handle).and_then(|slot| slot.asset.as_mut())
+    }
+
+    /// Adds a reference for another holder of `handle`.
+    pub fn retain(&mut self, handle: Handle<T>) {
+        if let Some(slot) = self.slot_mut(handle) {
+            slot.references += 1;
+        }
+    }
+
+    /// Drops a reference, the asset is unloaded with the next
+    /// [`AssetStore::unload_unused`] if it was the last one.
+    pub fn release(&mut self, handle: Handle<T>) {
+        if let Some(slot) = self.slot_mut(handle) {
+            slot.references = slot.references.saturating_sub(1);
+        }
+    }
+
+    /// Drops the assets nothing references anymore, returning how many.
+    pub fn unload_unused(&mut self) -> usize {
+        let mut unloaded = 0;
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            if slot.references > 0 || slot.asset.is_none() {
+                continue;
+            }
+            slot.asset = None;
+            if let Some(key) = slot.key.take() {
+                self.keys.remove(&key);
+            }
+            self.free.push(index as u32);
+            unloaded += 1;
+        }
+        unloaded
+    }
+
+    /// Number of loaded assets, used or not.
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+}
+
+impl<T> std::ops::Index<Handle<T>> for AssetStore<T> {
+    type Output = T;
+
+    fn index(&self, handle: Handle<T>) -> &T {
+        self.get(handle).expect("Asset handle outlived its asset!")
+    }
+}
+
+impl<T> std::ops::IndexMut<Handle<T>> for AssetStore<T> {
+    fn index_mut(&mut self, handle: Handle<T>) -> &mut T {
+        self.get_mut(handle)
+            .expect("Asset handle outlived its asset!")
+    }
+}
+
+/// Everything a scene is drawn with that can be shared between objects,
+/// each loaded once per key.
+#[derive(Default)]
+pub(crate) struct Assets {
+    pub meshes: AssetStore<GpuMesh>,
+    /// Skybox cube maps
+    pub textures: AssetStore<wgpu::TextureView>,
+    /// Modules keyed by their source, shared between pipelines
+    pub shaders: AssetStore<wgpu::ShaderModule>,
+    pub materials: AssetStore<Material>,
+}
+
+impl Assets {
+    /// Drops the assets nothing references anymore, in all stores.
+    pub fn unload_unused(&mut self) {
+        let unloaded = self.meshes.unload_unused()
+            + self.textures.unload_unused()
+            + self.shaders.unload_unused()
+            + self.materials.unload_unused();
+        if unloaded > 0 {
+            tracing::debug!(
+                "Unloaded {unloaded} unused assets, {} meshes, {} textures, {} shaders and {} materials remain",
+                self.meshes.len(),
+                self.textures.len(),
+                self.shaders.len(),
+                self.materials.len()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(name: &str) -> AssetKey {
+        AssetKey::Paths(vec![PathBuf::from(name)])
+    }
+
+    #[test]
+    fn equal_keys_share_an_asset() {
+        let mut store = AssetStore::default();
+        let mut created = 0;
+        let mut create = |value: u32| {
+            created += 1;
+            value
+        };
+        let first = store.load(key("a"), || create(1));
+        let second = store.load(key("a"), || create(2));
+        let other = store.load(key("b"), || create(3));
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+        assert_eq!((store[first], store[other]), (1, 3));
+        assert_eq!(created, 2);
+        assert_eq!(store.len(), 2);
+    }
+
+    #[test]
+    fn content_keys_compare_the_whole_content() {
+        let content = |bytes: &[u8]| AssetKey::content(|hasher| hasher.write(bytes));
+        assert_eq!(content(b"mesh"), content(b"mesh"));
+        assert_ne!(content(b"mesh"), content(b"mesh2"));
+        assert_ne!(
+            AssetKey::content(|hasher| hasher.write_u32(1)),
+            AssetKey::content(|hasher| hasher.write_u32(2))
+        );
+    }
+
+    #[test]
+    fn assets_are_unloaded_after_the_last_release() {
+        let mut store = AssetStore::default();
+        let handle = store.load(key("a"), || 1);
+        store.load(key("a"), || 1);
+        store.retain(handle);
+
+        store.release(handle);
+        store.release(handle);
+        assert_eq!(store.unload_unused(), 0);
+        assert_eq!(store.get(handle), Some(&1));
+
+        store.release(handle);
+        assert_eq!(store.unload_unused(), 1);
+        assert_eq!(store.get(handle), None);
+        assert_eq!(store.len(), 0);
+        assert_eq!(store.unload_unused(), 0);
+    }
+
+    #[test]
+    fn released_assets_are_reused_until_unloaded() {
+        let mut store = AssetStore::default();
+        let handle = store.load(key("a"), || 1);
+        store.release(handle);
+        assert_eq!(store.load(key("a"), || 2), handle);
+        assert_eq!(store[handle], 1);
+        assert_eq!(store.unload_unused(), 0);
+    }
+
+    #[test]
+    fn handles_to_unloaded_assets_go_stale() {
+        let mut store = AssetStore::default();
+        let stale = store.load(key("a"), || 1);
+        store.release(stale);
+        store.unload_unused();
+
+        // The slot is reused with a new generation
+        let handle = store.load(key("b"), || 2);
+        assert_eq!(handle.index, stale.index);
+        assert_ne!(handle, stale);
+        assert_eq!(store.get(stale), None);
+        assert_eq!(store.get(handle), Some(&2));
+
+        // Stale handles don't touch the new asset
+        store.release(stale);
+        store.retain(stale);
+        assert_eq!(store.unload_unused(), 0);
+        assert_eq!(store.get_mut(stale), None);
+    }
+}
@@ -0,0 +1,299 @@
+/// Axis-aligned box around a set of points.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BoundingBox {
+    pub min: nalgebra_glm::Vec3,
+    pub max: nalgebra_glm::Vec3,
+}
+
+/// Sphere around a set of points.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BoundingSphere {
+    pub center: nalgebra_glm::Vec3,
+    pub radius: f32,
+}
+
+/// Bounding volumes of a mesh, in the mesh's own space.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Bounds {
+    pub bounding_box: BoundingBox,
+    pub sphere: BoundingSphere,
+}
+
+/// The six planes enclosing what a camera sees, normals pointing inwards.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    /// Normal in xyz and distance in w, normalized
+    planes: [nalgebra_glm::Vec4; 6],
+    /// Computes the clip space w, the distance along the view direction
+    depth: nalgebra_glm::Vec4,
+    /// Clip space height of a unit at a distance of 1
+    vertical_scale: f32,
+}
+
+impl BoundingBox {
+    /// The smallest box containing all `points`, empty at the origin if there are none.
+    pub fn from_points(points: &[nalgebra_glm::Vec3]) -> Self {
+        let Some(first) = points.first() else {
+            return Self::default();
+        };
+        points.iter().fold(
+            Self {
+                min: *first,
+                max: *first,
+            },
+            |bounding_box, point| Self {
+                min: bounding_box.min.inf(point),
+                max: bounding_box.max.sup(point),
+            },
+        )
+    }
+
+    pub fn center(&self) -> nalgebra_glm::Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    /// Half the size along each axis.
+    pub fn extents(&self) -> nalgebra_glm::Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    /// The axis-aligned box around this one after transforming it by `matrix`.
+    pub fn transform(&self, matrix: &nalgebra_glm::Mat4) -> Self {
+        let center = (matrix * self.center().push(1.0)).xyz();
+        let extents = matrix.fixed_view::<3, 3>(0, 0).abs() * self.extents();
+        Self {
+            min: center - extents,
+            max: center + extents,
+        }
+    }
+}
+
+impl BoundingSphere {
+    /// A sphere centered on the bounding box of `points`.
+    pub fn from_points(points: &[nalgebra_glm::Vec3]) -> Self {
+        let center = BoundingBox::from_points(points).center();
+        Self {
+            center,
+            radius: points
+                .iter()
+                .map(|point| nalgebra_glm::distance(point, &center))
+                .fold(0.0, f32::max),
+        }
+    }
+
+    /// The sphere around this one after transforming it by `matrix`.
+    pub fn transform(&self, matrix: &nalgebra_glm::Mat4) -> Self {
+        let scale = (0..3)
+            .map(|axis| matrix.fixed_view::<3, 1>(0, axis).norm())
+            .fold(0.0, f32::max);
+        Self {
+            center: (matrix * self.center.push(1.0)).xyz(),
+            radius: self.radius * scale,
+        }
+    }
+}
+
+impl Bounds {
+    pub fn from_points(points: &[nalgebra_glm::Vec3]) -> Self {
+        Self {
+            bounding_box: BoundingBox::from_points(points),
+            sphere: BoundingSphere::from_points(points),
+        }
+    }
+}
+
+impl Frustum {
+    /// Extracts the planes from a view-projection matrix with a depth range of 0 to 1.
+    pub fn from_view_projection(view_projection: &nalgebra_glm::Mat4) -> Self {
+        let row = |index: usize| view_projection.row(index).transpose();
+        let planes = [
+            row(3) + row(0),
+            row(3) - row(0),
+            row(3) + row(1),
+            row(3) - row(1),
+            row(2),
+            row(3) - row(2),
+        ]
+        .map(|plane| plane / plane.xyz().norm());
+        Self {
+            planes,
+            depth: row(3),
+            vertical_scale: row(1).xyz().norm(),
+        }
+    }
+
+    /// Distance of `point` from the camera along the view direction.
+    pub fn depth(&self, point: &nalgebra_glm::Vec3) -> f32 {
+        self.depth.xyz().dot(point) + self.depth.w
+    }
+
+    /// Diameter of `sphere` on screen as a fraction of the viewport height,
+    /// infinite if the camera is inside it.
+    pub fn screen_size(&self, sphere: &BoundingSphere) -> f32 {
+        let distance = self.depth(&sphere.center);
+        if distance <= sphere.radius {
+            return f32::INFINITY;
+        }
+        sphere.radius * self.vertical_scale / distance
+    }
+
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.xyz().dot(&sphere.center) + plane.w >= -sphere.radius)
+    }
+
+    /// Conservative, boxes near the frustum's edges may pass without touching it.
+    pub fn intersects_box(&self, bounding_box: &BoundingBox) -> bool {
+        self.planes.iter().all(|plane| {
+            // The corner furthest along the plane's normal
+            let corner = nalgebra_glm::Vec3::from_fn(|axis, _| {
+                if plane[axis] >= 0.0 {
+                    bounding_box.max[axis]
+                } else {
+                    bounding_box.min[axis]
+                }
+            });
+            plane.xyz().dot(&corner) + plane.w >= 0.0
+        })
+    }
+
+    /// Whether a mesh with `bounds` may be visible when drawn with `model`,
+    /// testing the cheaper sphere first.
+    pub fn intersects(&self, bounds: &Bounds, model: &nalgebra_glm::Mat4) -> bool {
+        self.intersects_sphere(&bounds.sphere.transform(model))
+            && self.intersects_box(&bounds.bounding_box.transform(model))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Looking along +z from the origin with a 90° field of view, so the side
+    /// planes are x = ±z and y = ±z, between z = 1 and z = 10.
+    fn frustum() -> Frustum {
+        let projection =
+            nalgebra_glm::perspective_lh_zo(1.0, std::f32::consts::FRAC_PI_2, 1.0, 10.0);
+        Frustum::from_view_projection(&projection)
+    }
+
+    fn sphere(x: f32, y: f32, z: f32) -> BoundingSphere {
+        BoundingSphere {
+            center: nalgebra_glm::vec3(x, y, z),
+            radius: 1.0,
+        }
+    }
+
+    fn unit_box(x: f32, y: f32, z: f32) -> BoundingBox {
+        let center = nalgebra_glm::vec3(x, y, z);
+        BoundingBox {
+            min: center.add_scalar(-1.0),
+            max: center.add_scalar(1.0),
+        }
+    }
+
+    /// A center past each plane and one on it, in the order left, right,
+    /// bottom, top, near and far.
+    const OUTSIDE: [[f32; 3]; 6] = [
+        [-8.0, 0.0, 5.0],
+        [8.0, 0.0, 5.0],
+        [0.0, -8.0, 5.0],
+        [0.0, 8.0, 5.0],
+        [0.0, 0.0, -1.0],
+        [0.0, 0.0, 12.0],
+    ];
+    const STRADDLING: [[f32; 3]; 6] = [
+        [-5.0, 0.0, 5.0],
+        [5.0, 0.0, 5.0],
+        [0.0, -5.0, 5.0],
+        [0.0, 5.0, 5.0],
+        [0.0, 0.0, 1.0],
+        [0.0, 0.0, 10.0],
+    ];
+
+    #[test]
+    fn spheres_inside_the_frustum_intersect() {
+        assert!(frustum().intersects_sphere(&sphere(0.0, 0.0, 5.0)));
+        assert!(frustum().intersects_sphere(&sphere(2.0, -2.0, 3.0)));
+    }
+
+    #[test]
+    fn spheres_outside_any_plane_are_culled() {
+        for [x, y, z] in OUTSIDE {
+            assert!(
+                !frustum().intersects_sphere(&sphere(x, y, z)),
+                "{x} {y} {z}"
+            );
+        }
+    }
+
+    #[test]
+    fn spheres_straddling_a_plane_intersect() {
+        for [x, y, z] in STRADDLING {
+            assert!(frustum().intersects_sphere(&sphere(x, y, z)), "{x} {y} {z}");
+        }
+    }
+
+    #[test]
+    fn boxes_inside_the_frustum_intersect() {
+        assert!(frustum().intersects_box(&unit_box(0.0, 0.0, 5.0)));
+        assert!(frustum().intersects_box(&unit_box(2.0, -2.0, 3.0)));
+    }
+
+    #[test]
+    fn boxes_outside_any_plane_are_culled() {
+        for [x, y, z] in OUTSIDE {
+            assert!(!frustum().intersects_box(&unit_box(x, y, z)), "{x} {y} {z}");
+        }
+    }
+
+    #[test]
+    fn boxes_straddling_a_plane_intersect() {
+        for [x, y, z] in STRADDLING {
+            assert!(frustum().intersects_box(&unit_box(x, y, z)), "{x} {y} {z}");
+        }
+    }
+
+    #[test]
+    fn intersects_applies_the_model_matrix() {
+        let bounds = Bounds {
+            bounding_box: unit_box(0.0, 0.0, 0.0),
+            sphere: sphere(0.0, 0.0, 0.0),
+        };
+        let inside = nalgebra_glm::translation(&nalgebra_glm::vec3(0.0, 0.0, 5.0));
+        let outside = nalgebra_glm::translation(&nalgebra_glm::vec3(0.0, 0.0, -5.0));
+        assert!(frustum().intersects(&bounds, &inside));
+        assert!(!frustum().intersects(&bounds, &outside));
+    }
+
+    #[test]
+    fn screen_size_is_the_fraction_of_the_viewport_height() {
+        let frustum = frustum();
+        assert!((frustum.screen_size(&sphere(0.0, 0.0, 5.0)) - 0.2).abs() < 1e-5);
+        assert!((frustum.screen_size(&sphere(3.0, 0.0, 2.0)) - 0.5).abs() < 1e-5);
+        assert_eq!(frustum.screen_size(&sphere(0.0, 0.0, 0.5)), f32::INFINITY);
+    }
+
+    #[test]
+    fn box_transform_rotates_and_translates_the_extents() {
+        let bounding_box = BoundingBox {
+            min: nalgebra_glm::vec3(-1.0, -2.0, -3.0),
+            max: nalgebra_glm::vec3(1.0, 2.0, 3.0),
+        };
+        let matrix = nalgebra_glm::translation(&nalgebra_glm::vec3(10.0, 0.0, 0.0))
+            * nalgebra_glm::rotation(std::f32::consts::FRAC_PI_2, &nalgebra_glm::Vec3::z());
+        let transformed = bounding_box.transform(&matrix);
+        assert!((transformed.min - nalgebra_glm::vec3(8.0, -1.0, -3.0)).norm() < 1e-5);
+        assert!((transformed.max - nalgebra_glm::vec3(12.0, 1.0, 3.0)).norm() < 1e-5);
+    }
+
+    #[test]
+    fn sphere_transform_scales_by_the_largest_axis() {
+        let matrix = nalgebra_glm::translation(&nalgebra_glm::vec3(1.0, 2.0, 3.0))
+            * nalgebra_glm::scaling(&nalgebra_glm::vec3(1.0, 3.0, 2.0));
+        let transformed = sphere(1.0, 0.0, 0.0).transform(&matrix);
+        assert!((transformed.center - nalgebra_glm::vec3(2.0, 2.0, 3.0)).norm() < 1e-5);
+        assert!((transformed.radius - 3.0).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+
+/// A perspective camera looking at a fixed target.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Camera {
+    pub eye: nalgebra_glm::Vec3,
+    pub target: nalgebra_glm::Vec3,
+    pub up: nalgebra_glm::Vec3,
+    /// Vertical field of view in degrees
+    pub fov_y: f32,
+    pub z_near: f32,
+    pub z_far: f32,
+}
+
+impl Camera {
+    pub fn looking_at(eye: nalgebra_glm::Vec3, target: nalgebra_glm::Vec3) -> Self {
+        Self {
+            eye,
+            target,
+            ..Default::default()
+        }
+    }
+
+    /// A camera on a circle around the origin, `angle` degrees away from the default view.
+    pub fn orbiting(angle: f32, distance: f32, height: f32) -> Self {
+        let angle = angle.to_radians();
+        Self::looking_at(
+            nalgebra_glm::vec3(distance * angle.sin(), height, distance * angle.cos()),
+            nalgebra_glm::Vec3::zeros(),
+        )
+    }
+
+    pub fn view(&self) -> nalgebra_glm::Mat4 {
+        nalgebra_glm::look_at_lh(&self.eye, &self.target, &self.up)
+    }
+
+    pub fn projection(&self, aspect_ratio: f32) -> nalgebra_glm::Mat4 {
+        nalgebra_glm::perspective_lh_zo(
+            aspect_ratio,
+            self.fov_y.to_radians(),
+            self.z_near,
+            self.z_far,
+        )
+    }
+
+    pub fn view_projection(&self, aspect_ratio: f32) -> nalgebra_glm::Mat4 {
+        self.projection(aspect_ratio) * self.view()
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            eye: nalgebra_glm::vec3(0.0, 0.0, 3.0),
+            target: nalgebra_glm::Vec3::zeros(),
+            up: nalgebra_glm::Vec3::y(),
+            fov_y: 80.0,
+            z_near: 0.1,
+            z_far: 1000.0,
+        }
+    }
+}
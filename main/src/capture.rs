@@ -0,0 +1,320 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    AppConfig, Camera, Duration, GBuffer, Renderer, Scene, SimulationClock, UniformBinding,
+};
+
+/// A color and depth target the scene can be rendered into without a surface,
+/// with a buffer to read the result back on the CPU.
+pub struct OffscreenTarget {
+    pub camera: Camera,
+    width: u32,
+    height: u32,
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    msaa_view: Option<wgpu::TextureView>,
+    depth_view: wgpu::TextureView,
+    gbuffer: Option<GBuffer>,
+    uniform: UniformBinding,
+    /// Level of detail of every object, see [`crate::GpuScene::select_lods`]
+    lods: Vec<usize>,
+    readback_buffer: wgpu::Buffer,
+    padded_bytes_per_row: u32,
+}
+
+impl OffscreenTarget {
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+}
+
+impl Renderer {
+    pub fn create_offscreen_target(
+        &self,
+        width: u32,
+        height: u32,
+        camera: Camera,
+    ) -> OffscreenTarget {
+        let texture = self.gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Offscreen Color Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.target.color,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        // Rows copied into a buffer have to be aligned
+        let unpadded_bytes_per_row = width * 4;
+        let padded_bytes_per_row = unpadded_bytes_per_row
+            .div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let readback_buffer = self.gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Offscreen Readback Buffer"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let depth_view = self.gpu.create_depth_texture(width, height, self.target);
+        OffscreenTarget {
+            camera,
+            width,
+            height,
+            view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            texture,
+            msaa_view: self.gpu.create_msaa_texture(width, height, self.target),
+            gbuffer: self.create_gbuffer(width, height, &depth_view),
+            depth_view,
+            uniform: UniformBinding::new(&self.gpu.device, &self.scene.uniform_layout),
+            lods: Vec::new(),
+            readback_buffer,
+            padded_bytes_per_row,
+        }
+    }
+
+    /// Renders the scene into `target` and reads the result back as an RGBA image.
+    pub fn render_offscreen(&mut self, target: &mut OffscreenTarget) -> image::RgbaImage {
+        let started = crate::Instant::now();
+        let aspect_ratio = target.width as f32 / target.height.max(1) as f32;
+        let frustum = self.update_uniform(&mut target.uniform, &target.camera, aspect_ratio);
+        self.scene.select_lods(&frustum, &mut target.lods);
+
+        let mut encoder = self
+            .gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Offscreen Encoder"),
+            });
+        let draw_stats = self.encode_frame(
+            &mut encoder,
+            &target.view,
+            target.msaa_view.as_ref(),
+            &target.depth_view,
+            target.gbuffer.as_ref(),
+            &target.uniform,
+            &frustum,
+            &target.lods,
+            (target.width, target.height),
+        );
+        encoder.copy_texture_to_buffer(
+            target.texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &target.readback_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(target.padded_bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: target.width,
+                height: target.height,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.submit_frame(encoder, started, draw_stats);
+
+        let buffer_slice = target.readback_buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.gpu.device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .expect("Readback buffer was dropped!")
+            .expect("Failed to map readback buffer!");
+
+        let swap_red_blue = matches!(
+            self.target.color,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+        );
+        let mut pixels = Vec::with_capacity((target.width * target.height * 4) as usize);
+        {
+            let data = buffer_slice.get_mapped_range();
+            for row in data.chunks(target.padded_bytes_per_row as usize) {
+                let row = &row[..(target.width * 4) as usize];
+                if swap_red_blue {
+                    pixels.extend(
+                        row.chunks(4)
+                            .flat_map(|bgra| [bgra[2], bgra[1], bgra[0], bgra[3]]),
+                    );
+                } else {
+                    pixels.extend_from_slice(row);
+                }
+            }
+        }
+        target.readback_buffer.unmap();
+
+        image::RgbaImage::from_raw(target.width, target.height, pixels)
+            .expect("Readback size mismatch!")
+    }
+}
+
+/// How a recording is written to disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordingFormat {
+    /// Numbered PNG files in a directory
+    PngSequence,
+    Gif,
+    /// Animated PNG
+    Apng,
+}
+
+impl RecordingFormat {
+    /// A GIF for `.gif` files, an APNG for `.png` and `.apng` files, and a
+    /// PNG sequence for anything else, which is treated as a directory.
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("gif") => Self::Gif,
+            Some("png" | "apng") => Self::Apng,
+            _ => Self::PngSequence,
+        }
+    }
+}
+
+impl std::str::FromStr for RecordingFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().replace('_', "-").as_str() {
+            "png-sequence" | "png" => Ok(Self::PngSequence),
+            "gif" => Ok(Self::Gif),
+            "apng" => Ok(Self::Apng),
+            _ => Err(format!(
+                "unknown recording format '{value}', expected png-sequence, gif or apng"
+            )),
+        }
+    }
+}
+
+/// A fixed number of frames rendered without a window. The scene advances by
+/// `1 / frame_rate` seconds of simulated time per frame, independent of how
+/// long rendering takes, so the same config always gives the same frames.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recording {
+    pub frames: u32,
+    pub frame_rate: u32,
+    pub format: RecordingFormat,
+    pub output: PathBuf,
+}
+
+impl Recording {
+    pub fn delta_time(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.frame_rate.max(1) as f64)
+    }
+}
+
+/// Renders `recording` of `scene` at `width` x `height` and writes it to its output.
+pub fn render_frames(
+    config: &AppConfig,
+    scene: &Scene,
+    (width, height): (u32, u32),
+    recording: &Recording,
+) -> Result<(), Box<dyn Error>> {
+    let mut writer = FrameWriter::create(recording, (width, height))?;
+
+    let mut renderer = pollster::block_on(Renderer::new_headless(&config.renderer));
+    // The HUD is left hidden, its wall-clock timings would differ between runs
+    renderer.set_scene_settings(config.scene);
+    renderer.set_scene(scene)?;
+    let camera = scene.camera.unwrap_or(config.camera);
+    let mut target = renderer.create_offscreen_target(width, height, camera);
+    let mut clock = SimulationClock::new(config.simulation);
+    for frame in 0..recording.frames {
+        writer.write(frame, &renderer.render_offscreen(&mut target))?;
+        renderer.update(&mut clock, recording.delta_time());
+    }
+    writer.finish()?;
+    tracing::info!(
+        "Recorded {} frames to {}",
+        recording.frames,
+        recording.output.display()
+    );
+    Ok(())
+}
+
+enum FrameWriter {
+    PngSequence(PathBuf),
+    Gif {
+        encoder: image::codecs::gif::GifEncoder<BufWriter<File>>,
+        delay: image::Delay,
+    },
+    Apng(png::Writer<BufWriter<File>>),
+}
+
+impl FrameWriter {
+    fn create(recording: &Recording, (width, height): (u32, u32)) -> Result<Self, Box<dyn Error>> {
+        if recording.format == RecordingFormat::PngSequence {
+            std::fs::create_dir_all(&recording.output)?;
+            return Ok(Self::PngSequence(recording.output.clone()));
+        }
+
+        if let Some(parent) = recording.output.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = BufWriter::new(File::create(&recording.output)?);
+        match recording.format {
+            RecordingFormat::Gif => {
+                let mut encoder = image::codecs::gif::GifEncoder::new(file);
+                encoder.set_repeat(image::codecs::gif::Repeat::Infinite)?;
+                Ok(Self::Gif {
+                    encoder,
+                    delay: image::Delay::from_numer_denom_ms(1000, recording.frame_rate.max(1)),
+                })
+            }
+            RecordingFormat::Apng => {
+                let mut encoder = png::Encoder::new(file, width, height);
+                encoder.set_color(png::ColorType::Rgba);
+                encoder.set_depth(png::BitDepth::Eight);
+                // Zero plays loops forever
+                encoder.set_animated(recording.frames.max(1), 0)?;
+                let frame_rate = recording.frame_rate.clamp(1, u16::MAX as u32) as u16;
+                encoder.set_frame_delay(1, frame_rate)?;
+                Ok(Self::Apng(encoder.write_header()?))
+            }
+            RecordingFormat::PngSequence => unreachable!(),
+        }
+    }
+
+    fn write(&mut self, index: u32, frame: &image::RgbaImage) -> Result<(), Box<dyn Error>> {
+        match self {
+            Self::PngSequence(directory) => {
+                let path = directory.join(format!("frame_{index:05}.png"));
+                frame.save(&path)?;
+                tracing::info!("Wrote {}", path.display());
+            }
+            Self::Gif { encoder, delay } => {
+                encoder.encode_frame(image::Frame::from_parts(frame.clone(), 0, 0, *delay))?;
+            }
+            Self::Apng(writer) => writer.write_image_data(frame.as_raw())?,
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), Box<dyn Error>> {
+        match self {
+            Self::PngSequence(_) => {}
+            // Dropping the encoder writes the trailer
+            Self::Gif { encoder, .. } => drop(encoder),
+            Self::Apng(writer) => writer.finish()?,
+        }
+        Ok(())
+    }
+}
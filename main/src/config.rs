@@ -0,0 +1,216 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Camera, DisplaySettings, HudSettings, RendererSettings, SceneSettings, SimulationSettings,
+    WindowSettings,
+};
+
+/// Every user-adjustable setting of the app, persisted as TOML in the platform
+/// config directory, or in `localStorage` on the web.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppConfig {
+    pub display: DisplaySettings,
+    pub window: WindowSettings,
+    pub renderer: RendererSettings,
+    pub scene: SceneSettings,
+    pub simulation: SimulationSettings,
+    pub hud: HudSettings,
+    pub camera: Camera,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    Serialize(toml::ser::Error),
+    /// The browser refused access to `localStorage`
+    Storage(String),
+    /// A setting has a value the app can't use
+    Invalid(String),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "failed to access config file: {error}"),
+            Self::Parse(error) => write!(f, "invalid config: {error}"),
+            Self::Serialize(error) => write!(f, "failed to serialize config: {error}"),
+            Self::Storage(error) => write!(f, "failed to access local storage: {error}"),
+            Self::Invalid(error) => write!(f, "invalid config: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl AppConfig {
+    pub fn from_toml(source: &str) -> Result<Self, ConfigError> {
+        let config: Self = toml::from_str(source).map_err(ConfigError::Parse)?;
+        SimulationSettings::validate_time_scale(config.simulation.time_scale)
+            .map_err(ConfigError::Invalid)?;
+        Ok(config)
+    }
+
+    pub fn to_toml(&self) -> Result<String, ConfigError> {
+        toml::to_string(self).map_err(ConfigError::Serialize)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl AppConfig {
+    /// `<config dir>/wgpu_example/config.toml`, if the platform has a config directory.
+    pub fn default_path() -> Option<std::path::PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("wgpu_example").join("config.toml"))
+    }
+
+    /// Loads the config at `path`, falling back to the defaults when the file doesn't exist.
+    pub fn load(path: &std::path::Path) -> Result<Self, ConfigError> {
+        match std::fs::read_to_string(path) {
+            Ok(source) => Self::from_toml(&source),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(ConfigError::Io(error)),
+        }
+    }
+
+    pub fn save(&self, path: &std::path::Path) -> Result<(), ConfigError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(ConfigError::Io)?;
+        }
+        std::fs::write(path, self.to_toml()?).map_err(ConfigError::Io)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl AppConfig {
+    const STORAGE_KEY: &'static str = "wgpu_example.config";
+
+    fn local_storage() -> Result<web_sys::Storage, ConfigError> {
+        web_sys::window()
+            .ok_or_else(|| ConfigError::Storage("no window".into()))?
+            .local_storage()
+            .map_err(|error| ConfigError::Storage(format!("{error:?}")))?
+            .ok_or_else(|| ConfigError::Storage("local storage is unavailable".into()))
+    }
+
+    /// Loads the config from `localStorage`, falling back to the defaults when none is stored.
+    pub fn load() -> Result<Self, ConfigError> {
+        let source = Self::local_storage()?
+            .get_item(Self::STORAGE_KEY)
+            .map_err(|error| ConfigError::Storage(format!("{error:?}")))?;
+        match source {
+            Some(source) => Self::from_toml(&source),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), ConfigError> {
+        Self::local_storage()?
+            .set_item(Self::STORAGE_KEY, &self.to_toml()?)
+            .map_err(|error| ConfigError::Storage(format!("{error:?}")))
+    }
+}
+
+/// Where an [`AppConfig`] is loaded from and saved to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ConfigStore {
+    /// The config is not persisted
+    #[default]
+    None,
+    #[cfg(not(target_arch = "wasm32"))]
+    File(std::path::PathBuf),
+    #[cfg(target_arch = "wasm32")]
+    LocalStorage,
+}
+
+impl ConfigStore {
+    /// The config file in the platform config directory, or `localStorage` on the web.
+    pub fn platform_default() -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        return AppConfig::default_path().map_or(Self::None, Self::File);
+
+        #[cfg(target_arch = "wasm32")]
+        return Self::LocalStorage;
+    }
+
+    pub fn load(&self) -> Result<AppConfig, ConfigError> {
+        match self {
+            Self::None => Ok(AppConfig::default()),
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::File(path) => AppConfig::load(path),
+            #[cfg(target_arch = "wasm32")]
+            Self::LocalStorage => AppConfig::load(),
+        }
+    }
+
+    pub fn save(&self, config: &AppConfig) -> Result<(), ConfigError> {
+        match self {
+            Self::None => Ok(()),
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::File(path) => config.save(path),
+            #[cfg(target_arch = "wasm32")]
+            Self::LocalStorage => config.save(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_round_trips() {
+        let config = AppConfig::default();
+        let source = config.to_toml().unwrap();
+        assert_eq!(AppConfig::from_toml(&source).unwrap(), config);
+    }
+
+    #[test]
+    fn changed_config_round_trips() {
+        let mut config = AppConfig::default();
+        config.display.present_mode = wgpu::PresentMode::Mailbox;
+        config.display.target_fps = Some(30);
+        config.simulation.tick_rate = 120;
+        config.simulation.time_scale = 0.5;
+        config.hud.visible = true;
+        config.camera = Camera::orbiting(45.0, 5.0, 1.0);
+        let source = config.to_toml().unwrap();
+        assert_eq!(AppConfig::from_toml(&source).unwrap(), config);
+    }
+
+    #[test]
+    fn missing_settings_keep_their_defaults() {
+        let config = AppConfig::from_toml("[display]\ntarget_fps = 144\n").unwrap();
+        assert_eq!(config.display.target_fps, Some(144));
+        assert_eq!(
+            config.display.present_mode,
+            DisplaySettings::default().present_mode
+        );
+        assert_eq!(config.simulation, SimulationSettings::default());
+        assert_eq!(AppConfig::from_toml("").unwrap(), AppConfig::default());
+    }
+
+    #[test]
+    fn invalid_config_is_rejected() {
+        assert!(matches!(
+            AppConfig::from_toml("[display]\ntarget_fps = \"fast\"\n"),
+            Err(ConfigError::Parse(_))
+        ));
+        assert!(matches!(
+            AppConfig::from_toml("display = ["),
+            Err(ConfigError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_time_scale_is_rejected() {
+        for time_scale in ["-1.0", "nan", "inf", "-inf"] {
+            let source = format!("[simulation]\ntime_scale = {time_scale}\n");
+            assert!(matches!(
+                AppConfig::from_toml(&source),
+                Err(ConfigError::Invalid(_))
+            ));
+        }
+        assert!(AppConfig::from_toml("[simulation]\ntime_scale = 0.0\n").is_ok());
+    }
+}
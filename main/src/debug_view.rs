@@ -0,0 +1,292 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{DrawStats, TargetFormat, UniformBinding};
+
+/// What the scene pass shows in place of the shaded surfaces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DebugView {
+    #[default]
+    Shaded,
+    /// Triangle edges, with hidden ones showing through
+    Wireframe,
+    /// World-space normals mapped from -1..1 to 0..1 per channel
+    Normals,
+    /// Distance from the camera, read back from the depth buffer
+    Depth,
+    /// A checkerboard in texture space, tinted by the coordinates
+    Uvs,
+    /// How many triangles cover each pixel, from dark red to white
+    Overdraw,
+}
+
+impl DebugView {
+    /// Views cycled through at runtime.
+    pub const ALL: [DebugView; 6] = [
+        DebugView::Shaded,
+        DebugView::Wireframe,
+        DebugView::Normals,
+        DebugView::Depth,
+        DebugView::Uvs,
+        DebugView::Overdraw,
+    ];
+
+    pub fn next(&self) -> Self {
+        let index = Self::ALL
+            .iter()
+            .position(|view| view == self)
+            .map_or(0, |index| (index + 1) % Self::ALL.len());
+        Self::ALL[index]
+    }
+}
+
+impl std::str::FromStr for DebugView {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "shaded" | "none" => Ok(Self::Shaded),
+            "wireframe" => Ok(Self::Wireframe),
+            "normals" => Ok(Self::Normals),
+            "depth" => Ok(Self::Depth),
+            "uvs" | "uv" => Ok(Self::Uvs),
+            "overdraw" => Ok(Self::Overdraw),
+            _ => Err(format!(
+                "unknown debug view '{value}', expected shaded, wireframe, normals, depth, uvs or overdraw"
+            )),
+        }
+    }
+}
+
+/// Replaces the frame with the linearized depth buffer of the scene pass.
+pub(crate) struct DepthViewRenderer {
+    pipeline: wgpu::RenderPipeline,
+    depth_layout: wgpu::BindGroupLayout,
+}
+
+impl DepthViewRenderer {
+    /// Whether the depth buffers of `target` can be read by a shader.
+    pub fn supports(target: TargetFormat) -> bool {
+        target.sample_count == 1 && !target.depth.has_stencil_aspect()
+    }
+
+    pub fn new(
+        device: &wgpu::Device,
+        target: TargetFormat,
+        camera_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let depth_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Depth View Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                // Read as a plain float texture, GL has no other way to load depth values
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        });
+
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Depth View Shader"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(DEPTH_VIEW_SHADER_SOURCE)),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Depth View Pipeline Layout"),
+            bind_group_layouts: &[camera_layout, &depth_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Depth View Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: "vertex_main",
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: "fragment_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: target.color,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            multiview: None,
+            cache: None,
+        });
+        Self {
+            pipeline,
+            depth_layout,
+        }
+    }
+
+    /// Draws over `color_view` with the depth in `depth_view`, which must not be
+    /// attached to any pass in flight.
+    pub fn render(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        color_view: &wgpu::TextureView,
+        depth_view: &wgpu::TextureView,
+        camera: &UniformBinding,
+    ) -> DrawStats {
+        // Depth buffers are recreated on resize, so the bind group is made per frame
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Depth View Bind Group"),
+            layout: &self.depth_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(depth_view),
+            }],
+        });
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Depth View Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &camera.bind_group, &[]);
+        render_pass.set_bind_group(1, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+        DrawStats {
+            draw_calls: 1,
+            triangles: 1,
+            ..Default::default()
+        }
+    }
+}
+
+/// Vertex and fragment stages of the debug views, appended to the scene shader.
+pub(crate) const DEBUG_SHADER_SOURCE: &str = "
+const WIREFRAME_COLOR: vec3<f32> = vec3<f32>(0.9, 0.9, 0.9);
+
+struct BarycentricOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) barycentric: vec3<f32>,
+};
+
+// Expects every triangle to have its own vertices, in order
+fn barycentric(vertex_index: u32) -> vec3<f32> {
+    let corner = vertex_index % 3u;
+    return vec3<f32>(f32(corner == 0u), f32(corner == 1u), f32(corner == 2u));
+}
+
+@vertex
+fn vertex_barycentric(
+    vert: VertexInput,
+    @builtin(vertex_index) vertex_index: u32,
+) -> BarycentricOutput {
+    var out: BarycentricOutput;
+    out.position = camera.view_projection * object.model * vec4<f32>(vert.position, 1.0);
+    out.barycentric = barycentric(vertex_index);
+    return out;
+}
+
+@fragment
+fn fragment_wireframe() -> @location(0) vec4<f32> {
+    return vec4<f32>(WIREFRAME_COLOR, 1.0);
+}
+
+// Keeps the pixels within about a pixel of a triangle's edges
+@fragment
+fn fragment_barycentric(in: BarycentricOutput) -> @location(0) vec4<f32> {
+    let distance = in.barycentric / fwidth(in.barycentric);
+    if min(min(distance.x, distance.y), distance.z) > 1.0 {
+        discard;
+    }
+    return vec4<f32>(WIREFRAME_COLOR, 1.0);
+}
+
+@fragment
+fn fragment_normals(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(normalize(in.normal) * 0.5 + 0.5, 1.0);
+}
+
+// Eight squares per unit, red growing along u and green along v
+@fragment
+fn fragment_uvs(in: VertexOutput) -> @location(0) vec4<f32> {
+    let square = floor(in.uv * 8.0);
+    let checker = abs(square.x + square.y) % 2.0;
+    let tint = vec3<f32>(fract(in.uv), 0.5);
+    return vec4<f32>(tint * (0.4 + 0.6 * checker), 1.0);
+}
+
+// Blended additively, so every layer covering a pixel adds to it
+@fragment
+fn fragment_overdraw() -> @location(0) vec4<f32> {
+    return vec4<f32>(0.3, 0.12, 0.04, 1.0);
+}
+";
+
+/// Barycentric wireframe of skinned meshes, appended after [`DEBUG_SHADER_SOURCE`]
+/// and the skinning shader.
+pub(crate) const DEBUG_SKINNING_SHADER_SOURCE: &str = "
+@vertex
+fn vertex_skinned_barycentric(
+    vert: SkinnedVertexInput,
+    @builtin(vertex_index) vertex_index: u32,
+) -> BarycentricOutput {
+    let skin = skin_matrix(vert.joints, vert.weights);
+    var out: BarycentricOutput;
+    out.position = camera.view_projection * object.model * skin * vec4<f32>(vert.position, 1.0);
+    out.barycentric = barycentric(vertex_index);
+    return out;
+}
+";
+
+const DEPTH_VIEW_SHADER_SOURCE: &str = "
+struct Camera {
+    view_projection: mat4x4<f32>,
+    position: vec4<f32>,
+    inverse_view_projection: mat4x4<f32>,
+    depth_range: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+@group(1) @binding(0)
+var depth_texture: texture_2d<f32>;
+
+@vertex
+fn vertex_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    // A triangle covering the whole screen
+    return vec4<f32>(
+        f32(vertex_index == 1u) * 4.0 - 1.0,
+        f32(vertex_index == 2u) * 4.0 - 1.0,
+        0.0,
+        1.0,
+    );
+};
+
+// Black at the near plane to white at the far plane, on a logarithmic scale
+// so nearby depth differences stay visible
+@fragment
+fn fragment_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let depth = textureLoad(depth_texture, vec2<i32>(position.xy), 0).r;
+    let near = camera.depth_range.x;
+    let far = camera.depth_range.y;
+    // Inverts the 0 to 1 depth of the perspective projection
+    let distance = near * far / (far - depth * (far - near));
+    let value = log(distance / near) / log(far / near);
+    return vec4<f32>(vec3<f32>(value), 1.0);
+}
+";
@@ -0,0 +1,406 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AmbientOcclusionRenderer, DrawStats, ScenePipelines, TargetFormat, UniformBinding,
+    SHADER_SOURCE,
+};
+
+/// How the opaque objects of a frame are shaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RenderPath {
+    /// Every object is lit while it is drawn
+    #[default]
+    Forward,
+    /// Objects are drawn into a G-buffer that is lit once per pixel afterwards
+    Deferred,
+}
+
+impl std::str::FromStr for RenderPath {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "forward" => Ok(Self::Forward),
+            "deferred" => Ok(Self::Deferred),
+            _ => Err(format!(
+                "unknown render path '{value}', expected forward or deferred"
+            )),
+        }
+    }
+}
+
+/// Surface attributes of the opaque objects, written by the geometry pass
+/// and read by the lighting pass. Sized like the viewport it belongs to.
+pub(crate) struct GBuffer {
+    albedo_view: wgpu::TextureView,
+    normal_view: wgpu::TextureView,
+    material_view: wgpu::TextureView,
+    emissive_view: wgpu::TextureView,
+    /// How much ambient light reaches each pixel, white without ambient occlusion
+    pub occlusion_view: wgpu::TextureView,
+    /// Holds the occlusion between its blur passes
+    pub occlusion_scratch_view: wgpu::TextureView,
+    /// The targets, the depth buffer and the occlusion, for the lighting pass
+    bind_group: wgpu::BindGroup,
+}
+
+impl GBuffer {
+    /// Lets all ambient light through, for frames without ambient occlusion.
+    pub fn clear_occlusion(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Clear Occlusion Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.occlusion_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+    }
+}
+
+/// Draws the opaque objects into a [`GBuffer`] and lights it with a
+/// fullscreen pass.
+pub(crate) struct DeferredRenderer {
+    /// Geometry pipelines of the scene's objects
+    pub pipelines: ScenePipelines,
+    lighting_pipeline: wgpu::RenderPipeline,
+    gbuffer_layout: wgpu::BindGroupLayout,
+}
+
+impl DeferredRenderer {
+    /// Linear base color in rgb.
+    const ALBEDO_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+    /// World-space normal in xyz.
+    const NORMAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+    /// Metallic in r, roughness in g.
+    const MATERIAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+    /// Linear emitted color in rgb, which may exceed 1.
+    const EMISSIVE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+    /// Whether the lighting pass can read the depth buffers of `target`.
+    pub fn supports(target: TargetFormat) -> bool {
+        target.sample_count == 1 && !target.depth.has_stencil_aspect()
+    }
+
+    /// Takes the bind group layouts of the camera, the lights and the environment.
+    pub fn new(
+        device: &wgpu::Device,
+        target: TargetFormat,
+        pipelines: ScenePipelines,
+        [camera_layout, lights_layout, environment_layout]: [&wgpu::BindGroupLayout; 3],
+    ) -> Self {
+        // Depth is read as a plain float texture, GL has no other way to load it
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        // Next to the object uniform's binding, which the lighting pass doesn't use
+        let gbuffer_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("G-Buffer Bind Group Layout"),
+            entries: &[1, 2, 3, 4, 5, 6].map(texture_entry),
+        });
+
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Deferred Lighting Shader"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Owned(format!(
+                "{SHADER_SOURCE}{LIGHTING_SHADER_SOURCE}"
+            ))),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Deferred Lighting Pipeline Layout"),
+            bind_group_layouts: &[
+                camera_layout,
+                lights_layout,
+                &gbuffer_layout,
+                environment_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+        let lighting_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Deferred Lighting Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: "vertex_lighting",
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: "fragment_lighting",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: target.color,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            pipelines,
+            lighting_pipeline,
+            gbuffer_layout,
+        }
+    }
+
+    /// Formats of the G-buffer targets, in the order the geometry pass writes them.
+    pub fn color_targets() -> [Option<wgpu::ColorTargetState>; 4] {
+        [
+            Self::ALBEDO_FORMAT,
+            Self::NORMAL_FORMAT,
+            Self::MATERIAL_FORMAT,
+            Self::EMISSIVE_FORMAT,
+        ]
+        .map(|format| {
+            Some(wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })
+        })
+    }
+
+    /// Creates the targets for a viewport of `width` by `height`, reading
+    /// depth from `depth_view`.
+    pub fn create_gbuffer(
+        &self,
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        depth_view: &wgpu::TextureView,
+    ) -> GBuffer {
+        let create_view = |label, format| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size: wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                        | wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        let albedo_view = create_view("G-Buffer Albedo", Self::ALBEDO_FORMAT);
+        let normal_view = create_view("G-Buffer Normal", Self::NORMAL_FORMAT);
+        let material_view = create_view("G-Buffer Material", Self::MATERIAL_FORMAT);
+        let emissive_view = create_view("G-Buffer Emissive", Self::EMISSIVE_FORMAT);
+        let occlusion_view = create_view("G-Buffer Occlusion", AmbientOcclusionRenderer::FORMAT);
+        let occlusion_scratch_view = create_view(
+            "G-Buffer Occlusion Scratch",
+            AmbientOcclusionRenderer::FORMAT,
+        );
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("G-Buffer Bind Group"),
+            layout: &self.gbuffer_layout,
+            entries: &[
+                &albedo_view,
+                &normal_view,
+                &material_view,
+                &emissive_view,
+                depth_view,
+                &occlusion_view,
+            ]
+            .into_iter()
+            .zip(1..)
+            .map(|(view, binding)| wgpu::BindGroupEntry {
+                binding,
+                resource: wgpu::BindingResource::TextureView(view),
+            })
+            .collect::<Vec<_>>(),
+        });
+        GBuffer {
+            albedo_view,
+            normal_view,
+            material_view,
+            emissive_view,
+            occlusion_view,
+            occlusion_scratch_view,
+            bind_group,
+        }
+    }
+
+    /// Starts the geometry pass, clearing `gbuffer` and `depth_view`.
+    pub fn begin_geometry<'encoder>(
+        &self,
+        encoder: &'encoder mut wgpu::CommandEncoder,
+        gbuffer: &'encoder GBuffer,
+        depth_view: &'encoder wgpu::TextureView,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites<'encoder>>,
+    ) -> wgpu::RenderPass<'encoder> {
+        let color_attachments = [
+            &gbuffer.albedo_view,
+            &gbuffer.normal_view,
+            &gbuffer.material_view,
+            &gbuffer.emissive_view,
+        ]
+        .map(|view| {
+            Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })
+        });
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Geometry Pass"),
+            color_attachments: &color_attachments,
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes,
+            occlusion_query_set: None,
+        })
+    }
+
+    /// Lights every pixel the geometry pass covered, clearing the others of
+    /// `color_view` to `clear_color`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn light(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        color_view: &wgpu::TextureView,
+        clear_color: wgpu::Color,
+        camera: &UniformBinding,
+        lights: &UniformBinding,
+        environment: &wgpu::BindGroup,
+        gbuffer: &GBuffer,
+    ) -> DrawStats {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Lighting Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(clear_color),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.lighting_pipeline);
+        render_pass.set_bind_group(0, &camera.bind_group, &[]);
+        render_pass.set_bind_group(1, &lights.bind_group, &[]);
+        render_pass.set_bind_group(2, &gbuffer.bind_group, &[]);
+        render_pass.set_bind_group(3, environment, &[]);
+        render_pass.draw(0..3, 0..1);
+        DrawStats {
+            draw_calls: 1,
+            triangles: 1,
+            ..Default::default()
+        }
+    }
+}
+
+/// Fragment stage of the geometry pass, appended to the scene shader.
+pub(crate) const GBUFFER_SHADER_SOURCE: &str = "
+struct GBufferOutput {
+    @location(0) albedo: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+    @location(2) material: vec4<f32>,
+    @location(3) emissive: vec4<f32>,
+};
+
+@fragment
+fn fragment_gbuffer(in: VertexOutput) -> GBufferOutput {
+    var out: GBufferOutput;
+    out.albedo = vec4<f32>(in.color.rgb, 1.0);
+    out.normal = vec4<f32>(normalize(in.normal), 0.0);
+    out.material = vec4<f32>(object.material.xy, 0.0, 0.0);
+    out.emissive = vec4<f32>(object.emissive.rgb, 0.0);
+    return out;
+}
+";
+
+/// The lighting pass, appended to the scene shader.
+const LIGHTING_SHADER_SOURCE: &str = "
+@group(2) @binding(1)
+var gbuffer_albedo: texture_2d<f32>;
+@group(2) @binding(2)
+var gbuffer_normal: texture_2d<f32>;
+@group(2) @binding(3)
+var gbuffer_material: texture_2d<f32>;
+@group(2) @binding(4)
+var gbuffer_emissive: texture_2d<f32>;
+@group(2) @binding(5)
+var gbuffer_depth: texture_2d<f32>;
+@group(2) @binding(6)
+var gbuffer_occlusion: texture_2d<f32>;
+
+@vertex
+fn vertex_lighting(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    // A triangle covering the whole screen
+    return vec4<f32>(
+        f32(vertex_index == 1u) * 4.0 - 1.0,
+        f32(vertex_index == 2u) * 4.0 - 1.0,
+        0.0,
+        1.0,
+    );
+}
+
+@fragment
+fn fragment_lighting(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let coords = vec2<i32>(position.xy);
+    let depth = textureLoad(gbuffer_depth, coords, 0).r;
+    // Nothing was drawn here, keep the clear color
+    if depth >= 1.0 {
+        discard;
+    }
+
+    // Back from the pixel's clip space position to world space
+    let size = vec2<f32>(textureDimensions(gbuffer_depth));
+    let ndc = vec2<f32>(position.x / size.x * 2.0 - 1.0, 1.0 - position.y / size.y * 2.0);
+    let world_position = camera.inverse_view_projection * vec4<f32>(ndc, depth, 1.0);
+
+    let albedo = textureLoad(gbuffer_albedo, coords, 0).rgb;
+    let normal = textureLoad(gbuffer_normal, coords, 0).xyz;
+    let material = textureLoad(gbuffer_material, coords, 0);
+    let emissive = textureLoad(gbuffer_emissive, coords, 0).rgb;
+    let occlusion = textureLoad(gbuffer_occlusion, coords, 0).r;
+    let color = shade(
+        world_position.xyz / world_position.w,
+        normal,
+        albedo,
+        material.x,
+        material.y,
+        occlusion,
+    ) + emissive;
+    return vec4<f32>(color, 1.0);
+}
+";
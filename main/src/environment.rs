@@ -0,0 +1,738 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    skybox::{load_image, ReadFile},
+    SceneError, UniformBinding,
+};
+
+/// An HDR panorama the scene is lit by, usually an equirectangular `.hdr` file.
+/// A relative path is resolved against the directory of the scene file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Environment {
+    pub path: PathBuf,
+    /// Multiplier for the light coming from the environment
+    #[serde(default = "Environment::default_intensity")]
+    pub intensity: f32,
+}
+
+impl Environment {
+    pub fn load(
+        &self,
+        directory: Option<&Path>,
+        read: ReadFile,
+    ) -> Result<image::Rgba32FImage, SceneError> {
+        load_image(&self.path, directory, read).map(|image| image.to_rgba32f())
+    }
+
+    fn default_intensity() -> f32 {
+        1.0
+    }
+}
+
+/// Cube maps and lookup table the mesh shader samples for image-based lighting.
+pub(crate) struct EnvironmentMaps {
+    /// The panorama resampled into a cube, also usable as the background
+    pub environment: wgpu::TextureView,
+    pub bind_group: wgpu::BindGroup,
+}
+
+/// Precomputes [`EnvironmentMaps`] from a panorama with a series of render passes.
+pub(crate) struct EnvironmentBaker {
+    /// Layout of [`EnvironmentMaps::bind_group`]
+    pub layout: wgpu::BindGroupLayout,
+    uniform_layout: wgpu::BindGroupLayout,
+    panorama_layout: wgpu::BindGroupLayout,
+    environment_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    equirectangular_pipeline: wgpu::RenderPipeline,
+    irradiance_pipeline: wgpu::RenderPipeline,
+    specular_pipeline: wgpu::RenderPipeline,
+    brdf_pipeline: wgpu::RenderPipeline,
+}
+
+/// Parameters of a single bake pass.
+#[repr(C)]
+#[derive(Default, Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct BakeUniform {
+    /// Cube face rendered to, in +X, -X, +Y, -Y, +Z, -Z order
+    face: u32,
+    /// Mip level of the source texture to read
+    source_level: u32,
+    /// Roughness the specular map level is filtered for
+    roughness: f32,
+    _padding: u32,
+}
+
+impl EnvironmentBaker {
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+    const ENVIRONMENT_SIZE: u32 = 256;
+    const IRRADIANCE_SIZE: u32 = 32;
+    const SPECULAR_SIZE: u32 = 128;
+    /// Roughness goes from 0 at the first level to 1 at the last
+    pub const SPECULAR_LEVELS: u32 = 5;
+    const BRDF_LUT_SIZE: u32 = 128;
+
+    pub fn new(device: &wgpu::Device) -> Self {
+        let uniform_layout = UniformBinding::create_bind_group_layout(device);
+        let texture_entry = |binding, sample_type, view_dimension| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let sampler_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        };
+        let filterable = wgpu::TextureSampleType::Float { filterable: true };
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Environment Bind Group Layout"),
+            entries: &[
+                texture_entry(0, filterable, wgpu::TextureViewDimension::Cube),
+                texture_entry(1, filterable, wgpu::TextureViewDimension::Cube),
+                texture_entry(2, filterable, wgpu::TextureViewDimension::D2),
+                sampler_entry(3),
+            ],
+        });
+        // Float32 textures are only filterable with an optional feature, the
+        // panorama is read with `textureLoad` instead
+        let panorama_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Panorama Bind Group Layout"),
+            entries: &[texture_entry(
+                0,
+                wgpu::TextureSampleType::Float { filterable: false },
+                wgpu::TextureViewDimension::D2,
+            )],
+        });
+        let environment_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Environment Source Bind Group Layout"),
+                entries: &[
+                    texture_entry(0, filterable, wgpu::TextureViewDimension::Cube),
+                    sampler_entry(1),
+                ],
+            });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Environment Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let create_pipeline = |name: &str, source: &str, layouts: &[&wgpu::BindGroupLayout]| {
+            let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(&format!("{name} Shader")),
+                source: wgpu::ShaderSource::Wgsl(format!("{BAKE_SHADER_SOURCE}{source}").into()),
+            });
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(&format!("{name} Pipeline Layout")),
+                bind_group_layouts: layouts,
+                push_constant_ranges: &[],
+            });
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(&format!("{name} Pipeline")),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader_module,
+                    entry_point: "vertex_main",
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader_module,
+                    entry_point: "fragment_main",
+                    targets: &[Some(Self::FORMAT.into())],
+                    compilation_options: Default::default(),
+                }),
+                multiview: None,
+                cache: None,
+            })
+        };
+
+        Self {
+            equirectangular_pipeline: create_pipeline(
+                "Equirectangular",
+                EQUIRECTANGULAR_SHADER_SOURCE,
+                &[&uniform_layout, &panorama_layout],
+            ),
+            irradiance_pipeline: create_pipeline(
+                "Irradiance",
+                IRRADIANCE_SHADER_SOURCE,
+                &[&uniform_layout, &environment_layout],
+            ),
+            specular_pipeline: create_pipeline(
+                "Specular",
+                SPECULAR_SHADER_SOURCE,
+                &[&uniform_layout, &environment_layout],
+            ),
+            brdf_pipeline: create_pipeline("BRDF", BRDF_SHADER_SOURCE, &[&uniform_layout]),
+            layout,
+            uniform_layout,
+            panorama_layout,
+            environment_layout,
+            sampler,
+        }
+    }
+
+    /// Black maps for scenes without an environment.
+    pub fn placeholder(&self, device: &wgpu::Device) -> EnvironmentMaps {
+        let environment = Self::create_texture(device, "Environment", 1, 1, 6);
+        let irradiance = Self::create_texture(device, "Irradiance", 1, 1, 6);
+        let specular = Self::create_texture(device, "Specular", 1, 1, 6);
+        let brdf_lut = Self::create_texture(device, "BRDF LUT", 1, 1, 1);
+        self.create_maps(device, &environment, &irradiance, &specular, &brdf_lut)
+    }
+
+    pub fn bake(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        panorama: &image::Rgba32FImage,
+    ) -> EnvironmentMaps {
+        let environment_levels = Self::ENVIRONMENT_SIZE.ilog2() + 1;
+        let environment = Self::create_texture(
+            device,
+            "Environment",
+            Self::ENVIRONMENT_SIZE,
+            environment_levels,
+            6,
+        );
+        let irradiance = Self::create_texture(device, "Irradiance", Self::IRRADIANCE_SIZE, 1, 6);
+        let specular = Self::create_texture(
+            device,
+            "Specular",
+            Self::SPECULAR_SIZE,
+            Self::SPECULAR_LEVELS,
+            6,
+        );
+        let brdf_lut = Self::create_texture(device, "BRDF LUT", Self::BRDF_LUT_SIZE, 1, 1);
+
+        let panorama = Self::upload_panorama(device, queue, panorama);
+        let panorama_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Panorama Bind Group"),
+            layout: &self.panorama_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(
+                    &panorama.create_view(&Default::default()),
+                ),
+            }],
+        });
+        let environment_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Environment Source Bind Group"),
+            layout: &self.environment_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&Self::cube_view(&environment)),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Environment Bake Encoder"),
+        });
+
+        // Every level of the environment is resampled from the panorama level
+        // with about the same texel density, so no mip chain has to be built
+        // from a texture that is also being rendered to
+        for level in 0..environment_levels {
+            let panorama_level = (panorama.width() / (4 * (Self::ENVIRONMENT_SIZE >> level)))
+                .max(1)
+                .ilog2()
+                .min(panorama.mip_level_count() - 1);
+            for face in 0..6 {
+                self.encode_pass(
+                    device,
+                    &mut encoder,
+                    &self.equirectangular_pipeline,
+                    Some(&panorama_bind_group),
+                    &environment,
+                    level,
+                    BakeUniform {
+                        face,
+                        source_level: panorama_level,
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+
+        // The convolution is smooth, a low resolution level of the environment is enough
+        let irradiance_source_level = (Self::ENVIRONMENT_SIZE / Self::IRRADIANCE_SIZE).ilog2();
+        for face in 0..6 {
+            self.encode_pass(
+                device,
+                &mut encoder,
+                &self.irradiance_pipeline,
+                Some(&environment_bind_group),
+                &irradiance,
+                0,
+                BakeUniform {
+                    face,
+                    source_level: irradiance_source_level,
+                    ..Default::default()
+                },
+            );
+        }
+
+        for level in 0..Self::SPECULAR_LEVELS {
+            for face in 0..6 {
+                self.encode_pass(
+                    device,
+                    &mut encoder,
+                    &self.specular_pipeline,
+                    Some(&environment_bind_group),
+                    &specular,
+                    level,
+                    BakeUniform {
+                        face,
+                        roughness: level as f32 / (Self::SPECULAR_LEVELS - 1) as f32,
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+
+        self.encode_pass(
+            device,
+            &mut encoder,
+            &self.brdf_pipeline,
+            None,
+            &brdf_lut,
+            0,
+            BakeUniform::default(),
+        );
+
+        queue.submit(std::iter::once(encoder.finish()));
+        self.create_maps(device, &environment, &irradiance, &specular, &brdf_lut)
+    }
+
+    /// Uploads the panorama with a mip chain built on the CPU, downscaled to
+    /// the resolution the environment needs at most.
+    fn upload_panorama(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        panorama: &image::Rgba32FImage,
+    ) -> wgpu::Texture {
+        // The panorama wraps around the cube, 4 faces wide
+        let max_width = 4 * Self::ENVIRONMENT_SIZE;
+        let mut level_image = if panorama.width() > max_width {
+            image::imageops::resize(
+                panorama,
+                max_width,
+                (panorama.height() * max_width / panorama.width()).max(1),
+                image::imageops::FilterType::Triangle,
+            )
+        } else {
+            panorama.clone()
+        };
+
+        let level_count = level_image.width().max(level_image.height()).ilog2() + 1;
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Panorama Texture"),
+            size: wgpu::Extent3d {
+                width: level_image.width(),
+                height: level_image.height(),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        for level in 0..level_count {
+            if level > 0 {
+                level_image = image::imageops::resize(
+                    &level_image,
+                    (level_image.width() / 2).max(1),
+                    (level_image.height() / 2).max(1),
+                    image::imageops::FilterType::Triangle,
+                );
+            }
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: level,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                bytemuck::cast_slice(level_image.as_raw()),
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(16 * level_image.width()),
+                    rows_per_image: Some(level_image.height()),
+                },
+                wgpu::Extent3d {
+                    width: level_image.width(),
+                    height: level_image.height(),
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        texture
+    }
+
+    /// Renders a full-screen triangle into one layer and level of `target`.
+    #[allow(clippy::too_many_arguments)]
+    fn encode_pass(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        pipeline: &wgpu::RenderPipeline,
+        source: Option<&wgpu::BindGroup>,
+        target: &wgpu::Texture,
+        level: u32,
+        uniform: BakeUniform,
+    ) {
+        let view = target.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            base_mip_level: level,
+            mip_level_count: Some(1),
+            base_array_layer: uniform.face,
+            array_layer_count: Some(1),
+            ..Default::default()
+        });
+        // Every pass needs its own buffer, writes would all land before the submit
+        let uniform = UniformBinding::with_contents(device, &self.uniform_layout, uniform);
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Environment Bake Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &uniform.bind_group, &[]);
+        if let Some(source) = source {
+            render_pass.set_bind_group(1, source, &[]);
+        }
+        render_pass.draw(0..3, 0..1);
+    }
+
+    fn create_texture(
+        device: &wgpu::Device,
+        name: &str,
+        size: u32,
+        mip_level_count: u32,
+        layers: u32,
+    ) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(&format!("{name} Texture")),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: layers,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        })
+    }
+
+    fn cube_view(texture: &wgpu::Texture) -> wgpu::TextureView {
+        texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        })
+    }
+
+    fn create_maps(
+        &self,
+        device: &wgpu::Device,
+        environment: &wgpu::Texture,
+        irradiance: &wgpu::Texture,
+        specular: &wgpu::Texture,
+        brdf_lut: &wgpu::Texture,
+    ) -> EnvironmentMaps {
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Environment Bind Group"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&Self::cube_view(irradiance)),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&Self::cube_view(specular)),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(
+                        &brdf_lut.create_view(&Default::default()),
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+        EnvironmentMaps {
+            environment: Self::cube_view(environment),
+            bind_group,
+        }
+    }
+}
+
+/// Shared by all bake passes, each appends its own fragment shader.
+const BAKE_SHADER_SOURCE: &str = "
+const PI: f32 = 3.14159265359;
+
+struct Bake {
+    face: u32,
+    source_level: u32,
+    roughness: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> bake: Bake;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    // From -1 to 1, with v pointing down like the texture rows
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vertex_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let clip_position = vec2<f32>(
+        f32(vertex_index == 1u) * 4.0 - 1.0,
+        f32(vertex_index == 2u) * 4.0 - 1.0,
+    );
+    var out: VertexOutput;
+    out.position = vec4<f32>(clip_position, 0.0, 1.0);
+    out.uv = vec2<f32>(clip_position.x, -clip_position.y);
+    return out;
+}
+
+// Direction through a texel of the face being rendered
+fn face_direction(uv: vec2<f32>) -> vec3<f32> {
+    switch bake.face {
+        case 0u: { return normalize(vec3<f32>(1.0, -uv.y, -uv.x)); }
+        case 1u: { return normalize(vec3<f32>(-1.0, -uv.y, uv.x)); }
+        case 2u: { return normalize(vec3<f32>(uv.x, 1.0, uv.y)); }
+        case 3u: { return normalize(vec3<f32>(uv.x, -1.0, -uv.y)); }
+        case 4u: { return normalize(vec3<f32>(uv.x, -uv.y, 1.0)); }
+        default: { return normalize(vec3<f32>(-uv.x, -uv.y, -1.0)); }
+    }
+}
+
+// Low-discrepancy point set, the bit reversal is spelled out for WebGL
+fn hammersley(index: u32, count: u32) -> vec2<f32> {
+    var bits = index;
+    bits = (bits << 16u) | (bits >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+    return vec2<f32>(f32(index) / f32(count), f32(bits) * 2.3283064365386963e-10);
+}
+
+fn tangent_to_world(direction: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    let up = select(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 0.0, 1.0), abs(normal.z) < 0.999);
+    let tangent = normalize(cross(up, normal));
+    let bitangent = cross(normal, tangent);
+    return normalize(tangent * direction.x + bitangent * direction.y + normal * direction.z);
+}
+
+// Half vector around `normal`, distributed like the GGX microfacet normals
+fn importance_sample_ggx(xi: vec2<f32>, normal: vec3<f32>, roughness: f32) -> vec3<f32> {
+    let a = roughness * roughness;
+    let phi = 2.0 * PI * xi.x;
+    let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    return tangent_to_world(vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta), normal);
+}
+
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a2 = roughness * roughness * roughness * roughness;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+";
+
+const EQUIRECTANGULAR_SHADER_SOURCE: &str = "
+@group(1) @binding(0)
+var panorama: texture_2d<f32>;
+
+// Wraps around horizontally and clamps at the poles
+fn panorama_texel(position: vec2<i32>, size: vec2<i32>) -> vec4<f32> {
+    let wrapped = vec2<i32>(
+        ((position.x % size.x) + size.x) % size.x,
+        clamp(position.y, 0, size.y - 1),
+    );
+    return textureLoad(panorama, wrapped, i32(bake.source_level));
+}
+
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let direction = face_direction(in.uv);
+    let size = vec2<i32>(textureDimensions(panorama, i32(bake.source_level)));
+    let position = vec2<f32>(
+        atan2(direction.x, direction.z) / (2.0 * PI) + 0.5,
+        0.5 - asin(clamp(direction.y, -1.0, 1.0)) / PI,
+    ) * vec2<f32>(size) - 0.5;
+
+    let base = floor(position);
+    let t = position - base;
+    let texel = vec2<i32>(base);
+    let top = mix(
+        panorama_texel(texel, size),
+        panorama_texel(texel + vec2<i32>(1, 0), size),
+        t.x,
+    );
+    let bottom = mix(
+        panorama_texel(texel + vec2<i32>(0, 1), size),
+        panorama_texel(texel + vec2<i32>(1, 1), size),
+        t.x,
+    );
+    // Keep the brightest spots within the range of half floats
+    return vec4<f32>(min(mix(top, bottom, t.y).rgb, vec3<f32>(65504.0)), 1.0);
+}
+";
+
+const IRRADIANCE_SHADER_SOURCE: &str = "
+@group(1) @binding(0)
+var environment: texture_cube<f32>;
+@group(1) @binding(1)
+var environment_sampler: sampler;
+
+const PHI_STEPS: u32 = 48u;
+const THETA_STEPS: u32 = 12u;
+
+// Cosine-weighted light arriving from the hemisphere around each direction
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let normal = face_direction(in.uv);
+    var irradiance = vec3<f32>(0.0);
+    for (var phi_step = 0u; phi_step < PHI_STEPS; phi_step++) {
+        let phi = (f32(phi_step) + 0.5) / f32(PHI_STEPS) * 2.0 * PI;
+        for (var theta_step = 0u; theta_step < THETA_STEPS; theta_step++) {
+            let theta = (f32(theta_step) + 0.5) / f32(THETA_STEPS) * 0.5 * PI;
+            let direction = tangent_to_world(
+                vec3<f32>(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta)),
+                normal,
+            );
+            let radiance = textureSampleLevel(
+                environment,
+                environment_sampler,
+                direction,
+                f32(bake.source_level),
+            ).rgb;
+            irradiance += radiance * cos(theta) * sin(theta);
+        }
+    }
+    return vec4<f32>(PI * irradiance / f32(PHI_STEPS * THETA_STEPS), 1.0);
+}
+";
+
+const SPECULAR_SHADER_SOURCE: &str = "
+@group(1) @binding(0)
+var environment: texture_cube<f32>;
+@group(1) @binding(1)
+var environment_sampler: sampler;
+
+const SAMPLE_COUNT: u32 = 256u;
+
+// Environment convolved with the GGX lobe, assuming the view along the normal
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let normal = face_direction(in.uv);
+    if bake.roughness == 0.0 {
+        return vec4<f32>(textureSampleLevel(environment, environment_sampler, normal, 0.0).rgb, 1.0);
+    }
+
+    // Samples from unlikely directions read blurrier levels, which avoids
+    // fireflies from small, bright parts of the environment
+    let size = f32(textureDimensions(environment).x);
+    let texel_solid_angle = 4.0 * PI / (6.0 * size * size);
+
+    var color = vec3<f32>(0.0);
+    var weight = 0.0;
+    for (var index = 0u; index < SAMPLE_COUNT; index++) {
+        let half_vector = importance_sample_ggx(hammersley(index, SAMPLE_COUNT), normal, bake.roughness);
+        let light = normalize(2.0 * dot(normal, half_vector) * half_vector - normal);
+        let n_dot_l = dot(normal, light);
+        if n_dot_l > 0.0 {
+            let n_dot_h = max(dot(normal, half_vector), 0.0);
+            let pdf = distribution_ggx(n_dot_h, bake.roughness) / 4.0 + 0.0001;
+            let sample_solid_angle = 1.0 / (f32(SAMPLE_COUNT) * pdf + 0.0001);
+            let level = max(0.5 * log2(sample_solid_angle / texel_solid_angle), 0.0);
+            color += textureSampleLevel(environment, environment_sampler, light, level).rgb * n_dot_l;
+            weight += n_dot_l;
+        }
+    }
+    return vec4<f32>(color / weight, 1.0);
+}
+";
+
+const BRDF_SHADER_SOURCE: &str = "
+const SAMPLE_COUNT: u32 = 256u;
+
+fn geometry_schlick_ggx(n_dot_x: f32, roughness: f32) -> f32 {
+    let k = roughness * roughness / 2.0;
+    return n_dot_x / (n_dot_x * (1.0 - k) + k);
+}
+
+// Scale and bias to the Fresnel reflectance at normal incidence, indexed by
+// the angle between normal and view in x and the roughness in y
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coordinates = in.uv * 0.5 + 0.5;
+    let n_dot_v = max(coordinates.x, 0.001);
+    let roughness = coordinates.y;
+    let view = vec3<f32>(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+    let normal = vec3<f32>(0.0, 0.0, 1.0);
+
+    var scale = 0.0;
+    var bias = 0.0;
+    for (var index = 0u; index < SAMPLE_COUNT; index++) {
+        let half_vector = importance_sample_ggx(hammersley(index, SAMPLE_COUNT), normal, roughness);
+        let light = normalize(2.0 * dot(view, half_vector) * half_vector - view);
+        let n_dot_l = max(light.z, 0.0);
+        let n_dot_h = max(half_vector.z, 0.0);
+        let v_dot_h = max(dot(view, half_vector), 0.0);
+        if n_dot_l > 0.0 {
+            let geometry = geometry_schlick_ggx(n_dot_v, roughness)
+                * geometry_schlick_ggx(n_dot_l, roughness);
+            let visibility = geometry * v_dot_h / (n_dot_h * n_dot_v);
+            let fresnel = pow(1.0 - v_dot_h, 5.0);
+            scale += (1.0 - fresnel) * visibility;
+            bias += fresnel * visibility;
+        }
+    }
+    return vec4<f32>(scale, bias, 0.0, 1.0) / vec4<f32>(f32(SAMPLE_COUNT), f32(SAMPLE_COUNT), 1.0, 1.0);
+}
+";
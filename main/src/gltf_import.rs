@@ -0,0 +1,773 @@
+//! Conversion of glTF 2.0 files into [`Scene`]s.
+//!
+//! glTF is right-handed while the renderer is left-handed, so everything is
+//! mirrored along the x axis on the way in. Models keep facing +z, towards the
+//! default camera.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use gltf::animation::util::ReadOutputs;
+
+use crate::{
+    AnimationClip, AnimationPlayer, Interpolation, Joint, JointAnimation, Keyframe, Light,
+    LightKind, Material, Mesh, Object, Scene, SceneError, Skeleton, SkinVertex, Track, Transform,
+    Vertex,
+};
+
+/// Reads the meshes, materials, skins and animations of the default scene of
+/// a `.gltf` or `.glb` file.
+///
+/// Every mesh primitive becomes an object, playing the first animation that
+/// moves it. Lights come from `KHR_lights_punctual`, and a directional light
+/// is added to files without any, as those usually leave the lighting to the
+/// viewer.
+pub(crate) fn import(path: &Path) -> Result<Scene, SceneError> {
+    let gltf::Gltf { document, blob } = gltf::Gltf::open(path).map_err(SceneError::Gltf)?;
+    let buffers = gltf::import_buffers(&document, path.parent(), blob).map_err(SceneError::Gltf)?;
+    let buffer_data = |buffer: gltf::Buffer| buffers.get(buffer.index()).map(|data| &data.0[..]);
+
+    let nodes = NodeTree::new(&document);
+    let mut scene = Scene {
+        meshes: Vec::new(),
+        objects: Vec::new(),
+        lights: document
+            .nodes()
+            .filter(|node| nodes.in_scene[node.index()])
+            .filter_map(|node| Some(import_light(&node.light()?, &nodes.global[node.index()])))
+            .collect(),
+        directory: path.parent().map(PathBuf::from),
+        ..Scene::default()
+    };
+
+    if scene.lights.is_empty() {
+        scene.lights.push(Light {
+            kind: LightKind::Directional {
+                direction: nalgebra_glm::vec3(-0.3, -0.6, -1.0),
+            },
+            color: [1.0; 3],
+            intensity: 1.0,
+        });
+    }
+
+    let mut material_names = HashSet::new();
+    let materials: Vec<String> = document
+        .materials()
+        .map(|material| {
+            let pbr = material.pbr_metallic_roughness();
+            // Alpha only makes a material translucent in blend mode, masking isn't supported
+            let mut base_color = pbr.base_color_factor();
+            if material.alpha_mode() != gltf::material::AlphaMode::Blend {
+                base_color[3] = 1.0;
+            }
+            let name = unique_name(
+                &mut material_names,
+                material.name().map_or_else(
+                    || format!("material{}", material.index().unwrap_or_default()),
+                    str::to_string,
+                ),
+            );
+            scene.materials.push(Material {
+                name: name.clone(),
+                base_color,
+                emissive: material.emissive_factor(),
+                metallic: pbr.metallic_factor(),
+                roughness: pbr.roughness_factor(),
+            });
+            name
+        })
+        .collect();
+
+    let mut skeleton_names = HashSet::new();
+    let skins: Vec<ImportedSkin> = document
+        .skins()
+        .map(|skin| {
+            let name = unique_name(
+                &mut skeleton_names,
+                skin.name()
+                    .map_or_else(|| format!("skin{}", skin.index()), str::to_string),
+            );
+            let (skeleton, skin) = ImportedSkin::new(&skin, name, &nodes, buffer_data)?;
+            scene.skeletons.push(skeleton);
+            Ok(skin)
+        })
+        .collect::<Result<_, SceneError>>()?;
+
+    let mut mesh_names = HashSet::new();
+    let mut object_names = HashSet::new();
+    // Node of every object, for attaching node animations
+    let mut object_nodes = Vec::new();
+    // Skinned meshes are stored once per skin, as joint indices depend on it
+    let mut imported_meshes: HashMap<(usize, usize, Option<usize>), String> = HashMap::new();
+    for node in document.nodes() {
+        let Some(mesh) = node.mesh() else {
+            continue;
+        };
+        if !nodes.in_scene[node.index()] {
+            continue;
+        }
+        let skin = node.skin().map(|skin| skin.index());
+        let node_name = node
+            .name()
+            .map_or_else(|| format!("node{}", node.index()), str::to_string);
+        for primitive in mesh.primitives() {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                tracing::warn!(
+                    "Skipping primitive {} of mesh {}, only triangles are supported",
+                    primitive.index(),
+                    mesh.index()
+                );
+                continue;
+            }
+            let key = (mesh.index(), primitive.index(), skin);
+            let mesh_name = match imported_meshes.get(&key) {
+                Some(name) => name.clone(),
+                None => {
+                    let name = unique_name(
+                        &mut mesh_names,
+                        mesh.name()
+                            .map_or_else(|| format!("mesh{}", mesh.index()), str::to_string),
+                    );
+                    scene.meshes.push(import_primitive(
+                        &primitive,
+                        name.clone(),
+                        skin.map(|skin| &skins[skin]),
+                        buffer_data,
+                    )?);
+                    imported_meshes.insert(key, name.clone());
+                    name
+                }
+            };
+            let name = if mesh.primitives().len() > 1 {
+                format!("{node_name}/{}", primitive.index())
+            } else {
+                node_name.clone()
+            };
+            object_nodes.push(node.index());
+            scene.objects.push(Object {
+                name: unique_name(&mut object_names, name),
+                mesh: mesh_name,
+                material: primitive
+                    .material()
+                    .index()
+                    .map(|index| materials[index].clone()),
+                // Skinned meshes are placed by their joints instead of their node
+                transform: match skin {
+                    Some(skin) => Transform::from_matrix(&skins[skin].origin),
+                    None => Transform::from_matrix(&nodes.global[node.index()]),
+                },
+                animation: None,
+                skeleton: skin.map(|skin| scene.skeletons[skin].name.clone()),
+            });
+        }
+    }
+
+    let mut animation_names = HashSet::new();
+    for animation in document.animations() {
+        let name = animation
+            .name()
+            .map_or_else(|| format!("animation{}", animation.index()), str::to_string);
+        let mut joint_clips: Vec<Option<AnimationClip>> = vec![None; skins.len()];
+        let mut node_clips: HashMap<usize, AnimationClip> = HashMap::new();
+        for channel in animation.channels() {
+            let target = channel.target().node();
+            let sampler = channel.sampler();
+            let reader = channel.reader(buffer_data);
+            let (Some(times), Some(outputs)) = (reader.read_inputs(), reader.read_outputs()) else {
+                continue;
+            };
+            let times: Vec<f32> = times.collect();
+            let interpolation = match sampler.interpolation() {
+                gltf::animation::Interpolation::Linear => Interpolation::Linear,
+                gltf::animation::Interpolation::Step => Interpolation::Step,
+                gltf::animation::Interpolation::CubicSpline => Interpolation::Cubic,
+            };
+            let channel = match outputs {
+                ReadOutputs::Translations(values) => Channel::Translation(track(
+                    interpolation,
+                    &times,
+                    values.map(|value| mirror_vector(&value.into())).collect(),
+                )),
+                ReadOutputs::Rotations(values) => Channel::Rotation(track(
+                    interpolation,
+                    &times,
+                    values.into_f32().map(mirror_rotation).collect(),
+                )),
+                ReadOutputs::Scales(values) => Channel::Scale(track(
+                    interpolation,
+                    &times,
+                    values.map(nalgebra_glm::Vec3::from).collect(),
+                )),
+                ReadOutputs::MorphTargetWeights(_) => continue,
+            };
+
+            let mut animates_joint = false;
+            for (index, skin) in skins.iter().enumerate() {
+                let Some(joint) = skin.joint(target.index()) else {
+                    continue;
+                };
+                animates_joint = true;
+                let clip = joint_clips[index].get_or_insert_with(empty_clip);
+                let joint_name = &scene.skeletons[index].joints[joint].name;
+                let animation = match clip
+                    .joints
+                    .iter()
+                    .position(|animation| animation.joint == *joint_name)
+                {
+                    Some(position) => &mut clip.joints[position],
+                    None => {
+                        clip.joints.push(JointAnimation {
+                            joint: joint_name.clone(),
+                            translation: None,
+                            rotation: None,
+                            scale: None,
+                        });
+                        clip.joints.last_mut().unwrap()
+                    }
+                };
+                channel.clone().apply(
+                    &mut animation.translation,
+                    &mut animation.rotation,
+                    &mut animation.scale,
+                );
+            }
+
+            if !animates_joint && target.mesh().is_some() {
+                // Tracks are relative to the parent node, objects to the scene
+                let parent = nodes.parent[target.index()]
+                    .map_or_else(Transform::default, |parent| {
+                        Transform::from_matrix(&nodes.global[parent])
+                    });
+                let clip = node_clips.entry(target.index()).or_insert_with(empty_clip);
+                channel.relative_to(&parent).apply(
+                    &mut clip.translation,
+                    &mut clip.rotation,
+                    &mut clip.scale,
+                );
+            }
+        }
+
+        for (index, clip) in joint_clips.into_iter().enumerate() {
+            let Some(mut clip) = clip else {
+                continue;
+            };
+            let skeleton = &scene.skeletons[index].name;
+            clip.name = unique_name(&mut animation_names, format!("{name}/{skeleton}"));
+            for object in &mut scene.objects {
+                if object.animation.is_none() && object.skeleton.as_ref() == Some(skeleton) {
+                    object.animation = Some(AnimationPlayer::new(clip.name.clone()));
+                }
+            }
+            scene.animations.push(clip);
+        }
+        let mut node_clips: Vec<(usize, AnimationClip)> = node_clips.into_iter().collect();
+        node_clips.sort_by_key(|(node, _)| *node);
+        for (node, mut clip) in node_clips {
+            let node = document.nodes().nth(node).unwrap();
+            let node_name = node
+                .name()
+                .map_or_else(|| format!("node{}", node.index()), str::to_string);
+            clip.name = unique_name(&mut animation_names, format!("{name}/{node_name}"));
+            // Objects of all primitives of the node share the clip
+            for (object, object_node) in scene.objects.iter_mut().zip(&object_nodes) {
+                if *object_node == node.index() && object.animation.is_none() {
+                    object.animation = Some(AnimationPlayer::new(clip.name.clone()));
+                }
+            }
+            scene.animations.push(clip);
+        }
+    }
+
+    scene.validate()?;
+    Ok(scene)
+}
+
+/// Parents and global transforms of all nodes.
+struct NodeTree {
+    parent: Vec<Option<usize>>,
+    /// Transforms into the scene, already mirrored
+    global: Vec<nalgebra_glm::Mat4>,
+    /// Whether the node is part of the scene that is imported
+    in_scene: Vec<bool>,
+}
+
+impl NodeTree {
+    fn new(document: &gltf::Document) -> Self {
+        let count = document.nodes().len();
+        let mut parent = vec![None; count];
+        for node in document.nodes() {
+            for child in node.children() {
+                parent[child.index()] = Some(node.index());
+            }
+        }
+
+        let mut tree = Self {
+            parent,
+            global: vec![nalgebra_glm::Mat4::identity(); count],
+            in_scene: vec![false; count],
+        };
+        let roots: Vec<gltf::Node> = match document.default_scene().or(document.scenes().next()) {
+            Some(scene) => scene.nodes().collect(),
+            None => document
+                .nodes()
+                .filter(|node| tree.parent[node.index()].is_none())
+                .collect(),
+        };
+        let mut stack: Vec<(gltf::Node, nalgebra_glm::Mat4)> = roots
+            .into_iter()
+            .map(|node| (node, nalgebra_glm::Mat4::identity()))
+            .collect();
+        while let Some((node, parent_global)) = stack.pop() {
+            let global = parent_global * local_transform(&node).matrix();
+            tree.global[node.index()] = global;
+            tree.in_scene[node.index()] = true;
+            stack.extend(node.children().map(|child| (child, global)));
+        }
+        tree
+    }
+}
+
+/// A skin converted into a [`Skeleton`], with the mapping of its joints.
+struct ImportedSkin {
+    /// Node index of every skeleton joint
+    joint_nodes: Vec<usize>,
+    /// Skeleton joint index for every joint of the glTF skin
+    remap: Vec<u32>,
+    /// Global transform the root joints are relative to, which becomes the object's
+    origin: nalgebra_glm::Mat4,
+}
+
+impl ImportedSkin {
+    fn new<'a, 's>(
+        skin: &'a gltf::Skin<'a>,
+        name: String,
+        nodes: &NodeTree,
+        buffer_data: impl Clone + Fn(gltf::Buffer<'a>) -> Option<&'s [u8]>,
+    ) -> Result<(Skeleton, Self), SceneError> {
+        let skin_joints: Vec<gltf::Node> = skin.joints().collect();
+        if skin_joints.is_empty() {
+            return Err(SceneError::Invalid(format!("skin '{name}' has no joints")));
+        }
+        let inverse_bind_matrices: Vec<nalgebra_glm::Mat4> =
+            match skin.reader(buffer_data).read_inverse_bind_matrices() {
+                Some(matrices) => matrices
+                    .map(|matrix| mirror_matrix(&matrix.into()))
+                    .collect(),
+                None => vec![nalgebra_glm::Mat4::identity(); skin_joints.len()],
+            };
+        if inverse_bind_matrices.len() < skin_joints.len() {
+            return Err(SceneError::Invalid(format!(
+                "skin '{name}' has fewer inverse bind matrices than joints"
+            )));
+        }
+
+        // Parents have to come first, so sort by depth in the node tree
+        let depth = |mut node: usize| {
+            let mut depth = 0;
+            while let Some(parent) = nodes.parent[node] {
+                node = parent;
+                depth += 1;
+            }
+            depth
+        };
+        let mut order: Vec<usize> = (0..skin_joints.len()).collect();
+        order.sort_by_key(|joint| depth(skin_joints[*joint].index()));
+        let joint_nodes: Vec<usize> = order
+            .iter()
+            .map(|joint| skin_joints[*joint].index())
+            .collect();
+        let mut remap = vec![0; skin_joints.len()];
+        for (index, joint) in order.iter().enumerate() {
+            remap[*joint] = index as u32;
+        }
+
+        let ancestor_joint = |mut node: usize| {
+            while let Some(parent) = nodes.parent[node] {
+                if let Some(joint) = joint_nodes.iter().position(|joint| *joint == parent) {
+                    return Some(joint);
+                }
+                node = parent;
+            }
+            None
+        };
+        let origin = nodes.parent[joint_nodes[0]]
+            .map_or_else(nalgebra_glm::Mat4::identity, |parent| nodes.global[parent]);
+
+        let mut names = HashSet::new();
+        let joints = order
+            .iter()
+            .zip(&joint_nodes)
+            .map(|(joint, node)| {
+                let gltf_node = &skin_joints[*joint];
+                let parent = ancestor_joint(*node);
+                let reference = match parent {
+                    Some(parent) => Some(joint_nodes[parent]),
+                    None => nodes.parent[joint_nodes[0]],
+                };
+                // Joints directly below their reference keep the node's values,
+                // which animation tracks are relative to
+                let transform = if reference == nodes.parent[*node] {
+                    local_transform(gltf_node)
+                } else {
+                    let reference = reference.map_or(origin, |reference| nodes.global[reference]);
+                    Transform::from_matrix(
+                        &(nalgebra_glm::inverse(&reference) * nodes.global[*node]),
+                    )
+                };
+                Joint {
+                    name: unique_name(
+                        &mut names,
+                        gltf_node
+                            .name()
+                            .map_or_else(|| format!("joint{node}"), str::to_string),
+                    ),
+                    parent,
+                    transform,
+                    inverse_bind_matrix: inverse_bind_matrices[*joint],
+                }
+            })
+            .collect();
+        Ok((
+            Skeleton { name, joints },
+            Self {
+                joint_nodes,
+                remap,
+                origin,
+            },
+        ))
+    }
+
+    /// Skeleton joint index of a node.
+    fn joint(&self, node: usize) -> Option<usize> {
+        self.joint_nodes.iter().position(|joint| *joint == node)
+    }
+}
+
+fn import_primitive<'a, 's>(
+    primitive: &'a gltf::Primitive<'a>,
+    name: String,
+    skin: Option<&ImportedSkin>,
+    buffer_data: impl Clone + Fn(gltf::Buffer<'a>) -> Option<&'s [u8]>,
+) -> Result<Mesh, SceneError> {
+    let reader = primitive.reader(buffer_data);
+    let positions: Vec<nalgebra_glm::Vec3> = reader
+        .read_positions()
+        .ok_or_else(|| SceneError::Invalid(format!("mesh '{name}' has no positions")))?
+        .map(|position| mirror_vector(&position.into()))
+        .collect();
+    let indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..positions.len() as u32).collect(),
+    };
+    let normals: Vec<nalgebra_glm::Vec3> = match reader.read_normals() {
+        Some(normals) => normals
+            .map(|normal| mirror_vector(&normal.into()))
+            .collect(),
+        None => smooth_normals(&positions, &indices),
+    };
+    let colors: Vec<[f32; 4]> = match reader.read_colors(0) {
+        Some(colors) => colors.into_rgba_f32().collect(),
+        None => vec![[1.0; 4]; positions.len()],
+    };
+    let uvs: Vec<[f32; 2]> = match reader.read_tex_coords(0) {
+        Some(uvs) => uvs.into_f32().collect(),
+        None => vec![[0.0; 2]; positions.len()],
+    };
+
+    let skin = match (skin, reader.read_joints(0), reader.read_weights(0)) {
+        (Some(skin), Some(joints), Some(weights)) => joints
+            .into_u16()
+            .zip(weights.into_f32())
+            .map(|(joints, weights)| {
+                let total: f32 = weights.iter().sum();
+                SkinVertex {
+                    joints: joints
+                        .map(|joint| skin.remap.get(joint as usize).copied().unwrap_or_default()),
+                    weights: if total > 0.0 {
+                        weights.map(|weight| weight / total)
+                    } else {
+                        [1.0, 0.0, 0.0, 0.0]
+                    },
+                }
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    Ok(Mesh {
+        name,
+        vertices: positions
+            .iter()
+            .zip(&normals)
+            .zip(&colors)
+            .zip(&uvs)
+            .map(|(((position, normal), color), uv)| Vertex {
+                position: (*position).into(),
+                normal: (*normal).into(),
+                color: *color,
+                uv: *uv,
+            })
+            .collect(),
+        indices,
+        skin,
+        lods: Vec::new(),
+    })
+}
+
+/// Area-weighted vertex normals, for meshes that come without any.
+fn smooth_normals(positions: &[nalgebra_glm::Vec3], indices: &[u32]) -> Vec<nalgebra_glm::Vec3> {
+    let mut normals = vec![nalgebra_glm::Vec3::zeros(); positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|corner| triangle[corner] as usize);
+        if [a, b, c].iter().any(|index| *index >= positions.len()) {
+            continue;
+        }
+        let normal = (positions[b] - positions[a]).cross(&(positions[c] - positions[a]));
+        for index in [a, b, c] {
+            normals[index] += normal;
+        }
+    }
+    normals
+        .into_iter()
+        .map(|normal| {
+            normal
+                .try_normalize(f32::EPSILON)
+                .unwrap_or(nalgebra_glm::Vec3::z())
+        })
+        .collect()
+}
+
+/// Keyframes of one animation channel.
+#[derive(Clone)]
+enum Channel {
+    Translation(Track<nalgebra_glm::Vec3>),
+    Rotation(Track<nalgebra_glm::Quat>),
+    Scale(Track<nalgebra_glm::Vec3>),
+}
+
+impl Channel {
+    fn apply(
+        self,
+        translation: &mut Option<Track<nalgebra_glm::Vec3>>,
+        rotation: &mut Option<Track<nalgebra_glm::Quat>>,
+        scale: &mut Option<Track<nalgebra_glm::Vec3>>,
+    ) {
+        match self {
+            Self::Translation(track) => *translation = Some(track),
+            Self::Rotation(track) => *rotation = Some(track),
+            Self::Scale(track) => *scale = Some(track),
+        }
+    }
+
+    /// The keyframes with `parent` applied, exact unless it scales non-uniformly.
+    fn relative_to(mut self, parent: &Transform) -> Self {
+        match &mut self {
+            Self::Translation(track) => {
+                let matrix = parent.matrix();
+                // Tangents are directions, unaffected by the parent's translation
+                let transform = |value: nalgebra_glm::Vec3, w: f32| {
+                    (matrix * nalgebra_glm::vec4(value.x, value.y, value.z, w)).xyz()
+                };
+                for keyframe in &mut track.keyframes {
+                    keyframe.value = transform(keyframe.value, 1.0);
+                    keyframe.in_tangent =
+                        keyframe.in_tangent.map(|tangent| transform(tangent, 0.0));
+                    keyframe.out_tangent =
+                        keyframe.out_tangent.map(|tangent| transform(tangent, 0.0));
+                }
+            }
+            Self::Rotation(track) => {
+                for keyframe in &mut track.keyframes {
+                    keyframe.value = parent.rotation * keyframe.value;
+                    keyframe.in_tangent =
+                        keyframe.in_tangent.map(|tangent| parent.rotation * tangent);
+                    keyframe.out_tangent = keyframe
+                        .out_tangent
+                        .map(|tangent| parent.rotation * tangent);
+                }
+            }
+            Self::Scale(track) => {
+                for keyframe in &mut track.keyframes {
+                    keyframe.value = parent.scale.component_mul(&keyframe.value);
+                    keyframe.in_tangent = keyframe
+                        .in_tangent
+                        .map(|tangent| parent.scale.component_mul(&tangent));
+                    keyframe.out_tangent = keyframe
+                        .out_tangent
+                        .map(|tangent| parent.scale.component_mul(&tangent));
+                }
+            }
+        }
+        self
+    }
+}
+
+/// Range of imported point lights without one, glTF's are infinite.
+const DEFAULT_LIGHT_RANGE: f32 = 20.0;
+
+/// The light of a node placed by `global`, the node's transform. Spot lights
+/// aren't supported and become point lights.
+fn import_light(light: &gltf::khr_lights_punctual::Light, global: &nalgebra_glm::Mat4) -> Light {
+    use gltf::khr_lights_punctual::Kind;
+
+    let range = light.range().unwrap_or(DEFAULT_LIGHT_RANGE);
+    let kind = match light.kind() {
+        // Lights shine along the node's -z axis
+        Kind::Directional => LightKind::Directional {
+            direction: (global * nalgebra_glm::vec4(0.0, 0.0, -1.0, 0.0))
+                .xyz()
+                .normalize(),
+        },
+        Kind::Point => LightKind::Point {
+            position: global.column(3).xyz(),
+            range,
+        },
+        Kind::Spot { .. } => {
+            tracing::warn!(
+                "Importing spot light {} as a point light, spot lights are not supported",
+                light.index()
+            );
+            LightKind::Point {
+                position: global.column(3).xyz(),
+                range,
+            }
+        }
+    };
+    Light {
+        kind,
+        color: light.color(),
+        intensity: light.intensity(),
+    }
+}
+
+/// A clip without any tracks, named once all of its tracks are known.
+fn empty_clip() -> AnimationClip {
+    AnimationClip {
+        name: String::new(),
+        repeat: Default::default(),
+        translation: None,
+        rotation: None,
+        scale: None,
+        joints: Vec::new(),
+    }
+}
+
+/// Pairs keyframe times with values, cubic splines store an in tangent, the
+/// value and an out tangent for every keyframe.
+fn track<T: Copy>(interpolation: Interpolation, times: &[f32], values: Vec<T>) -> Track<T> {
+    let keyframes = match interpolation {
+        Interpolation::Cubic => times
+            .iter()
+            .zip(values.chunks_exact(3))
+            .map(|(time, chunk)| Keyframe {
+                in_tangent: Some(chunk[0]),
+                out_tangent: Some(chunk[2]),
+                ..Keyframe::new(*time, chunk[1])
+            })
+            .collect(),
+        _ => times
+            .iter()
+            .zip(values)
+            .map(|(time, value)| Keyframe::new(*time, value))
+            .collect(),
+    };
+    Track {
+        interpolation,
+        keyframes,
+    }
+}
+
+fn local_transform(node: &gltf::Node) -> Transform {
+    let (translation, rotation, scale) = node.transform().decomposed();
+    Transform {
+        translation: mirror_vector(&translation.into()),
+        rotation: mirror_rotation(rotation),
+        scale: scale.into(),
+    }
+}
+
+fn mirror_vector(vector: &nalgebra_glm::Vec3) -> nalgebra_glm::Vec3 {
+    nalgebra_glm::vec3(-vector.x, vector.y, vector.z)
+}
+
+fn mirror_rotation([x, y, z, w]: [f32; 4]) -> nalgebra_glm::Quat {
+    nalgebra_glm::quat(x, -y, -z, w)
+}
+
+fn mirror_matrix(matrix: &nalgebra_glm::Mat4) -> nalgebra_glm::Mat4 {
+    let mirror = nalgebra_glm::scaling(&nalgebra_glm::vec3(-1.0, 1.0, 1.0));
+    mirror * matrix * mirror
+}
+
+/// `name`, or `name` with a number appended if it is already taken.
+fn unique_name(names: &mut HashSet<String>, name: String) -> String {
+    let mut candidate = name.clone();
+    let mut number = 1;
+    while !names.insert(candidate.clone()) {
+        number += 1;
+        candidate = format!("{name}.{number}");
+    }
+    candidate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A triangle in the xy plane, with its positions in a data URI.
+    const TRIANGLE: &str = r#"{
+        "asset": { "version": "2.0" },
+        "buffers": [{
+            "byteLength": 36,
+            "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAA"
+        }],
+        "bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+        "accessors": [{
+            "bufferView": 0,
+            "componentType": 5126,
+            "count": 3,
+            "type": "VEC3",
+            "min": [0, 0, 0],
+            "max": [1, 1, 0]
+        }],
+        "meshes": [{
+            "name": "triangle",
+            "primitives": [{ "attributes": { "POSITION": 0 } }]
+        }],
+        "nodes": [{ "mesh": 0 }],
+        "scenes": [{ "nodes": [0] }],
+        "scene": 0
+        SKINS
+    }"#;
+
+    /// Imports `source` from a file named after the calling test.
+    fn import_source(name: &str, source: &str) -> Result<Scene, SceneError> {
+        let path = std::env::temp_dir().join(format!("gltf_import_{name}.gltf"));
+        std::fs::write(&path, source).unwrap();
+        let result = import(&path);
+        let _ = std::fs::remove_file(&path);
+        result
+    }
+
+    #[test]
+    fn embedded_triangle_imports() {
+        let scene = import_source("triangle", &TRIANGLE.replace("SKINS", "")).unwrap();
+        assert_eq!(scene.meshes.len(), 1);
+        assert_eq!(scene.meshes[0].vertices.len(), 3);
+        assert_eq!(scene.meshes[0].indices, [0, 1, 2]);
+        assert_eq!(scene.objects.len(), 1);
+        // Files without lights get a default one
+        assert_eq!(scene.lights.len(), 1);
+    }
+
+    #[test]
+    fn skins_without_joints_are_rejected() {
+        let source = TRIANGLE.replace("SKINS", r#", "skins": [{ "name": "empty", "joints": [] }]"#);
+        match import_source("empty_skin", &source) {
+            Err(SceneError::Invalid(message)) => assert!(message.contains("no joints")),
+            result => panic!("expected a validation error, got {result:?}"),
+        }
+    }
+}
@@ -0,0 +1,599 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use crate::{Duration, HudSettings};
+
+/// Timings and counters of the last frame, shown by the HUD.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameStats {
+    /// Time between the last two frames
+    pub frame_time: Duration,
+    /// Time spent recording and submitting commands for all viewports
+    pub cpu_time: Duration,
+    /// Time the GPU spent on the scene pass, if the device supports timestamp queries
+    pub gpu_time: Option<Duration>,
+    pub draw_calls: u32,
+    pub triangles: u32,
+    /// Objects skipped for being outside of the camera's view
+    pub culled_objects: u32,
+}
+
+/// Draw calls and triangles submitted by a pass, and the objects it culled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DrawStats {
+    pub draw_calls: u32,
+    pub triangles: u32,
+    pub culled_objects: u32,
+}
+
+impl std::ops::AddAssign for DrawStats {
+    fn add_assign(&mut self, other: Self) {
+        self.draw_calls += other.draw_calls;
+        self.triangles += other.triangles;
+        self.culled_objects += other.culled_objects;
+    }
+}
+
+/// Overlay with frame statistics and a scrolling frame-time graph, drawn on
+/// top of the resolved scene.
+pub(crate) struct Hud {
+    pub settings: HudSettings,
+    stats: FrameStats,
+    /// Work of the frame in progress, moved to `stats` when it ends
+    cpu_time: Duration,
+    draw_stats: DrawStats,
+    /// Frame times in milliseconds, oldest first
+    history: VecDeque<f32>,
+    gpu_timer: Option<GpuTimer>,
+    pipeline: wgpu::RenderPipeline,
+    instance_buffer: wgpu::Buffer,
+    instance_capacity: usize,
+}
+
+impl Hud {
+    /// Number of frames shown in the graph
+    const HISTORY_LEN: usize = 120;
+    /// Frame time at the top of the graph
+    const GRAPH_MAX_MS: f32 = 1000.0 / 30.0;
+    /// Frame time marked in the graph, one frame at 60 Hz
+    const GRAPH_TARGET_MS: f32 = 1000.0 / 60.0;
+    /// Height of the graph in font pixels
+    const GRAPH_HEIGHT: f32 = 24.0;
+    /// Distance to the window edges and padding of the panel in font pixels
+    const MARGIN: f32 = 4.0;
+
+    const BACKGROUND_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+    const TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+    const TARGET_LINE_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.4];
+    const FAST_FRAME_COLOR: [f32; 4] = [0.2, 0.9, 0.3, 1.0];
+    const SLOW_FRAME_COLOR: [f32; 4] = [1.0, 0.8, 0.1, 1.0];
+    const DROPPED_FRAME_COLOR: [f32; 4] = [1.0, 0.2, 0.2, 1.0];
+
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        color_format: wgpu::TextureFormat,
+    ) -> Self {
+        let instance_capacity = 1024;
+        Self {
+            settings: HudSettings::default(),
+            stats: FrameStats::default(),
+            cpu_time: Duration::ZERO,
+            draw_stats: DrawStats::default(),
+            history: VecDeque::with_capacity(Self::HISTORY_LEN),
+            gpu_timer: GpuTimer::new(device, queue),
+            pipeline: Self::create_pipeline(device, color_format),
+            instance_buffer: Self::create_instance_buffer(device, instance_capacity),
+            instance_capacity,
+        }
+    }
+
+    /// Rebuilds the GPU resources on another device, keeping the statistics.
+    pub fn recreate(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        color_format: wgpu::TextureFormat,
+    ) -> Self {
+        Self {
+            settings: self.settings,
+            stats: self.stats,
+            history: self.history.clone(),
+            ..Self::new(device, queue, color_format)
+        }
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        self.stats
+    }
+
+    /// Ends the current frame, publishing the work recorded for it.
+    pub fn end_frame(&mut self, device: &wgpu::Device, frame_time: Duration) {
+        if self.history.len() == Self::HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(frame_time.as_secs_f32() * 1000.0);
+
+        if let Some(gpu_timer) = self.gpu_timer.as_mut() {
+            device.poll(wgpu::Maintain::Poll);
+            if let Some(gpu_time) = gpu_timer.read() {
+                self.stats.gpu_time = Some(gpu_time);
+            }
+        }
+        self.stats.frame_time = frame_time;
+        self.stats.cpu_time = std::mem::take(&mut self.cpu_time);
+        let draw_stats = std::mem::take(&mut self.draw_stats);
+        self.stats.draw_calls = draw_stats.draw_calls;
+        self.stats.triangles = draw_stats.triangles;
+        self.stats.culled_objects = draw_stats.culled_objects;
+    }
+
+    /// Adds the work of rendering one viewport to the current frame.
+    pub fn record(&mut self, cpu_time: Duration, draw_stats: DrawStats) {
+        self.cpu_time += cpu_time;
+        self.draw_stats += draw_stats;
+    }
+
+    /// Timestamp writes for the scene pass, if a measurement can be started.
+    pub fn timestamp_writes(&self) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        self.gpu_timer
+            .as_ref()
+            .filter(|gpu_timer| !gpu_timer.in_flight)
+            .map(GpuTimer::timestamp_writes)
+    }
+
+    /// Copies the timestamps written by the scene pass for reading back.
+    pub fn resolve_timestamps(&self, encoder: &mut wgpu::CommandEncoder) {
+        if let Some(gpu_timer) = self.gpu_timer.as_ref().filter(|timer| !timer.in_flight) {
+            gpu_timer.resolve(encoder);
+        }
+    }
+
+    /// Starts reading back the timestamps once the commands have been submitted.
+    pub fn map_timestamps(&mut self) {
+        if let Some(gpu_timer) = self.gpu_timer.as_mut().filter(|timer| !timer.in_flight) {
+            gpu_timer.map();
+        }
+    }
+
+    /// Draws the overlay on top of `color_view`, which must not be multisampled.
+    pub fn encode(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        color_view: &wgpu::TextureView,
+        (width, height): (u32, u32),
+    ) {
+        let rects = self.layout();
+        if rects.is_empty() {
+            return;
+        }
+
+        // Pixel coordinates from the top left corner to clip space
+        let scale = [2.0 / width as f32, -2.0 / height as f32];
+        let instances = rects
+            .iter()
+            .map(|rect| RectInstance {
+                position: [
+                    rect.position[0] * scale[0] - 1.0,
+                    rect.position[1] * scale[1] + 1.0,
+                ],
+                size: [rect.size[0] * scale[0], rect.size[1] * scale[1]],
+                color: rect.color,
+            })
+            .collect::<Vec<_>>();
+
+        if instances.len() > self.instance_capacity {
+            self.instance_capacity = instances.len().next_power_of_two();
+            self.instance_buffer = Self::create_instance_buffer(device, self.instance_capacity);
+        }
+        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&instances));
+
+        encoder.insert_debug_marker("Render HUD");
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("HUD Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        render_pass.draw(0..6, 0..instances.len() as u32);
+    }
+
+    /// The rectangles making up the overlay, in pixels.
+    fn layout(&self) -> Vec<Rect> {
+        if !self.settings.visible {
+            return Vec::new();
+        }
+
+        let pixel = self.settings.scale.max(1) as f32;
+        let average_ms = match self.history.len() {
+            0 => 0.0,
+            len => self.history.iter().sum::<f32>() / len as f32,
+        };
+        let fps = if average_ms > 0.0 {
+            1000.0 / average_ms
+        } else {
+            0.0
+        };
+        let gpu_time = match self.stats.gpu_time {
+            Some(gpu_time) => format!("GPU   {:6.2} MS", gpu_time.as_secs_f32() * 1000.0),
+            None => "GPU      N/A".to_string(),
+        };
+        let lines = [
+            format!("FPS   {fps:6.1}"),
+            format!(
+                "FRAME {:6.2} MS",
+                self.stats.frame_time.as_secs_f32() * 1000.0
+            ),
+            format!(
+                "CPU   {:6.2} MS",
+                self.stats.cpu_time.as_secs_f32() * 1000.0
+            ),
+            gpu_time,
+            format!("DRAWS {:6}", self.stats.draw_calls),
+            format!("TRIS  {:6}", self.stats.triangles),
+            format!("CULL  {:6}", self.stats.culled_objects),
+        ];
+
+        let margin = Self::MARGIN * pixel;
+        let line_height = (GLYPH_HEIGHT + 2) as f32 * pixel;
+        let text_width = lines
+            .iter()
+            .map(|line| line.chars().count() as f32 * GLYPH_ADVANCE as f32 * pixel)
+            .fold(0.0, f32::max);
+        let graph_width = Self::HISTORY_LEN as f32 * pixel;
+        let graph_height = Self::GRAPH_HEIGHT * pixel;
+        let text_height = lines.len() as f32 * line_height;
+
+        let mut rects = vec![Rect {
+            position: [margin, margin],
+            size: [
+                text_width.max(graph_width) + 2.0 * margin,
+                text_height + graph_height + 3.0 * margin,
+            ],
+            color: Self::BACKGROUND_COLOR,
+        }];
+
+        let origin = [2.0 * margin, 2.0 * margin];
+        for (index, line) in lines.iter().enumerate() {
+            let position = [origin[0], origin[1] + index as f32 * line_height];
+            push_text(&mut rects, line, position, pixel, Self::TEXT_COLOR);
+        }
+
+        // Newest frame on the right, bars grow upwards from the bottom of the graph
+        let graph_bottom = origin[1] + text_height + margin + graph_height;
+        let graph_left = origin[0] + graph_width - self.history.len() as f32 * pixel;
+        for (index, frame_ms) in self.history.iter().enumerate() {
+            let bar_height = (frame_ms / Self::GRAPH_MAX_MS).clamp(0.0, 1.0) * graph_height;
+            let color = if *frame_ms <= Self::GRAPH_TARGET_MS * 1.05 {
+                Self::FAST_FRAME_COLOR
+            } else if *frame_ms <= Self::GRAPH_MAX_MS {
+                Self::SLOW_FRAME_COLOR
+            } else {
+                Self::DROPPED_FRAME_COLOR
+            };
+            rects.push(Rect {
+                position: [graph_left + index as f32 * pixel, graph_bottom - bar_height],
+                size: [pixel, bar_height.max(1.0)],
+                color,
+            });
+        }
+        let target_height = Self::GRAPH_TARGET_MS / Self::GRAPH_MAX_MS * graph_height;
+        rects.push(Rect {
+            position: [origin[0], graph_bottom - target_height],
+            size: [graph_width, 1.0],
+            color: Self::TARGET_LINE_COLOR,
+        });
+
+        rects
+    }
+
+    fn create_instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("HUD Instance Buffer"),
+            size: (capacity * std::mem::size_of::<RectInstance>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        color_format: wgpu::TextureFormat,
+    ) -> wgpu::RenderPipeline {
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("HUD Shader"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(HUD_SHADER_SOURCE)),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("HUD Pipeline Layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("HUD Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: "vertex_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<RectInstance>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x2,
+                        1 => Float32x2,
+                        2 => Float32x4
+                    ],
+                }],
+                compilation_options: Default::default(),
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: "fragment_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            multiview: None,
+            cache: None,
+        })
+    }
+}
+
+/// Measures the duration of a pass with a pair of timestamp queries.
+struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    /// Nanoseconds per timestamp tick
+    period: f32,
+    /// Whether the readback buffer is being mapped or waiting to be read
+    in_flight: bool,
+    mapped: Arc<AtomicBool>,
+    /// Set when mapping failed, so the next measurement can start anyway
+    map_failed: Arc<AtomicBool>,
+}
+
+impl GpuTimer {
+    const QUERY_COUNT: u32 = 2;
+    const BUFFER_SIZE: wgpu::BufferAddress =
+        Self::QUERY_COUNT as wgpu::BufferAddress * wgpu::QUERY_SIZE as wgpu::BufferAddress;
+
+    /// `None` when the device was created without timestamp queries.
+    fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+        Some(Self {
+            query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("GPU Timer Queries"),
+                ty: wgpu::QueryType::Timestamp,
+                count: Self::QUERY_COUNT,
+            }),
+            resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("GPU Timer Resolve Buffer"),
+                size: Self::BUFFER_SIZE,
+                usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            readback_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("GPU Timer Readback Buffer"),
+                size: Self::BUFFER_SIZE,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }),
+            period: queue.get_timestamp_period(),
+            in_flight: false,
+            mapped: Arc::new(AtomicBool::new(false)),
+            map_failed: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    fn timestamp_writes(&self) -> wgpu::RenderPassTimestampWrites<'_> {
+        wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(0),
+            end_of_pass_write_index: Some(1),
+        }
+    }
+
+    fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.resolve_query_set(
+            &self.query_set,
+            0..Self::QUERY_COUNT,
+            &self.resolve_buffer,
+            0,
+        );
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            Self::BUFFER_SIZE,
+        );
+    }
+
+    fn map(&mut self) {
+        self.in_flight = true;
+        let (mapped, map_failed) = (self.mapped.clone(), self.map_failed.clone());
+        self.readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| match result {
+                Ok(()) => mapped.store(true, Ordering::Release),
+                Err(_) => map_failed.store(true, Ordering::Release),
+            });
+    }
+
+    /// The measured duration, once the readback buffer has been mapped.
+    fn read(&mut self) -> Option<Duration> {
+        if self.map_failed.swap(false, Ordering::Acquire) {
+            tracing::warn!("Failed to read back the GPU timestamps");
+            self.in_flight = false;
+            return None;
+        }
+        if !self.mapped.swap(false, Ordering::Acquire) {
+            return None;
+        }
+        let timestamps: [u64; 2] = {
+            let data = self.readback_buffer.slice(..).get_mapped_range();
+            bytemuck::pod_read_unaligned(&data)
+        };
+        self.readback_buffer.unmap();
+        self.in_flight = false;
+
+        let ticks = timestamps[1].saturating_sub(timestamps[0]);
+        Some(Duration::from_nanos(
+            (ticks as f64 * self.period as f64) as u64,
+        ))
+    }
+}
+
+/// An axis-aligned rectangle in pixels from the top left corner.
+struct Rect {
+    position: [f32; 2],
+    size: [f32; 2],
+    color: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct RectInstance {
+    /// Top left corner in clip space
+    position: [f32; 2],
+    size: [f32; 2],
+    color: [f32; 4],
+}
+
+const GLYPH_WIDTH: u32 = 3;
+const GLYPH_HEIGHT: u32 = 5;
+/// Horizontal distance between characters, including spacing
+const GLYPH_ADVANCE: u32 = GLYPH_WIDTH + 1;
+
+/// Adds a rectangle for every lit pixel of the glyphs in `text`.
+fn push_text(rects: &mut Vec<Rect>, text: &str, position: [f32; 2], pixel: f32, color: [f32; 4]) {
+    for (index, character) in text.chars().enumerate() {
+        let bits = glyph(character);
+        let left = position[0] + (index as u32 * GLYPH_ADVANCE) as f32 * pixel;
+        for row in 0..GLYPH_HEIGHT {
+            for column in 0..GLYPH_WIDTH {
+                let bit = (GLYPH_HEIGHT - 1 - row) * GLYPH_WIDTH + (GLYPH_WIDTH - 1 - column);
+                if bits & (1 << bit) != 0 {
+                    rects.push(Rect {
+                        position: [
+                            left + column as f32 * pixel,
+                            position[1] + row as f32 * pixel,
+                        ],
+                        size: [pixel, pixel],
+                        color,
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// A 3x5 pixel glyph, one row of three bits per line from the top.
+fn glyph(character: char) -> u16 {
+    match character.to_ascii_uppercase() {
+        ' ' => 0,
+        '0' => 0b111_101_101_101_111,
+        '1' => 0b010_110_010_010_111,
+        '2' => 0b111_001_111_100_111,
+        '3' => 0b111_001_111_001_111,
+        '4' => 0b101_101_111_001_001,
+        '5' => 0b111_100_111_001_111,
+        '6' => 0b111_100_111_101_111,
+        '7' => 0b111_001_001_001_001,
+        '8' => 0b111_101_111_101_111,
+        '9' => 0b111_101_111_001_111,
+        'A' => 0b010_101_111_101_101,
+        'B' => 0b110_101_110_101_110,
+        'C' => 0b011_100_100_100_011,
+        'D' => 0b110_101_101_101_110,
+        'E' => 0b111_100_110_100_111,
+        'F' => 0b111_100_110_100_100,
+        'G' => 0b011_100_101_101_011,
+        'H' => 0b101_101_111_101_101,
+        'I' => 0b111_010_010_010_111,
+        'J' => 0b001_001_001_101_010,
+        'K' => 0b101_101_110_101_101,
+        'L' => 0b100_100_100_100_111,
+        'M' => 0b101_111_111_101_101,
+        'N' => 0b110_101_101_101_101,
+        'O' => 0b010_101_101_101_010,
+        'P' => 0b110_101_110_100_100,
+        'Q' => 0b010_101_101_110_011,
+        'R' => 0b110_101_110_101_101,
+        'S' => 0b011_100_010_001_110,
+        'T' => 0b111_010_010_010_010,
+        'U' => 0b101_101_101_101_111,
+        'V' => 0b101_101_101_101_010,
+        'W' => 0b101_101_111_111_101,
+        'X' => 0b101_101_010_101_101,
+        'Y' => 0b101_101_010_010_010,
+        'Z' => 0b111_001_010_100_111,
+        '.' => 0b000_000_000_000_010,
+        ':' => 0b000_010_000_010_000,
+        '-' => 0b000_000_111_000_000,
+        '/' => 0b001_001_010_100_100,
+        _ => 0b111_001_010_000_010,
+    }
+}
+
+const HUD_SHADER_SOURCE: &str = "
+struct RectInput {
+    @location(0) position: vec2<f32>,
+    @location(1) size: vec2<f32>,
+    @location(2) color: vec4<f32>,
+};
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vertex_main(@builtin(vertex_index) vertex_index: u32, rect: RectInput) -> VertexOutput {
+    // Two triangles covering the rectangle
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+    );
+    var out: VertexOutput;
+    out.position = vec4<f32>(rect.position + corners[vertex_index] * rect.size, 0.0, 1.0);
+    out.color = rect.color;
+    return out;
+};
+
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
+";
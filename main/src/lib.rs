@@ -1,9 +1,17 @@
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 use winit::{
     application::ApplicationHandler,
     dpi::PhysicalSize,
     event::WindowEvent,
-    window::Window,
+    event_loop::EventLoopProxy,
+    window::{Window, WindowId},
 };
 
 #[cfg(target_arch = "wasm32")]
@@ -18,25 +26,604 @@ pub use web_time::{Duration, Instant};
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
+mod ambient_occlusion;
+mod animation;
+mod assets;
+mod bounds;
+mod camera;
+#[cfg(not(target_arch = "wasm32"))]
+mod capture;
+mod config;
+mod debug_view;
+mod deferred;
+mod environment;
+#[cfg(not(target_arch = "wasm32"))]
+mod gltf_import;
+mod hud;
+mod loader;
+mod primitives;
+mod scene;
+mod settings;
+mod simplify;
+mod skeleton;
+mod skybox;
+mod terrain;
+mod transform;
+mod transparency;
+mod window_mode;
+
+pub use animation::{
+    AnimationClip, AnimationPlayer, Interpolation, JointAnimation, Keyframe, Repeat, Track,
+    TrackValue,
+};
+pub use bounds::{BoundingBox, BoundingSphere, Bounds, Frustum};
+pub use camera::Camera;
+#[cfg(not(target_arch = "wasm32"))]
+pub use capture::{render_frames, OffscreenTarget, Recording, RecordingFormat};
+pub use config::{AppConfig, ConfigError, ConfigStore};
+pub use debug_view::DebugView;
+pub use deferred::RenderPath;
+pub use environment::Environment;
+pub use hud::{DrawStats, FrameStats};
+pub use loader::LoadedScene;
+pub use scene::{
+    Light, LightKind, Lod, LodSource, Material, Mesh, Object, Scene, SceneError, SkinVertex, Vertex,
+};
+pub use settings::{
+    select_present_mode, AmbientOcclusionSettings, DisplaySettings, FrameLimiter, HudSettings,
+    RendererSettings, SceneSettings, SimulationClock, SimulationSettings,
+};
+pub use simplify::simplify;
+pub use skeleton::{Joint, Skeleton};
+pub use skybox::{Cubemap, Skybox};
+pub use terrain::Terrain;
+pub use transform::Transform;
+pub use transparency::Transparency;
+pub use window_mode::{WindowMode, WindowSettings};
+
+use ambient_occlusion::AmbientOcclusionRenderer;
+use assets::{AssetKey, AssetStore, Assets, Handle};
+use debug_view::{DepthViewRenderer, DEBUG_SHADER_SOURCE, DEBUG_SKINNING_SHADER_SOURCE};
+use deferred::{DeferredRenderer, GBuffer, GBUFFER_SHADER_SOURCE};
+use environment::{EnvironmentBaker, EnvironmentMaps};
+use hud::Hud;
+use skybox::{ReadFile, SkyboxRenderer};
+use transparency::{WeightedBlendedRenderer, ACCUMULATE_SHADER_SOURCE};
+
+/// Events sent to the [`App`] from outside the event loop.
+pub enum AppEvent {
+    /// A scene finished loading in the background
+    SceneLoaded {
+        path: PathBuf,
+        /// Which [`App::load_scene`] call this answers, only the latest one is applied
+        request: u64,
+        result: Result<LoadedScene, SceneError>,
+    },
+}
+
+/// Changes applied on top of a loaded [`AppConfig`].
+type ConfigOverrides = Box<dyn Fn(&mut AppConfig)>;
+
 #[derive(Default)]
 pub struct App {
-    window: Option<Arc<Window>>,
-    renderer: Option<Renderer<'static>>,
+    windows: HashMap<WindowId, AppWindow>,
+    renderer: Option<Renderer>,
     last_render_time: Option<Instant>,
     #[cfg(target_arch = "wasm32")]
-    renderer_receiver: Option<Receiver<Renderer<'static>>>,
-    last_size: (u32, u32),
+    pending_window: Option<(Arc<Window>, Receiver<(Renderer, Viewport<'static>)>)>,
+    #[cfg(target_arch = "wasm32")]
+    pending_recovery: Option<Receiver<Renderer>>,
+    main_window: Option<WindowId>,
+    suspended: bool,
+    config: AppConfig,
+    /// The loaded config with the changes made in the app, without the overrides
+    saved_config: AppConfig,
+    /// Settings for this run only, applied on top of the loaded config
+    overrides: Option<ConfigOverrides>,
+    config_store: ConfigStore,
+    frame_limiter: FrameLimiter,
+    simulation: SimulationClock,
+    scene: Scene,
+    /// Loaded in the background before the renderer was ready for it
+    pending_scene: Option<LoadedScene>,
+    /// For delivering scenes loaded in the background
+    event_proxy: Option<EventLoopProxy<AppEvent>>,
+    /// Number of the latest background load, earlier ones are dropped when they arrive
+    scene_request: u64,
+}
+
+/// A window together with the surface it is rendered to.
+struct AppWindow {
+    viewport: Viewport<'static>,
+    window: Arc<Window>,
+}
+
+impl App {
+    /// Distance of the cameras used by additional windows from the origin
+    const SECONDARY_CAMERA_DISTANCE: f32 = 3.0;
+
+    pub fn new(config: AppConfig, config_store: ConfigStore) -> Self {
+        Self {
+            frame_limiter: FrameLimiter::new(config.display.target_fps),
+            simulation: SimulationClock::new(config.simulation),
+            saved_config: config.clone(),
+            config,
+            config_store,
+            ..Default::default()
+        }
+    }
+
+    /// Layers settings over the loaded config that last for this run only,
+    /// such as command line options. They survive reloads and are never saved.
+    pub fn with_overrides(mut self, overrides: impl Fn(&mut AppConfig) + 'static) -> Self {
+        overrides(&mut self.config);
+        self.frame_limiter = FrameLimiter::new(self.config.display.target_fps);
+        self.simulation = SimulationClock::new(self.config.simulation);
+        self.overrides = Some(Box::new(overrides));
+        self
+    }
+
+    /// Renders `scene` instead of the default one.
+    pub fn with_scene(mut self, scene: Scene) -> Self {
+        self.scene = scene;
+        self
+    }
+
+    /// Lets [`App::load_scene`] deliver scenes through the event loop `proxy` belongs to.
+    pub fn with_event_proxy(mut self, proxy: EventLoopProxy<AppEvent>) -> Self {
+        self.event_proxy = Some(proxy);
+        self
+    }
+
+    /// Starts loading the scene at `path` in the background, a URL relative
+    /// to the page on the web. The current scene is drawn until it is done,
+    /// and replaced by whichever scene was requested last.
+    pub fn load_scene(&mut self, path: impl Into<PathBuf>) {
+        match &self.event_proxy {
+            Some(proxy) => {
+                self.scene_request += 1;
+                loader::spawn_load(path.into(), self.scene_request, proxy.clone());
+            }
+            None => tracing::error!("Loading scenes in the background needs an event proxy"),
+        }
+    }
+
+    /// Switches to a scene loaded in the background, or keeps it for the
+    /// renderer if there is none yet.
+    fn set_loaded_scene(&mut self, loaded: LoadedScene) {
+        self.scene = loaded.scene().clone();
+        let Some(renderer) = self.renderer.as_mut() else {
+            self.pending_scene = Some(loaded);
+            return;
+        };
+        renderer.set_loaded_scene(loaded);
+        let camera = self.main_camera();
+        if let Some(app_window) = self
+            .main_window
+            .and_then(|window_id| self.windows.get_mut(&window_id))
+        {
+            app_window.viewport.camera = camera;
+        }
+    }
+
+    /// Hands the scene to a renderer that was just created.
+    fn upload_scene(&mut self, renderer: &mut Renderer) {
+        match self.pending_scene.take() {
+            Some(loaded) => renderer.set_loaded_scene(loaded),
+            None => {
+                if let Err(error) = renderer.set_scene(&self.scene) {
+                    tracing::error!("Failed to load scene: {error}");
+                }
+            }
+        }
+    }
+
+    /// The scene's camera if it has one, otherwise the configured camera.
+    fn main_camera(&self) -> Camera {
+        self.scene.camera.unwrap_or(self.config.camera)
+    }
+
+    pub fn config(&self) -> &AppConfig {
+        &self.config
+    }
+
+    pub fn display_settings(&self) -> DisplaySettings {
+        self.config.display
+    }
+
+    /// Writes the loaded config with the changes made in the app back to the
+    /// store it was loaded from.
+    pub fn save_config(&self) {
+        match self.config_store.save(&self.saved_config) {
+            Ok(()) => tracing::debug!("Saved config to {:?}", self.config_store),
+            Err(error) => tracing::error!("Failed to save config: {error}"),
+        }
+    }
+
+    /// Reloads the config from its store and applies it to the running app.
+    pub fn reload_config(&mut self) {
+        match self.config_store.load() {
+            Ok(config) => {
+                tracing::info!("Reloaded config from {:?}", self.config_store);
+                let mut effective = config.clone();
+                if let Some(overrides) = self.overrides.as_ref() {
+                    overrides(&mut effective);
+                }
+                self.apply_config(effective);
+                self.saved_config = config;
+            }
+            Err(error) => tracing::error!("Failed to reload config: {error}"),
+        }
+    }
+
+    /// Applies every setting that can change at runtime, renderer settings
+    /// only take effect when the renderer is created.
+    pub fn apply_config(&mut self, mut config: AppConfig) {
+        if config.renderer != self.config.renderer {
+            tracing::warn!("Renderer settings take effect after a restart");
+        }
+        if config.display.present_mode != self.config.display.present_mode {
+            self.set_present_mode(config.display.present_mode);
+            config.display.present_mode = self.config.display.present_mode;
+        }
+        if config.display.target_fps != self.config.display.target_fps {
+            self.set_target_fps(config.display.target_fps);
+        }
+        if let Some(renderer) = self.renderer.as_mut() {
+            renderer.set_scene_settings(config.scene);
+            renderer.set_hud_settings(config.hud);
+        }
+        self.simulation.apply_settings(config.simulation);
+
+        let previous_window = self.config.window;
+        self.config = config;
+
+        let Some(main_window) = self.main_window else {
+            return;
+        };
+        let camera = self.main_camera();
+        if let Some(app_window) = self.windows.get_mut(&main_window) {
+            app_window.viewport.camera = camera;
+            if let Some((width, height)) = self.config.window.size {
+                if self.config.window.size != previous_window.size {
+                    let _ = app_window
+                        .window
+                        .request_inner_size(PhysicalSize::new(width, height));
+                }
+            }
+        }
+        if self.config.window.monitor != previous_window.monitor {
+            self.select_monitor(main_window, self.config.window.monitor);
+        }
+        if self.config.window.mode != previous_window.mode {
+            self.set_window_mode(main_window, self.config.window.mode);
+        }
+    }
+
+    /// Reconfigures the surfaces with a new present mode, keeping the renderer
+    /// intact, and keeps the mode that was actually applied.
+    pub fn set_present_mode(&mut self, present_mode: wgpu::PresentMode) {
+        self.config.display.present_mode = present_mode;
+        if let Some(renderer) = self.renderer.as_ref() {
+            for app_window in self.windows.values_mut() {
+                self.config.display.present_mode =
+                    renderer.set_present_mode(&mut app_window.viewport, present_mode);
+            }
+        }
+        self.saved_config.display.present_mode = self.config.display.present_mode;
+    }
+
+    pub fn set_target_fps(&mut self, target_fps: Option<u32>) {
+        self.config.display.target_fps = target_fps;
+        self.saved_config.display.target_fps = target_fps;
+        self.frame_limiter.set_target_fps(target_fps);
+        tracing::info!("Frame rate limit: {target_fps:?}");
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.simulation.set_paused(paused);
+        tracing::info!("Simulation {}", if paused { "paused" } else { "resumed" });
+    }
+
+    /// Changes the simulation speed, clamped to the supported range.
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        let time_scale = time_scale.clamp(
+            SimulationSettings::MIN_TIME_SCALE,
+            SimulationSettings::MAX_TIME_SCALE,
+        );
+        self.config.simulation.time_scale = time_scale;
+        self.saved_config.simulation.time_scale = time_scale;
+        self.simulation.apply_settings(self.config.simulation);
+        tracing::info!("Time scale: {time_scale}x");
+    }
+
+    pub fn set_hud_visible(&mut self, visible: bool) {
+        self.config.hud.visible = visible;
+        self.saved_config.hud.visible = visible;
+        if let Some(renderer) = self.renderer.as_mut() {
+            renderer.set_hud_settings(self.config.hud);
+        }
+    }
+
+    /// Switches what the scene is drawn as, for inspecting it.
+    pub fn set_debug_view(&mut self, view: DebugView) {
+        self.config.scene.debug_view = view;
+        self.saved_config.scene.debug_view = view;
+        if let Some(renderer) = self.renderer.as_mut() {
+            renderer.set_scene_settings(self.config.scene);
+        }
+        tracing::info!("Debug view: {view:?}");
+    }
+
+    /// Switches how translucent objects are blended.
+    pub fn set_transparency(&mut self, transparency: Transparency) {
+        self.config.scene.transparency = transparency;
+        self.saved_config.scene.transparency = transparency;
+        if let Some(renderer) = self.renderer.as_mut() {
+            renderer.set_scene_settings(self.config.scene);
+        }
+        tracing::info!("Transparency: {transparency:?}");
+    }
+
+    /// Turns screen-space ambient occlusion on or off.
+    pub fn set_ambient_occlusion(&mut self, enabled: bool) {
+        self.config.scene.ambient_occlusion.enabled = enabled;
+        self.saved_config.scene.ambient_occlusion.enabled = enabled;
+        if let Some(renderer) = self.renderer.as_mut() {
+            renderer.set_scene_settings(self.config.scene);
+        }
+        tracing::info!("Ambient occlusion: {}", if enabled { "on" } else { "off" });
+    }
+
+    pub fn window_settings(&self) -> WindowSettings {
+        self.config.window
+    }
+
+    /// Switches a window between windowed, borderless and exclusive fullscreen
+    /// on the selected monitor.
+    pub fn set_window_mode(&mut self, window_id: WindowId, mode: WindowMode) {
+        let Some(app_window) = self.windows.get(&window_id) else {
+            return;
+        };
+        let window = &app_window.window;
+        let monitor = self.config.window.select_monitor(
+            window.available_monitors(),
+            window
+                .current_monitor()
+                .or_else(|| window.primary_monitor()),
+        );
+        tracing::info!("Window mode: {mode:?}");
+        window.set_fullscreen(self.config.window.fullscreen(mode, monitor));
+        self.config.window.mode = mode;
+        self.saved_config.window.mode = mode;
+        self.sync_viewport_size(window_id);
+    }
+
+    /// Moves a window to another monitor, keeping its current window mode.
+    pub fn select_monitor(&mut self, window_id: WindowId, monitor: Option<usize>) {
+        let Some(app_window) = self.windows.get(&window_id) else {
+            return;
+        };
+        self.config.window.monitor = monitor;
+        self.saved_config.window.monitor = monitor;
+
+        let window = app_window.window.clone();
+        match WindowMode::of(&window) {
+            WindowMode::Windowed => {
+                let selected = self
+                    .config
+                    .window
+                    .select_monitor(window.available_monitors(), window.primary_monitor());
+                if let Some(selected) = selected {
+                    tracing::info!("Moving window to monitor {:?}", selected.name());
+                    window.set_outer_position(selected.position());
+                }
+            }
+            mode => self.set_window_mode(window_id, mode),
+        }
+    }
+
+    /// Resizes a viewport to its window when the window size changed without an event,
+    /// as some platforms do when entering fullscreen.
+    fn sync_viewport_size(&mut self, window_id: WindowId) {
+        let (Some(renderer), Some(app_window)) =
+            (self.renderer.as_ref(), self.windows.get_mut(&window_id))
+        else {
+            return;
+        };
+        let PhysicalSize { width, height } = app_window.window.inner_size();
+        let (width, height) = (width.max(1), height.max(1));
+        let config = &app_window.viewport.surface_config;
+        if (config.width, config.height) != (width, height) {
+            renderer.resize(&mut app_window.viewport, width, height);
+        }
+    }
+
+    /// Opens an additional window that shares the renderer's device and scene,
+    /// viewed through its own camera.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn open_window(
+        &mut self,
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        camera: Camera,
+    ) -> Option<WindowId> {
+        let renderer = self.renderer.as_ref()?;
+        let attributes = Window::default_attributes().with_title(format!(
+            "Standalone Winit/Wgpu Example ({})",
+            self.windows.len() + 1
+        ));
+        let window = match event_loop.create_window(attributes) {
+            Ok(window) => Arc::new(window),
+            Err(error) => {
+                tracing::error!("Failed to create window: {error}");
+                return None;
+            }
+        };
+        let PhysicalSize { width, height } = window.inner_size();
+        let viewport = renderer.create_viewport(
+            window.clone(),
+            width.max(1),
+            height.max(1),
+            self.config.display.present_mode,
+            camera,
+        );
+        self.config.display.present_mode = viewport.surface_config.present_mode;
+        let window_id = window.id();
+        self.windows
+            .insert(window_id, AppWindow { viewport, window });
+        Some(window_id)
+    }
+
+    /// Replaces a lost GPU device and rebuilds every viewport on the new one.
+    fn recover_device(&mut self) {
+        let Some(mut renderer) = self.renderer.take() else {
+            return;
+        };
+        tracing::error!("GPU device lost, recreating the renderer");
+
+        // Surfaces of the lost device have to go before new ones are created for the windows
+        for app_window in self.windows.values_mut() {
+            app_window.viewport.suspend();
+        }
+        let compatible_window = self
+            .main_window
+            .and_then(|window_id| self.windows.get(&window_id))
+            .or_else(|| self.windows.values().next())
+            .map(|app_window| wgpu::SurfaceTarget::from(app_window.window.clone()));
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            pollster::block_on(renderer.recover(compatible_window));
+            self.renderer = Some(renderer);
+            self.rebuild_viewports();
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            let (sender, receiver) = futures::channel::oneshot::channel();
+            self.pending_recovery = Some(receiver);
+            wasm_bindgen_futures::spawn_local(async move {
+                renderer.recover(compatible_window).await;
+                if sender.send(renderer).is_err() {
+                    tracing::error!("Failed to send recovered renderer!");
+                }
+            });
+        }
+    }
+
+    fn rebuild_viewports(&mut self) {
+        let Some(renderer) = self.renderer.as_ref() else {
+            return;
+        };
+        for app_window in self.windows.values_mut() {
+            let PhysicalSize { width, height } = app_window.window.inner_size();
+            renderer.rebuild_viewport(
+                &mut app_window.viewport,
+                app_window.window.clone(),
+                width.max(1),
+                height.max(1),
+            );
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn receive_renderer(&mut self) {
+        if let Some(receiver) = self.pending_recovery.as_mut() {
+            match receiver.try_recv() {
+                Ok(Some(renderer)) => {
+                    self.renderer = Some(renderer);
+                    self.pending_recovery = None;
+                    self.rebuild_viewports();
+                    if let Some(loaded) = self.pending_scene.take() {
+                        self.set_loaded_scene(loaded);
+                    }
+                }
+                Ok(None) => {}
+                Err(_) => {
+                    tracing::error!("Device recovery was cancelled!");
+                    self.pending_recovery = None;
+                }
+            }
+        }
+
+        let Some((window, receiver)) = self.pending_window.as_mut() else {
+            return;
+        };
+        match receiver.try_recv() {
+            Ok(Some((mut renderer, mut viewport))) => {
+                let window = window.clone();
+                renderer.set_scene_settings(self.config.scene);
+                renderer.set_hud_settings(self.config.hud);
+                self.upload_scene(&mut renderer);
+                viewport.camera = self.main_camera();
+                self.main_window = Some(window.id());
+                self.windows
+                    .insert(window.id(), AppWindow { viewport, window });
+                self.renderer = Some(renderer);
+                self.pending_window = None;
+            }
+            Ok(None) => window.request_redraw(),
+            Err(_) => {
+                tracing::error!("Renderer creation was cancelled!");
+                self.pending_window = None;
+            }
+        }
+    }
 }
 
-impl ApplicationHandler for App {
+impl ApplicationHandler<AppEvent> for App {
     fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        self.suspended = false;
+
+        #[cfg(target_arch = "wasm32")]
+        let window_pending = self.pending_window.is_some();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let window_pending = false;
+
+        // The main window is only created once, later windows are opened on demand.
+        // Resuming reattaches surfaces to the existing windows, reusing the device
+        // and every scene resource.
+        if let Some(renderer) = self.renderer.as_ref() {
+            for app_window in self.windows.values_mut() {
+                if app_window.viewport.is_suspended() {
+                    let PhysicalSize { width, height } = app_window.window.inner_size();
+                    renderer.resume_viewport(
+                        &mut app_window.viewport,
+                        app_window.window.clone(),
+                        width.max(1),
+                        height.max(1),
+                    );
+                }
+            }
+            // Don't let the time spent suspended advance the scene
+            self.last_render_time = Some(Instant::now());
+            return;
+        }
+        if !self.windows.is_empty() || window_pending {
+            return;
+        }
+
         let mut attributes = Window::default_attributes();
 
         #[cfg(not(target_arch = "wasm32"))]
         {
             attributes = attributes.with_title("Standalone Winit/Wgpu Example");
+            if let Some((width, height)) = self.config.window.size {
+                attributes = attributes.with_inner_size(PhysicalSize::new(width, height));
+            }
         }
 
+        let monitor = self.config.window.select_monitor(
+            event_loop.available_monitors(),
+            event_loop.primary_monitor(),
+        );
+        attributes = attributes.with_fullscreen(
+            self.config
+                .window
+                .fullscreen(self.config.window.mode, monitor),
+        );
+
         #[allow(unused_assignments)]
         #[cfg(target_arch = "wasm32")]
         let (mut canvas_width, mut canvas_height) = (0, 0);
@@ -54,79 +641,86 @@ impl ApplicationHandler for App {
                 .unwrap();
             canvas_width = canvas.width();
             canvas_height = canvas.height();
-            self.last_size = (canvas_width, canvas_height);
             attributes = attributes.with_canvas(Some(canvas));
         }
 
         if let Ok(window) = event_loop.create_window(attributes) {
-            let first_window_handle = self.window.is_none();
             let window_handle = Arc::new(window);
-            self.window = Some(window_handle.clone());
-            if first_window_handle {
-                #[cfg(not(target_arch = "wasm32"))]
-                {
-                    let inner_size = window_handle.inner_size();
-                    self.last_size = (inner_size.width, inner_size.height);
-                }
 
-                #[cfg(not(target_arch = "wasm32"))]
-                let (width, height) = (
-                    window_handle.inner_size().width,
-                    window_handle.inner_size().height,
+            #[cfg(not(target_arch = "wasm32"))]
+            let (width, height) = (
+                window_handle.inner_size().width,
+                window_handle.inner_size().height,
+            );
+
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                let present_mode = self.config.display.present_mode;
+                let surface_target = window_handle.clone();
+                let renderer_settings = &self.config.renderer;
+                let (mut renderer, mut viewport) = pollster::block_on(async move {
+                    Renderer::new(
+                        surface_target,
+                        width,
+                        height,
+                        present_mode,
+                        renderer_settings,
+                    )
+                    .await
+                });
+                renderer.set_scene_settings(self.config.scene);
+                renderer.set_hud_settings(self.config.hud);
+                self.upload_scene(&mut renderer);
+                viewport.camera = self.main_camera();
+                self.renderer = Some(renderer);
+                self.main_window = Some(window_handle.id());
+                self.windows.insert(
+                    window_handle.id(),
+                    AppWindow {
+                        viewport,
+                        window: window_handle,
+                    },
                 );
+            }
 
-                #[cfg(not(target_arch = "wasm32"))]
-                {
-                    let renderer = pollster::block_on(async move {
-                        Renderer::new(window_handle.clone(), width, height).await
-                    });
-                    self.renderer = Some(renderer);
-                }
-
-                #[cfg(target_arch = "wasm32")]
-                {
-                    let (sender, receiver) = futures::channel::oneshot::channel();
-                    self.renderer_receiver = Some(receiver);
-                    tracing::info!("Canvas dimensions: ({canvas_width} x {canvas_height})");
-                    wasm_bindgen_futures::spawn_local(async move {
-                        let renderer =
-                            Renderer::new(window_handle.clone(), canvas_width, canvas_height).await;
-                        if sender.send(renderer).is_err() {
-                            tracing::error!("Failed to create and send renderer!");
-                        }
-                    });
-                }
-
-                self.last_render_time = Some(Instant::now());
+            #[cfg(target_arch = "wasm32")]
+            {
+                let (sender, receiver) = futures::channel::oneshot::channel();
+                self.pending_window = Some((window_handle.clone(), receiver));
+                tracing::info!("Canvas dimensions: ({canvas_width} x {canvas_height})");
+                let present_mode = self.config.display.present_mode;
+                let renderer_settings = self.config.renderer.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    let renderer = Renderer::new(
+                        window_handle,
+                        canvas_width,
+                        canvas_height,
+                        present_mode,
+                        &renderer_settings,
+                    )
+                    .await;
+                    if sender.send(renderer).is_err() {
+                        tracing::error!("Failed to create and send renderer!");
+                    }
+                });
             }
+
+            self.last_render_time = Some(Instant::now());
         }
     }
 
     fn window_event(
         &mut self,
         event_loop: &winit::event_loop::ActiveEventLoop,
-        _window_id: winit::window::WindowId,
+        window_id: WindowId,
         event: winit::event::WindowEvent,
     ) {
         #[cfg(target_arch = "wasm32")]
-        {
-            let mut renderer_received = false;
-            if let Some(receiver) = self.renderer_receiver.as_mut() {
-                if let Ok(Some(renderer)) = receiver.try_recv() {
-                    self.renderer = Some(renderer);
-                    renderer_received = true;
-                }
-            }
-            if renderer_received {
-                self.renderer_receiver = None;
-            }
-        }
+        self.receive_renderer();
 
-        let (Some(renderer), Some(window), Some(last_render_time)) = (
-            self.renderer.as_mut(),
-            self.window.as_ref(),
-            self.last_render_time.as_mut(),
-        ) else {
+        let (Some(renderer), Some(app_window)) =
+            (self.renderer.as_mut(), self.windows.get_mut(&window_id))
+        else {
             return;
         };
 
@@ -136,76 +730,512 @@ impl ApplicationHandler for App {
                 event:
                     winit::event::KeyEvent {
                         physical_key: winit::keyboard::PhysicalKey::Code(key_code),
+                        state: winit::event::ElementState::Pressed,
+                        repeat: false,
                         ..
                     },
                 ..
-            } => {
+            } => match key_code {
                 // Exit by pressing the escape key
-                if matches!(key_code, winit::keyboard::KeyCode::Escape) {
-                    event_loop.exit();
+                winit::keyboard::KeyCode::Escape => event_loop.exit(),
+                // Cycle through the present modes
+                winit::keyboard::KeyCode::KeyV => {
+                    let supported = app_window.viewport.present_modes.clone();
+                    let present_mode = self.config.display.next_present_mode(&supported);
+                    self.set_present_mode(present_mode);
+                    self.save_config();
                 }
-            }
+                // Toggle the frame rate limiter
+                winit::keyboard::KeyCode::KeyL => {
+                    let target_fps = match self.config.display.target_fps {
+                        Some(_) => None,
+                        None => Some(DisplaySettings::DEFAULT_TARGET_FPS),
+                    };
+                    self.set_target_fps(target_fps);
+                    self.save_config();
+                }
+                // Open another view of the scene from a different angle
+                #[cfg(not(target_arch = "wasm32"))]
+                winit::keyboard::KeyCode::KeyN => {
+                    let angle = 90.0 * self.windows.len() as f32;
+                    let camera = Camera::orbiting(angle, Self::SECONDARY_CAMERA_DISTANCE, 1.5);
+                    self.open_window(event_loop, camera);
+                }
+                // Toggle borderless fullscreen
+                winit::keyboard::KeyCode::F11 => {
+                    let mode = match WindowMode::of(&app_window.window) {
+                        WindowMode::Borderless => WindowMode::Windowed,
+                        _ => WindowMode::Borderless,
+                    };
+                    self.set_window_mode(window_id, mode);
+                    self.save_config();
+                }
+                // Toggle exclusive fullscreen
+                winit::keyboard::KeyCode::F10 => {
+                    let mode = match WindowMode::of(&app_window.window) {
+                        WindowMode::Exclusive => WindowMode::Windowed,
+                        _ => WindowMode::Exclusive,
+                    };
+                    self.set_window_mode(window_id, mode);
+                    self.save_config();
+                }
+                // Move the window to the next monitor
+                winit::keyboard::KeyCode::KeyM => {
+                    let monitor_count = app_window.window.available_monitors().count();
+                    if monitor_count > 0 {
+                        let monitor = self.config.window.monitor.map_or(0, |index| index + 1);
+                        self.select_monitor(window_id, Some(monitor % monitor_count));
+                        self.save_config();
+                    }
+                }
+                // Pause or resume the simulation
+                winit::keyboard::KeyCode::Space => {
+                    self.set_paused(!self.simulation.is_paused());
+                }
+                // Advance the paused simulation by a single step
+                winit::keyboard::KeyCode::Period => self.simulation.step(),
+                // Slow down or speed up the simulation
+                winit::keyboard::KeyCode::BracketLeft => {
+                    self.set_time_scale(self.config.simulation.time_scale / 2.0);
+                    self.save_config();
+                }
+                winit::keyboard::KeyCode::BracketRight => {
+                    self.set_time_scale(self.config.simulation.time_scale * 2.0);
+                    self.save_config();
+                }
+                // Toggle the performance overlay
+                winit::keyboard::KeyCode::KeyH => {
+                    self.set_hud_visible(!self.config.hud.visible);
+                    self.save_config();
+                }
+                // Cycle through the debug views
+                winit::keyboard::KeyCode::KeyG => {
+                    self.set_debug_view(self.config.scene.debug_view.next());
+                    self.save_config();
+                }
+                // Toggle order-independent transparency
+                winit::keyboard::KeyCode::KeyT => {
+                    self.set_transparency(self.config.scene.transparency.next());
+                    self.save_config();
+                }
+                // Toggle screen-space ambient occlusion
+                winit::keyboard::KeyCode::KeyO => {
+                    self.set_ambient_occlusion(!self.config.scene.ambient_occlusion.enabled);
+                    self.save_config();
+                }
+                // Reload the config file
+                winit::keyboard::KeyCode::F5 => self.reload_config(),
+                // Simulate a driver reset to test device-lost recovery
+                #[cfg(debug_assertions)]
+                winit::keyboard::KeyCode::F9 => renderer.lose_device(),
+                _ => (),
+            },
             WindowEvent::Resized(PhysicalSize { width, height }) => {
                 let (width, height) = ((width).max(1), (height).max(1));
                 tracing::info!("Resizing renderer surface to: ({width}, {height})");
-                renderer.resize(width, height);
-                self.last_size = (width, height);
+                renderer.resize(&mut app_window.viewport, width, height);
             }
             WindowEvent::CloseRequested => {
-                tracing::info!("Close requested. Exiting...");
-                event_loop.exit();
+                self.windows.remove(&window_id);
+                if self.main_window == Some(window_id) {
+                    self.main_window = None;
+                }
+                if self.windows.is_empty() {
+                    tracing::info!("Last window closed. Exiting...");
+                    event_loop.exit();
+                } else {
+                    tracing::info!("Closed window {window_id:?}");
+                }
             }
             WindowEvent::RedrawRequested => {
-                let now = Instant::now();
-                let delta_time = now - *last_render_time;
-                *last_render_time = now;
-                renderer.render_frame(delta_time);
+                renderer.render_frame(&mut app_window.viewport);
             }
             _ => (),
         }
+    }
+
+    fn user_event(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop, event: AppEvent) {
+        match event {
+            AppEvent::SceneLoaded {
+                path,
+                request,
+                result,
+            } => {
+                // Loads can finish out of order, a later request wins
+                if request != self.scene_request {
+                    tracing::debug!(
+                        "Dropping scene {}, another one was requested",
+                        path.display()
+                    );
+                    return;
+                }
+                match result {
+                    Ok(loaded) => {
+                        tracing::info!("Loaded scene {}", path.display());
+                        self.set_loaded_scene(loaded);
+                    }
+                    Err(error) => {
+                        tracing::error!("Failed to load scene {}: {error}", path.display())
+                    }
+                }
+            }
+        }
+    }
+
+    fn suspended(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        tracing::info!("Suspended, releasing surfaces");
+        self.suspended = true;
+        for app_window in self.windows.values_mut() {
+            app_window.viewport.suspend();
+        }
+        event_loop.set_control_flow(winit::event_loop::ControlFlow::Wait);
+    }
+
+    fn about_to_wait(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        if self.suspended {
+            return;
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        self.receive_renderer();
+
+        if self
+            .renderer
+            .as_ref()
+            .is_some_and(|renderer| renderer.is_device_lost())
+        {
+            self.recover_device();
+        }
+
+        let (Some(renderer), Some(last_render_time)) =
+            (self.renderer.as_mut(), self.last_render_time.as_mut())
+        else {
+            return;
+        };
+
+        let now = Instant::now();
+        if self.frame_limiter.is_frame_due(now) {
+            // The scene is advanced once per frame and then drawn into every window
+            renderer.update(&mut self.simulation, now - *last_render_time);
+            *last_render_time = now;
+            self.frame_limiter.frame_rendered(now);
+            for app_window in self.windows.values() {
+                app_window.window.request_redraw();
+            }
+        }
 
-        window.request_redraw();
+        let control_flow = match self.frame_limiter.next_frame() {
+            Some(next_frame) => winit::event_loop::ControlFlow::WaitUntil(next_frame),
+            None => winit::event_loop::ControlFlow::Wait,
+        };
+        event_loop.set_control_flow(control_flow);
     }
 }
 
-pub struct Renderer<'window> {
-    gpu: Gpu<'window>,
-    depth_texture_view: wgpu::TextureView,
-    scene: Scene,
+/// Owns the GPU device and the scene resources shared by all viewports.
+pub struct Renderer {
+    gpu: Gpu,
+    settings: RendererSettings,
+    target: TargetFormat,
+    scene: GpuScene,
+    hud: Hud,
 }
 
-impl<'window> Renderer<'window> {
-    const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+impl Renderer {
+    /// Color format used when rendering without a surface.
+    const OFFSCREEN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
 
-    pub async fn new(
+    /// Creates the renderer along with a viewport for the window it was created for.
+    pub async fn new<'window>(
         window: impl Into<wgpu::SurfaceTarget<'window>>,
         width: u32,
         height: u32,
-    ) -> Self {
-        let gpu = Gpu::new_async(window, width, height).await;
-        let depth_texture_view = gpu.create_depth_texture(width, height);
+        present_mode: wgpu::PresentMode,
+        settings: &RendererSettings,
+    ) -> (Self, Viewport<'window>) {
+        let instance = Gpu::create_instance(settings);
+        let surface = instance.create_surface(window).unwrap();
+        let gpu = Gpu::new_async(instance, Some(&surface), settings).await;
+
+        let surface_capabilities = surface.get_capabilities(&gpu.adapter);
+        let target = gpu.target_format(Viewport::preferred_format(&surface_capabilities), settings);
+        let uniform_layout = UniformBinding::create_bind_group_layout(&gpu.device);
+        let mut viewport = Viewport::new(
+            &gpu,
+            surface,
+            width,
+            height,
+            present_mode,
+            target,
+            Camera::default(),
+            &uniform_layout,
+        );
 
-        let scene = Scene::new(&gpu.device, gpu.surface_format);
+        let mut scene = GpuScene::new(
+            &gpu.device,
+            &gpu.queue,
+            target,
+            uniform_layout,
+            gpu.supports_skinning(),
+            LoadedScene::default(),
+        );
+        scene.set_render_path(&gpu.device, settings.render_path);
+        let hud = Hud::new(&gpu.device, &gpu.queue, target.color);
 
-        Self {
+        let renderer = Self {
             gpu,
-            depth_texture_view,
+            settings: settings.clone(),
+            target,
             scene,
-        }
-    }
-
-    pub fn resize(&mut self, width: u32, height: u32) {
-        self.gpu.resize(width, height);
-        self.depth_texture_view = self.gpu.create_depth_texture(width, height);
+            hud,
+        };
+        viewport.gbuffer = renderer.create_gbuffer(width, height, &viewport.depth_texture_view);
+        (renderer, viewport)
     }
 
-    pub fn render_frame(
+    /// Creates a renderer without a window, for drawing into offscreen targets only.
+    pub async fn new_headless(settings: &RendererSettings) -> Self {
+        let instance = Gpu::create_instance(settings);
+        let gpu = Gpu::new_async(instance, None, settings).await;
+        let target = gpu.target_format(Self::OFFSCREEN_FORMAT, settings);
+        let uniform_layout = UniformBinding::create_bind_group_layout(&gpu.device);
+        let mut scene = GpuScene::new(
+            &gpu.device,
+            &gpu.queue,
+            target,
+            uniform_layout,
+            gpu.supports_skinning(),
+            LoadedScene::default(),
+        );
+        scene.set_render_path(&gpu.device, settings.render_path);
+        let hud = Hud::new(&gpu.device, &gpu.queue, target.color);
+        Self {
+            gpu,
+            settings: settings.clone(),
+            target,
+            scene,
+            hud,
+        }
+    }
+
+    /// Creates a viewport for another window, sharing this renderer's device and scene.
+    pub fn create_viewport<'window>(
+        &self,
+        window: impl Into<wgpu::SurfaceTarget<'window>>,
+        width: u32,
+        height: u32,
+        present_mode: wgpu::PresentMode,
+        camera: Camera,
+    ) -> Viewport<'window> {
+        let surface = self
+            .gpu
+            .instance
+            .create_surface(window)
+            .expect("Failed to create surface!");
+        let mut viewport = Viewport::new(
+            &self.gpu,
+            surface,
+            width,
+            height,
+            present_mode,
+            self.target,
+            camera,
+            &self.scene.uniform_layout,
+        );
+        viewport.gbuffer = self.create_gbuffer(width, height, &viewport.depth_texture_view);
+        viewport
+    }
+
+    /// The G-buffer for a viewport of `width` by `height`, if shading is deferred.
+    fn create_gbuffer(
+        &self,
+        width: u32,
+        height: u32,
+        depth_view: &wgpu::TextureView,
+    ) -> Option<GBuffer> {
+        self.scene
+            .deferred
+            .as_ref()
+            .map(|deferred| deferred.create_gbuffer(&self.gpu.device, width, height, depth_view))
+    }
+
+    pub fn target_format(&self) -> TargetFormat {
+        self.target
+    }
+
+    pub fn scene_settings(&self) -> SceneSettings {
+        self.scene.settings
+    }
+
+    pub fn set_scene_settings(&mut self, settings: SceneSettings) {
+        self.scene.settings = settings;
+        self.scene.prepare_settings(&self.gpu.device);
+    }
+
+    pub fn scene(&self) -> &Scene {
+        &self.scene.scene
+    }
+
+    /// Replaces the rendered scene, keeping the scene settings and turntable rotation.
+    pub fn set_scene(&mut self, scene: &Scene) -> Result<(), SceneError> {
+        self.set_loaded_scene(LoadedScene::new(scene.clone())?);
+        Ok(())
+    }
+
+    /// Like [`Renderer::set_scene`], with everything but the upload done already.
+    pub fn set_loaded_scene(&mut self, scene: LoadedScene) {
+        self.scene.upload(&self.gpu.device, &self.gpu.queue, scene);
+    }
+
+    pub fn hud_settings(&self) -> HudSettings {
+        self.hud.settings
+    }
+
+    pub fn set_hud_settings(&mut self, settings: HudSettings) {
+        self.hud.settings = settings;
+    }
+
+    /// Statistics of the last completed frame.
+    pub fn frame_stats(&self) -> FrameStats {
+        self.hud.stats()
+    }
+
+    /// Attaches a new surface for `window` to a suspended viewport.
+    pub fn resume_viewport<'window>(
+        &self,
+        viewport: &mut Viewport<'window>,
+        window: impl Into<wgpu::SurfaceTarget<'window>>,
+        width: u32,
+        height: u32,
+    ) {
+        let surface = self
+            .gpu
+            .instance
+            .create_surface(window)
+            .expect("Failed to create surface!");
+        viewport.present_modes = surface.get_capabilities(&self.gpu.adapter).present_modes;
+        viewport.surface = Some(surface);
+        self.resize(viewport, width, height);
+    }
+
+    pub fn resize(&self, viewport: &mut Viewport, width: u32, height: u32) {
+        viewport.resize(&self.gpu, width, height);
+        viewport.gbuffer = self.create_gbuffer(width, height, &viewport.depth_texture_view);
+    }
+
+    pub fn is_device_lost(&self) -> bool {
+        self.gpu.is_device_lost()
+    }
+
+    /// Destroys the device, for exercising device-lost recovery.
+    pub fn lose_device(&self) {
+        tracing::warn!("Destroying the GPU device");
+        // Flag the loss first, the callback doesn't treat destroying as one
+        self.gpu.device_lost.store(true, Ordering::Release);
+        self.gpu.device.destroy();
+        self.gpu.device.poll(wgpu::Maintain::Poll);
+    }
+
+    /// Replaces a lost device with a new adapter and device, recreating the scene's
+    /// pipelines, buffers and textures from its CPU-side state.
+    ///
+    /// Viewports still hold resources of the lost device and have to be passed to
+    /// [`Renderer::rebuild_viewport`] afterwards, their surfaces should be released first.
+    pub async fn recover<'window>(
         &mut self,
-        delta_time: crate::Duration,
+        compatible_window: Option<wgpu::SurfaceTarget<'window>>,
     ) {
-        let delta_time = delta_time.as_secs_f32();
-        self.scene
-            .update(&self.gpu.queue, self.gpu.aspect_ratio(), delta_time);
+        let instance = Gpu::create_instance(&self.settings);
+        let surface = compatible_window.map(|window| {
+            instance
+                .create_surface(window)
+                .expect("Failed to create surface!")
+        });
+        let gpu = Gpu::new_async(instance, surface.as_ref(), &self.settings).await;
+        let color_format = match surface.as_ref() {
+            Some(surface) => Viewport::preferred_format(&surface.get_capabilities(&gpu.adapter)),
+            None => self.target.color,
+        };
+        let target = gpu.target_format(color_format, &self.settings);
+        let uniform_layout = UniformBinding::create_bind_group_layout(&gpu.device);
+
+        self.scene = self.scene.recreate(
+            &gpu.device,
+            &gpu.queue,
+            target,
+            uniform_layout,
+            gpu.supports_skinning(),
+        );
+        self.hud = self.hud.recreate(&gpu.device, &gpu.queue, target.color);
+        self.gpu = gpu;
+        self.target = target;
+        tracing::info!("Recovered from device loss");
+    }
+
+    /// Recreates a viewport's surface and GPU resources on the current device,
+    /// keeping its camera and present mode.
+    pub fn rebuild_viewport<'window>(
+        &self,
+        viewport: &mut Viewport<'window>,
+        window: impl Into<wgpu::SurfaceTarget<'window>>,
+        width: u32,
+        height: u32,
+    ) {
+        viewport.suspend();
+        *viewport = self.create_viewport(
+            window,
+            width,
+            height,
+            viewport.surface_config.present_mode,
+            viewport.camera,
+        );
+    }
+
+    /// Returns the present mode that was applied, which may be a fallback.
+    pub fn set_present_mode(
+        &self,
+        viewport: &mut Viewport,
+        present_mode: wgpu::PresentMode,
+    ) -> wgpu::PresentMode {
+        viewport.set_present_mode(&self.gpu, present_mode)
+    }
+
+    /// Advances the scene simulation in fixed steps of `clock` and sets up the
+    /// interpolation between them, independently of how many viewports draw it.
+    pub fn update(&mut self, clock: &mut SimulationClock, frame_time: crate::Duration) {
+        for _ in 0..clock.advance(frame_time) {
+            self.scene.step(clock.timestep().as_secs_f32());
+        }
+        self.scene.interpolation = clock.alpha();
+        self.hud.end_frame(&self.gpu.device, frame_time);
+    }
+
+    pub fn render_frame(&mut self, viewport: &mut Viewport) {
+        let Some(surface) = viewport.surface.as_ref() else {
+            return;
+        };
+        let surface_texture = match surface.get_current_texture() {
+            Ok(surface_texture) => surface_texture,
+            // The surface needs to be reconfigured, e.g. after resuming or a display change
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                viewport.configure(&self.gpu);
+                return;
+            }
+            Err(wgpu::SurfaceError::Timeout) => {
+                tracing::warn!("Timed out acquiring the surface texture, skipping frame");
+                return;
+            }
+            Err(error) => {
+                tracing::error!("Failed to get surface texture, rebuilding the surface: {error}");
+                viewport.configure(&self.gpu);
+                return;
+            }
+        };
+        let started = Instant::now();
+
+        let aspect_ratio = viewport.aspect_ratio();
+        let frustum = self.update_uniform(&mut viewport.uniform, &viewport.camera, aspect_ratio);
+        self.scene.select_lods(&frustum, &mut viewport.lods);
 
         let mut encoder = self
             .gpu
@@ -214,20 +1244,13 @@ impl<'window> Renderer<'window> {
                 label: Some("Render Encoder"),
             });
 
-
-        let surface_texture = self
-            .gpu
-            .surface
-            .get_current_texture()
-            .expect("Failed to get surface texture!");
-
         let surface_texture_view =
             surface_texture
                 .texture
                 .create_view(&wgpu::TextureViewDescriptor {
                     label: wgpu::Label::default(),
                     aspect: wgpu::TextureAspect::default(),
-                    format: Some(self.gpu.surface_format),
+                    format: Some(self.target.color),
                     dimension: None,
                     base_mip_level: 0,
                     mip_level_count: None,
@@ -235,63 +1258,389 @@ impl<'window> Renderer<'window> {
                     array_layer_count: None,
                 });
 
+        let draw_stats = self.encode_frame(
+            &mut encoder,
+            &surface_texture_view,
+            viewport.msaa_texture_view.as_ref(),
+            &viewport.depth_texture_view,
+            viewport.gbuffer.as_ref(),
+            &viewport.uniform,
+            &frustum,
+            &viewport.lods,
+            (
+                viewport.surface_config.width,
+                viewport.surface_config.height,
+            ),
+        );
+
+        self.submit_frame(encoder, started, draw_stats);
+        surface_texture.present();
+    }
+
+    /// Records the scene followed by the HUD.
+    #[allow(clippy::too_many_arguments)]
+    fn encode_frame(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        color_view: &wgpu::TextureView,
+        msaa_view: Option<&wgpu::TextureView>,
+        depth_view: &wgpu::TextureView,
+        gbuffer: Option<&GBuffer>,
+        uniform: &UniformBinding,
+        frustum: &Frustum,
+        lods: &[usize],
+        size: (u32, u32),
+    ) -> DrawStats {
+        self.scene.update_uniforms(&self.gpu.queue);
+        self.scene.prepare_targets(&self.gpu.device, size);
+        let draw_stats = self.encode_scene(
+            encoder, color_view, msaa_view, depth_view, gbuffer, uniform, frustum, lods,
+        );
+        self.hud.resolve_timestamps(encoder);
+        self.hud
+            .encode(&self.gpu.device, &self.gpu.queue, encoder, color_view, size);
+        draw_stats
+    }
+
+    fn submit_frame(
+        &mut self,
+        encoder: wgpu::CommandEncoder,
+        started: Instant,
+        draw_stats: DrawStats,
+    ) {
+        self.gpu.queue.submit(std::iter::once(encoder.finish()));
+        self.hud.map_timestamps();
+        self.hud.record(started.elapsed(), draw_stats);
+    }
+
+    /// Writes the camera uniform, returning the frustum objects are culled against.
+    fn update_uniform(
+        &self,
+        uniform: &mut UniformBinding,
+        camera: &Camera,
+        aspect_ratio: f32,
+    ) -> Frustum {
+        let contents = UniformBuffer::new(camera, aspect_ratio);
+        uniform.update_buffer(&self.gpu.queue, 0, contents);
+        Frustum::from_view_projection(&contents.view_projection)
+    }
+
+    /// Records the scene passes, resolving into `color_view` when multisampling.
+    /// With a `gbuffer`, the opaque objects are shaded deferred.
+    #[allow(clippy::too_many_arguments)]
+    fn encode_scene(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        color_view: &wgpu::TextureView,
+        msaa_view: Option<&wgpu::TextureView>,
+        depth_view: &wgpu::TextureView,
+        gbuffer: Option<&GBuffer>,
+        uniform: &UniformBinding,
+        frustum: &Frustum,
+        lods: &[usize],
+    ) -> DrawStats {
         encoder.insert_debug_marker("Render scene");
 
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &surface_texture_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.19,
-                            g: 0.24,
-                            b: 0.42,
-                            a: 1.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
+        let mut draw_stats = DrawStats::default();
+        let deferred = self.scene.deferred().zip(gbuffer);
+        if let Some((deferred, gbuffer)) = deferred {
+            let mut render_pass =
+                deferred.begin_geometry(encoder, gbuffer, depth_view, self.hud.timestamp_writes());
+            draw_stats += self.scene.render_opaque(
+                &mut render_pass,
+                uniform,
+                frustum,
+                lods,
+                &deferred.pipelines,
+            );
+            drop(render_pass);
+            draw_stats += match self.scene.ambient_occlusion() {
+                Some(ambient_occlusion) => ambient_occlusion.render(
+                    &self.gpu.device,
+                    encoder,
+                    uniform,
+                    depth_view,
+                    [&gbuffer.occlusion_view, &gbuffer.occlusion_scratch_view],
+                ),
+                None => {
+                    gbuffer.clear_occlusion(encoder);
+                    DrawStats::default()
+                }
+            };
+            draw_stats += deferred.light(
+                encoder,
+                color_view,
+                self.scene.clear_color(),
+                uniform,
+                &self.scene.lights,
+                &self.scene.environment.bind_group,
+                gbuffer,
+            );
+        }
+
+        // Weighted-blended transparency is composited before resolving
+        let weighted_blended = self.scene.weighted_blended();
+        let resolve_view = msaa_view.map(|_| color_view);
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: msaa_view.unwrap_or(color_view),
+                resolve_target: resolve_view.filter(|_| weighted_blended.is_none()),
+                ops: wgpu::Operations {
+                    load: match deferred {
+                        Some(_) => wgpu::LoadOp::Load,
+                        None => wgpu::LoadOp::Clear(self.scene.clear_color()),
                     },
-                })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_texture_view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: wgpu::StoreOp::Store,
-                    }),
-                    stencil_ops: None,
+                    // The multisampled texture is only needed until it is resolved
+                    store: match msaa_view {
+                        Some(_) if weighted_blended.is_none() => wgpu::StoreOp::Discard,
+                        _ => wgpu::StoreOp::Store,
+                    },
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: match deferred {
+                        Some(_) => wgpu::LoadOp::Load,
+                        None => wgpu::LoadOp::Clear(1.0),
+                    },
+                    store: wgpu::StoreOp::Store,
                 }),
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
-            self.scene.render(&mut render_pass);
+                stencil_ops: None,
+            }),
+            timestamp_writes: match deferred {
+                Some(_) => None,
+                None => self.hud.timestamp_writes(),
+            },
+            occlusion_query_set: None,
+        });
+        // After deferred shading, only the skybox and translucent objects are left
+        draw_stats += match deferred {
+            Some(_) => self
+                .scene
+                .render_transparent(&mut render_pass, uniform, frustum, lods),
+            None => self.scene.render(&mut render_pass, uniform, frustum, lods),
+        };
+        drop(render_pass);
+
+        if let Some(weighted_blended) = weighted_blended {
+            let mut render_pass = weighted_blended.begin_accumulation(encoder, depth_view);
+            draw_stats += self.scene.render_weighted_blended(
+                &mut render_pass,
+                uniform,
+                frustum,
+                lods,
+                weighted_blended,
+            );
+            drop(render_pass);
+            draw_stats +=
+                weighted_blended.composite(encoder, msaa_view.unwrap_or(color_view), resolve_view);
         }
 
-        self.gpu.queue.submit(std::iter::once(encoder.finish()));
-        surface_texture.present();
+        if self.scene.settings.debug_view == DebugView::Depth {
+            if let Some(depth_renderer) = self.scene.depth_renderer.as_ref() {
+                draw_stats += depth_renderer.render(
+                    &self.gpu.device,
+                    encoder,
+                    color_view,
+                    depth_view,
+                    uniform,
+                );
+            }
+        }
+        draw_stats
     }
 }
 
-pub struct Gpu<'window> {
-    pub surface: wgpu::Surface<'window>,
-    pub device: wgpu::Device,
-    pub queue: wgpu::Queue,
+/// The formats and sample count every pass of the scene pipeline renders with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TargetFormat {
+    pub color: wgpu::TextureFormat,
+    pub depth: wgpu::TextureFormat,
+    pub sample_count: u32,
+}
+
+/// A surface with its own depth buffer and camera, drawn by a shared [`Renderer`].
+pub struct Viewport<'window> {
+    /// `None` while the app is suspended
+    pub surface: Option<wgpu::Surface<'window>>,
     pub surface_config: wgpu::SurfaceConfiguration,
-    pub surface_format: wgpu::TextureFormat,
+    pub present_modes: Vec<wgpu::PresentMode>,
+    pub depth_texture_view: wgpu::TextureView,
+    pub msaa_texture_view: Option<wgpu::TextureView>,
+    pub camera: Camera,
+    target: TargetFormat,
+    uniform: UniformBinding,
+    /// Set by the renderer when shading is deferred
+    gbuffer: Option<GBuffer>,
+    /// Level of detail of every object, see [`GpuScene::select_lods`]
+    lods: Vec<usize>,
 }
 
-impl<'window> Gpu<'window> {
+impl<'window> Viewport<'window> {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        gpu: &Gpu,
+        surface: wgpu::Surface<'window>,
+        width: u32,
+        height: u32,
+        present_mode: wgpu::PresentMode,
+        target: TargetFormat,
+        camera: Camera,
+        uniform_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let surface_capabilities = surface.get_capabilities(&gpu.adapter);
+        if !surface_capabilities.formats.contains(&target.color) {
+            tracing::warn!("Surface does not support the renderer's {:?}", target.color);
+        }
+
+        let present_mode = select_present_mode(present_mode, &surface_capabilities.present_modes);
+
+        // Prefer an opaque surface, the scene doesn't need to blend with the desktop
+        let alpha_mode = surface_capabilities
+            .alpha_modes
+            .iter()
+            .copied()
+            .find(|mode| *mode == wgpu::CompositeAlphaMode::Opaque)
+            .unwrap_or(surface_capabilities.alpha_modes[0]);
+
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: target.color,
+            width,
+            height,
+            present_mode,
+            alpha_mode,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+
+        surface.configure(&gpu.device, &surface_config);
+
+        Self {
+            surface: Some(surface),
+            surface_config,
+            present_modes: surface_capabilities.present_modes,
+            depth_texture_view: gpu.create_depth_texture(width, height, target),
+            msaa_texture_view: gpu.create_msaa_texture(width, height, target),
+            camera,
+            target,
+            uniform: UniformBinding::new(&gpu.device, uniform_layout),
+            gbuffer: None,
+            lods: Vec::new(),
+        }
+    }
+
+    /// The surface format the renderer's pipelines are built for.
+    fn preferred_format(surface_capabilities: &wgpu::SurfaceCapabilities) -> wgpu::TextureFormat {
+        // This assumes an sRGB surface texture
+        surface_capabilities
+            .formats
+            .iter()
+            .copied()
+            .find(|f| !f.is_srgb()) // egui wants a non-srgb surface texture
+            .unwrap_or(surface_capabilities.formats[0])
+    }
+
     pub fn aspect_ratio(&self) -> f32 {
         self.surface_config.width as f32 / self.surface_config.height.max(1) as f32
     }
 
-    pub fn resize(&mut self, width: u32, height: u32) {
+    pub fn resize(&mut self, gpu: &Gpu, width: u32, height: u32) {
         self.surface_config.width = width;
         self.surface_config.height = height;
-        self.surface.configure(&self.device, &self.surface_config);
+        self.configure(gpu);
+        self.depth_texture_view = gpu.create_depth_texture(width, height, self.target);
+        self.msaa_texture_view = gpu.create_msaa_texture(width, height, self.target);
+    }
+
+    /// Switches to the closest supported present mode and returns the one that was applied.
+    pub fn set_present_mode(
+        &mut self,
+        gpu: &Gpu,
+        present_mode: wgpu::PresentMode,
+    ) -> wgpu::PresentMode {
+        let present_mode = select_present_mode(present_mode, &self.present_modes);
+        tracing::info!("Present mode: {present_mode:?}");
+        self.surface_config.present_mode = present_mode;
+        self.configure(gpu);
+        present_mode
+    }
+
+    fn configure(&self, gpu: &Gpu) {
+        if let Some(surface) = self.surface.as_ref() {
+            surface.configure(&gpu.device, &self.surface_config);
+        }
+    }
+
+    /// Releases the surface, keeping the depth buffer, camera and uniforms
+    /// so drawing can continue once a new surface is attached.
+    pub fn suspend(&mut self) {
+        self.surface = None;
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.surface.is_none()
+    }
+}
+
+pub struct Gpu {
+    pub instance: wgpu::Instance,
+    pub adapter: wgpu::Adapter,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    device_lost: Arc<AtomicBool>,
+}
+
+impl Gpu {
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost.load(Ordering::Acquire)
     }
 
-    pub fn create_depth_texture(&self, width: u32, height: u32) -> wgpu::TextureView {
+    /// Whether vertex shaders can read the joint matrices of skinned meshes.
+    pub fn supports_skinning(&self) -> bool {
+        self.adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::VERTEX_STORAGE)
+            && self.device.limits().max_storage_buffers_per_shader_stage > 0
+    }
+
+    /// Flags the device as lost when the driver resets or the adapter goes away.
+    fn watch_device_lost(device: &wgpu::Device) -> Arc<AtomicBool> {
+        let device_lost = Arc::new(AtomicBool::new(false));
+
+        let lost = device_lost.clone();
+        device.set_device_lost_callback(move |reason, message| {
+            // Only a driver reset is a loss, the other reasons come from our own calls
+            if reason != wgpu::DeviceLostReason::Unknown {
+                tracing::debug!("GPU device lost ({reason:?}): {message}");
+                return;
+            }
+            tracing::error!("GPU device lost: {message}");
+            lost.store(true, Ordering::Release);
+        });
+
+        // Errors are expected while the device is lost, until it has been replaced
+        let lost = device_lost.clone();
+        device.on_uncaptured_error(Box::new(move |error| {
+            if lost.load(Ordering::Acquire) {
+                tracing::warn!("Error on lost device: {error}");
+            } else {
+                panic!("Uncaptured wgpu error: {error}");
+            }
+        }));
+
+        device_lost
+    }
+
+    pub fn create_depth_texture(
+        &self,
+        width: u32,
+        height: u32,
+        target: TargetFormat,
+    ) -> wgpu::TextureView {
         let texture = self.device.create_texture(
             &(wgpu::TextureDescriptor {
                 label: Some("Depth Texture"),
@@ -301,17 +1650,24 @@ impl<'window> Gpu<'window> {
                     depth_or_array_layers: 1,
                 },
                 mip_level_count: 1,
-                sample_count: 1,
+                sample_count: target.sample_count,
                 dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Depth32Float,
-                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
-                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                format: target.depth,
+                usage: match target.sample_count {
+                    1 => {
+                        wgpu::TextureUsages::RENDER_ATTACHMENT
+                            | wgpu::TextureUsages::TEXTURE_BINDING
+                    }
+                    // Multisampled depth can't be sampled like a regular texture, and GL
+                    // can't mix it with the renderbuffer backed color attachment otherwise
+                    _ => wgpu::TextureUsages::RENDER_ATTACHMENT,
+                },
                 view_formats: &[],
             }),
         );
         texture.create_view(&wgpu::TextureViewDescriptor {
             label: None,
-            format: Some(wgpu::TextureFormat::Depth32Float),
+            format: Some(target.depth),
             dimension: Some(wgpu::TextureViewDimension::D2),
             aspect: wgpu::TextureAspect::All,
             base_mip_level: 0,
@@ -321,177 +1677,1484 @@ impl<'window> Gpu<'window> {
         })
     }
 
-    pub async fn new_async(
-        window: impl Into<wgpu::SurfaceTarget<'window>>,
-        width: u32,
-        height: u32,
-    ) -> Self {
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::util::backend_bits_from_env().unwrap_or_else(wgpu::Backends::all),
-            ..Default::default()
-        });
+    /// Creates the multisampled color texture that is resolved into the final target,
+    /// or `None` when multisampling is disabled.
+    pub fn create_msaa_texture(
+        &self,
+        width: u32,
+        height: u32,
+        target: TargetFormat,
+    ) -> Option<wgpu::TextureView> {
+        if target.sample_count <= 1 {
+            return None;
+        }
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("MSAA Color Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: target.sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: target.color,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        Some(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+    }
+
+    /// Resolves the depth format and sample count requested in `settings`,
+    /// lowering the sample count to the highest one supported for both formats.
+    pub fn target_format(
+        &self,
+        color_format: wgpu::TextureFormat,
+        settings: &RendererSettings,
+    ) -> TargetFormat {
+        let depth_format = if settings.depth_format.has_depth_aspect() {
+            settings.depth_format
+        } else {
+            tracing::warn!("{:?} is not a depth format", settings.depth_format);
+            RendererSettings::default().depth_format
+        };
+
+        let adapter_specific = self
+            .device
+            .features()
+            .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+        let supported = |count: u32| {
+            // Without adapter specific format features only 1x and 4x are guaranteed
+            if !adapter_specific {
+                return count == 1 || count == 4;
+            }
+            [color_format, depth_format].iter().all(|format| {
+                self.adapter
+                    .get_texture_format_features(*format)
+                    .flags
+                    .sample_count_supported(count)
+            })
+        };
+        let sample_count = settings.sample_count;
+        let supported_count = [16, 8, 4, 2, 1]
+            .into_iter()
+            .find(|count| *count <= sample_count.max(1) && supported(*count))
+            .unwrap_or(1);
+        if supported_count != sample_count {
+            tracing::warn!("MSAA {sample_count}x is not supported, using {supported_count}x");
+        }
+        TargetFormat {
+            color: color_format,
+            depth: depth_format,
+            sample_count: supported_count,
+        }
+    }
+
+    pub fn create_instance(settings: &RendererSettings) -> wgpu::Instance {
+        wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: settings.backends.unwrap_or_else(|| {
+                wgpu::util::backend_bits_from_env().unwrap_or_else(wgpu::Backends::all)
+            }),
+            ..Default::default()
+        })
+    }
+
+    /// Picks the adapter named in `settings`, if any, or the default one
+    /// compatible with `compatible_surface`.
+    async fn request_adapter(
+        instance: &wgpu::Instance,
+        compatible_surface: Option<&wgpu::Surface<'_>>,
+        settings: &RendererSettings,
+    ) -> wgpu::Adapter {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(name) = settings.adapter.as_deref() {
+            let name = name.to_lowercase();
+            let adapter = instance
+                .enumerate_adapters(wgpu::Backends::all())
+                .into_iter()
+                .filter(|adapter| {
+                    compatible_surface.is_none_or(|surface| adapter.is_surface_supported(surface))
+                })
+                .find(|adapter| adapter.get_info().name.to_lowercase().contains(&name));
+            match adapter {
+                Some(adapter) => return adapter,
+                None => tracing::warn!("No compatible adapter matches '{name}'"),
+            }
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        if settings.adapter.is_some() {
+            tracing::warn!("Adapter selection is not supported on the web");
+        }
+
+        instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface,
+                force_fallback_adapter: false,
+            })
+            .await
+            .expect("Failed to request adapter!")
+    }
+
+    pub async fn new_async(
+        instance: wgpu::Instance,
+        compatible_surface: Option<&wgpu::Surface<'_>>,
+        settings: &RendererSettings,
+    ) -> Self {
+        let adapter = Self::request_adapter(&instance, compatible_surface, settings).await;
+        tracing::info!("WGPU Adapter: {:?}", adapter.get_info());
+
+        let (device, queue) = {
+            tracing::info!("WGPU Adapter Features: {:#?}", adapter.features());
+            adapter
+                .request_device(
+                    &wgpu::DeviceDescriptor {
+                        label: Some("WGPU Device"),
+
+                        // Needed for MSAA sample counts other than 4, GPU timings in the HUD
+                        // and drawing the wireframe debug view with lines
+                        #[cfg(not(target_arch = "wasm32"))]
+                        required_features: adapter.features()
+                            & (wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
+                                | wgpu::Features::TIMESTAMP_QUERY
+                                | wgpu::Features::POLYGON_MODE_LINE),
+
+                        #[cfg(all(target_arch = "wasm32", feature = "webgpu"))]
+                        required_features: adapter.features() & wgpu::Features::TIMESTAMP_QUERY,
+
+                        #[cfg(all(target_arch = "wasm32", feature = "webgl"))]
+                        required_features: wgpu::Features::default(),
+
+                        #[cfg(not(target_arch = "wasm32"))]
+                        required_limits: wgpu::Limits {
+                            max_texture_dimension_2d: 4096, // Allow higher resolutions on native
+                            ..wgpu::Limits::downlevel_defaults()
+                        },
+
+                        #[cfg(all(target_arch = "wasm32", feature = "webgpu"))]
+                        required_limits: wgpu::Limits::default(),
+
+                        #[cfg(all(target_arch = "wasm32", feature = "webgl"))]
+                        required_limits: wgpu::Limits::downlevel_webgl2_defaults(),
+
+                        memory_hints: wgpu::MemoryHints::default(),
+                    },
+                    None,
+                )
+                .await
+                .expect("Failed to request a device!")
+        };
+
+        Self {
+            device_lost: Self::watch_device_lost(&device),
+            instance,
+            adapter,
+            device,
+            queue,
+        }
+    }
+}
+
+/// GPU resources for a [`Scene`], plus the turntable rotation applied to all of it.
+struct GpuScene {
+    /// Kept to rebuild the GPU resources on another device
+    pub scene: Scene,
+    /// State after the latest simulation step
+    pub transform: Transform,
+    /// State before the latest simulation step
+    pub previous_transform: Transform,
+    /// Blend factor between the previous and the latest state used for rendering
+    pub interpolation: f32,
+    pub uniform_layout: wgpu::BindGroupLayout,
+    pub settings: SceneSettings,
+    target: TargetFormat,
+    /// Object uniform plus joint matrices, `None` without skinning support
+    skin_layout: Option<wgpu::BindGroupLayout>,
+    pipelines: ScenePipelines,
+    /// Blending translucent objects over the opaque ones, back to front
+    transparent_pipelines: ScenePipelines,
+    /// Built when weighted-blended transparency is first selected
+    weighted_blended: Option<WeightedBlendedRenderer>,
+    /// Set when the opaque objects are shaded deferred
+    deferred: Option<DeferredRenderer>,
+    /// Built when ambient occlusion is first enabled with deferred shading
+    ambient_occlusion: Option<AmbientOcclusionRenderer>,
+    /// Pipelines of the debug view selected last, built when it is selected
+    debug_pipelines: Option<ScenePipelines>,
+    /// Built when the depth view is first selected
+    depth_renderer: Option<DepthViewRenderer>,
+    /// Meshes, skybox textures, shader modules and materials, shared by
+    /// everything drawn with them
+    assets: Assets,
+    /// The scene's meshes followed by the terrain chunks
+    meshes: Vec<Handle<GpuMesh>>,
+    /// The scene's objects followed by one for every terrain chunk
+    objects: Vec<GpuObject>,
+    /// The scene's meshes with their levels of detail built, followed by the
+    /// terrain chunks, kept to rebuild the GPU meshes
+    source_meshes: Vec<Mesh>,
+    lights: UniformBinding,
+    /// Kept with the scene to rebuild the skybox and environment on another device
+    images: SceneImages,
+    skybox: SkyboxRenderer,
+    /// `None` when the scene has no skybox
+    skybox_texture: Option<Handle<wgpu::TextureView>>,
+    environment_baker: EnvironmentBaker,
+    environment: EnvironmentMaps,
+}
+
+/// Images referenced by a [`Scene`], decoded before it is uploaded.
+#[derive(Clone, Default)]
+struct SceneImages {
+    cubemap: Option<Cubemap>,
+    environment: Option<image::Rgba32FImage>,
+}
+
+impl SceneImages {
+    fn load(scene: &Scene, read: ReadFile) -> Result<Self, SceneError> {
+        let directory = scene.directory.as_deref();
+        Ok(Self {
+            cubemap: scene
+                .skybox
+                .as_ref()
+                .map(|skybox| Cubemap::load(skybox, directory, read))
+                .transpose()?,
+            environment: scene
+                .environment
+                .as_ref()
+                .map(|environment| environment.load(directory, read))
+                .transpose()?,
+        })
+    }
+}
+
+/// Which objects a pipeline draws and how it blends them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ObjectPass {
+    /// Writes depth, so the nearest surface is kept
+    Opaque,
+    /// Blended over what is behind without writing depth
+    Transparent,
+    /// Into the targets of weighted-blended transparency
+    Accumulate,
+    /// Into the G-buffer of deferred shading, writing depth
+    Geometry,
+}
+
+/// Pipelines drawing the scene's objects in one of the views.
+struct ScenePipelines {
+    view: DebugView,
+    pipeline: wgpu::RenderPipeline,
+    /// `None` without skinning support
+    skinned_pipeline: Option<wgpu::RenderPipeline>,
+    /// Modules the pipelines were built from
+    shaders: Vec<Handle<wgpu::ShaderModule>>,
+}
+
+impl ScenePipelines {
+    /// Drops the references to the shader modules, once the pipelines are replaced.
+    fn release(&self, shaders: &mut AssetStore<wgpu::ShaderModule>) {
+        for &shader in &self.shaders {
+            shaders.release(shader);
+        }
+    }
+}
+
+struct GpuMesh {
+    bounds: Bounds,
+    /// Whether any vertex color is translucent
+    translucent: bool,
+    /// The mesh followed by its levels of detail
+    levels: Vec<GpuMeshLevel>,
+}
+
+struct GpuMeshLevel {
+    /// Screen size below which this level is drawn
+    screen_size: f32,
+    vertex_buffer: wgpu::Buffer,
+    /// Joint influences, for skinned meshes
+    skin_buffer: Option<wgpu::Buffer>,
+    index_buffer: wgpu::Buffer,
+    index_count: u32,
+    /// The same triangles without shared vertices, for the wireframe view on
+    /// devices that can't draw lines. Only built once that view is selected.
+    barycentric: Option<Box<GpuMeshLevel>>,
+}
+
+impl GpuMesh {
+    fn new(device: &wgpu::Device, mesh: &Mesh, skinning: bool, barycentric: bool) -> Self {
+        Self {
+            bounds: mesh.bounds(),
+            translucent: mesh.vertices.iter().any(|vertex| vertex.color[3] < 1.0),
+            levels: std::iter::once(GpuMeshLevel::new(
+                device,
+                mesh,
+                f32::INFINITY,
+                skinning,
+                barycentric,
+            ))
+            .chain(mesh.lods.iter().zip(mesh.lod_meshes()).enumerate().map(
+                |(level, (lod, lod_mesh))| {
+                    GpuMeshLevel::new(
+                        device,
+                        &Mesh {
+                            name: format!("{} LOD {}", mesh.name, level + 1),
+                            ..lod_mesh
+                        },
+                        lod.screen_size,
+                        skinning,
+                        barycentric,
+                    )
+                },
+            ))
+            .collect(),
+        }
+    }
+
+    /// Identifies meshes with the same contents, whatever their names, so
+    /// they share one set of buffers.
+    fn key(mesh: &Mesh) -> AssetKey {
+        let write = |hasher: &mut dyn std::hash::Hasher,
+                     vertices: &[Vertex],
+                     indices: &[u32],
+                     skin: &[SkinVertex]| {
+            for bytes in [
+                bytemuck::cast_slice(vertices),
+                bytemuck::cast_slice(indices),
+                bytemuck::cast_slice(skin),
+            ] {
+                hasher.write_usize(bytes.len());
+                hasher.write(bytes);
+            }
+        };
+        AssetKey::content(|hasher| {
+            write(hasher, &mesh.vertices, &mesh.indices, &mesh.skin);
+            for lod in &mesh.lods {
+                hasher.write_u32(lod.screen_size.to_bits());
+                match &lod.source {
+                    LodSource::Simplified(ratio) => hasher.write_u32(ratio.to_bits()),
+                    LodSource::Authored {
+                        vertices,
+                        indices,
+                        skin,
+                    } => write(hasher, vertices, indices, skin),
+                }
+            }
+        })
+    }
+
+    /// Screen sizes have to pass a level's threshold by this fraction to
+    /// switch, so objects near it don't flicker between levels
+    const LOD_HYSTERESIS: f32 = 0.1;
+
+    /// The level to draw at `screen_size`, given the level drawn last.
+    fn select_level(&self, current: usize, screen_size: f32) -> usize {
+        let mut level = current.min(self.levels.len() - 1);
+        while level + 1 < self.levels.len()
+            && screen_size < self.levels[level + 1].screen_size * (1.0 - Self::LOD_HYSTERESIS)
+        {
+            level += 1;
+        }
+        while level > 0
+            && screen_size > self.levels[level].screen_size * (1.0 + Self::LOD_HYSTERESIS)
+        {
+            level -= 1;
+        }
+        level
+    }
+}
+
+impl GpuMeshLevel {
+    fn new(
+        device: &wgpu::Device,
+        mesh: &Mesh,
+        screen_size: f32,
+        skinning: bool,
+        barycentric: bool,
+    ) -> Self {
+        Self {
+            screen_size,
+            vertex_buffer: wgpu::util::DeviceExt::create_buffer_init(
+                device,
+                &wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("{} Vertex Buffer", mesh.name)),
+                    contents: bytemuck::cast_slice(&mesh.vertices),
+                    usage: wgpu::BufferUsages::VERTEX,
+                },
+            ),
+            skin_buffer: (!mesh.skin.is_empty() && skinning).then(|| {
+                wgpu::util::DeviceExt::create_buffer_init(
+                    device,
+                    &wgpu::util::BufferInitDescriptor {
+                        label: Some(&format!("{} Skin Buffer", mesh.name)),
+                        contents: bytemuck::cast_slice(&mesh.skin),
+                        usage: wgpu::BufferUsages::VERTEX,
+                    },
+                )
+            }),
+            index_buffer: wgpu::util::DeviceExt::create_buffer_init(
+                device,
+                &wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("{} Index Buffer", mesh.name)),
+                    contents: bytemuck::cast_slice(&mesh.indices),
+                    usage: wgpu::BufferUsages::INDEX,
+                },
+            ),
+            index_count: mesh.indices.len() as u32,
+            barycentric: barycentric.then(|| {
+                Box::new(Self::new(
+                    device,
+                    &Mesh {
+                        name: format!("{} Barycentric", mesh.name),
+                        ..mesh.unindexed()
+                    },
+                    screen_size,
+                    skinning,
+                    false,
+                ))
+            }),
+        }
+    }
+}
+
+struct GpuObject {
+    mesh: Handle<GpuMesh>,
+    /// State after the latest simulation step
+    transform: Transform,
+    animation: Option<ObjectAnimation>,
+    /// Model matrix of the frame being rendered
+    model: nalgebra_glm::Mat4,
+    /// Position in `GpuScene::objects`, indexing the state viewports keep per object
+    index: usize,
+    /// Drawn after the opaque objects, blended over them
+    transparent: bool,
+    material: Handle<Material>,
+    uniform: UniformBinding,
+    skin: Option<GpuSkin>,
+}
+
+struct GpuSkin {
+    /// Index into `Scene::skeletons`
+    skeleton: usize,
+    /// Local joint transforms after the latest simulation step
+    pose: Vec<Transform>,
+    /// Local joint transforms before the latest simulation step
+    previous_pose: Vec<Transform>,
+    joint_buffer: wgpu::Buffer,
+    /// Object uniform and joint matrices, replacing the object's own bind group
+    bind_group: wgpu::BindGroup,
+}
+
+#[derive(Clone)]
+struct ObjectAnimation {
+    player: AnimationPlayer,
+    /// Index into `Scene::animations`
+    clip: usize,
+    /// State before the latest simulation step
+    previous_transform: Transform,
+}
+
+impl GpuScene {
+    /// Lights beyond this count are ignored
+    const MAX_LIGHTS: usize = 8;
+
+    /// Uploads `scene`.
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        target: TargetFormat,
+        uniform_layout: wgpu::BindGroupLayout,
+        skinning: bool,
+        scene: LoadedScene,
+    ) -> Self {
+        let environment_baker = EnvironmentBaker::new(device);
+        let environment = environment_baker.placeholder(device);
+        let skin_layout = skinning.then(|| Self::create_skin_layout(device));
+        let mut assets = Assets::default();
+        let pipelines = Self::create_pipelines(
+            device,
+            &mut assets.shaders,
+            target,
+            DebugView::Shaded,
+            ObjectPass::Opaque,
+            &uniform_layout,
+            skin_layout.as_ref(),
+            &environment_baker.layout,
+        );
+        let transparent_pipelines = Self::create_pipelines(
+            device,
+            &mut assets.shaders,
+            target,
+            DebugView::Shaded,
+            ObjectPass::Transparent,
+            &uniform_layout,
+            skin_layout.as_ref(),
+            &environment_baker.layout,
+        );
+        let lights =
+            UniformBinding::with_contents(device, &uniform_layout, LightsUniform::default());
+        let skybox = SkyboxRenderer::new(device, target, &uniform_layout);
+        let mut gpu_scene = Self {
+            scene: Scene::default(),
+            transform: Transform::default(),
+            previous_transform: Transform::default(),
+            interpolation: 1.0,
+            uniform_layout,
+            settings: SceneSettings::default(),
+            target,
+            skin_layout,
+            pipelines,
+            transparent_pipelines,
+            weighted_blended: None,
+            deferred: None,
+            ambient_occlusion: None,
+            debug_pipelines: None,
+            depth_renderer: None,
+            assets,
+            meshes: Vec::new(),
+            objects: Vec::new(),
+            source_meshes: Vec::new(),
+            lights,
+            images: SceneImages::default(),
+            skybox,
+            skybox_texture: None,
+            environment_baker,
+            environment,
+        };
+        gpu_scene.upload(device, queue, scene);
+        gpu_scene
+    }
+
+    /// Replaces the meshes, objects, lights, skybox and terrain with the ones
+    /// of `scene`. Meshes, materials and the skybox texture already loaded
+    /// for the previous scene are reused.
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, loaded: LoadedScene) {
+        let LoadedScene {
+            scene,
+            images,
+            meshes,
+        } = loaded;
+        let skinning = self.skin_layout.is_some();
+        let barycentric = self.needs_barycentric_meshes(device);
+        let mesh_handles = meshes
+            .iter()
+            .map(|mesh| {
+                self.assets.meshes.load(GpuMesh::key(mesh), || {
+                    GpuMesh::new(device, mesh, skinning, barycentric)
+                })
+            })
+            .collect();
+        let previous_meshes = std::mem::replace(&mut self.meshes, mesh_handles);
+
+        // Chunks are matched with their objects by index, their names could
+        // clash with the scene's meshes
+        let terrain_objects: Vec<Object> = scene
+            .terrain
+            .iter()
+            .flat_map(|terrain| {
+                meshes[scene.meshes.len()..]
+                    .iter()
+                    .map(|mesh| terrain.object(mesh))
+            })
+            .collect();
+        let objects = scene
+            .objects
+            .iter()
+            .map(|object| {
+                let mesh = scene
+                    .meshes
+                    .iter()
+                    .position(|mesh| mesh.name == object.mesh)
+                    .expect("Scene references an unknown mesh!");
+                (object, mesh)
+            })
+            .chain(terrain_objects.iter().zip(scene.meshes.len()..))
+            .enumerate()
+            .map(|(index, (object, mesh))| {
+                let material = object
+                    .material
+                    .as_deref()
+                    .and_then(|name| scene.material(name))
+                    .cloned()
+                    .unwrap_or_default();
+                let transparent = material.base_color[3] < 1.0
+                    || self.assets.meshes[self.meshes[mesh]].translucent;
+                let material = self
+                    .assets
+                    .materials
+                    .load(Self::material_key(&material), || material);
+                let animation = object.animation.as_ref().map(|player| ObjectAnimation {
+                    player: player.clone(),
+                    clip: scene
+                        .animations
+                        .iter()
+                        .position(|clip| clip.name == player.clip)
+                        .expect("Scene references an unknown animation!"),
+                    previous_transform: object.transform,
+                });
+                let transform = match &animation {
+                    Some(animation) => scene.animations[animation.clip]
+                        .sample(animation.player.time, &object.transform),
+                    None => object.transform,
+                };
+                let uniform = UniformBinding::with_contents(
+                    device,
+                    &self.uniform_layout,
+                    ObjectUniform::default(),
+                );
+                let skin = match (&self.skin_layout, &object.skeleton) {
+                    (Some(skin_layout), Some(skeleton)) if !meshes[mesh].skin.is_empty() => {
+                        let skeleton = scene
+                            .skeletons
+                            .iter()
+                            .position(|candidate| candidate.name == *skeleton)
+                            .expect("Scene references an unknown skeleton!");
+                        let pose = match &animation {
+                            Some(animation) => scene.animations[animation.clip]
+                                .sample_pose(animation.player.time, &scene.skeletons[skeleton]),
+                            None => scene.skeletons[skeleton].rest_pose(),
+                        };
+                        Some(Self::create_skin(
+                            device,
+                            skin_layout,
+                            &uniform,
+                            skeleton,
+                            pose,
+                        ))
+                    }
+                    _ => None,
+                };
+                let mesh = self.meshes[mesh];
+                self.assets.meshes.retain(mesh);
+                GpuObject {
+                    mesh,
+                    transform,
+                    animation: animation.map(|animation| ObjectAnimation {
+                        previous_transform: transform,
+                        ..animation
+                    }),
+                    model: nalgebra_glm::Mat4::identity(),
+                    index,
+                    transparent,
+                    material,
+                    uniform,
+                    skin,
+                }
+            })
+            .collect();
+        for object in std::mem::replace(&mut self.objects, objects) {
+            self.assets.meshes.release(object.mesh);
+            self.assets.materials.release(object.material);
+        }
+        for mesh in previous_meshes {
+            self.assets.meshes.release(mesh);
+        }
+
+        if self.skin_layout.is_none() && scene.meshes.iter().any(|mesh| !mesh.skin.is_empty()) {
+            tracing::warn!(
+                "Skinning is not supported, skinned meshes are drawn in their bind pose"
+            );
+        }
+
+        if scene.lights.len() > Self::MAX_LIGHTS {
+            tracing::warn!(
+                "Scene has {} lights, only the first {} are used",
+                scene.lights.len(),
+                Self::MAX_LIGHTS
+            );
+        }
+        self.lights = UniformBinding::with_contents(
+            device,
+            &self.uniform_layout,
+            LightsUniform::new(
+                scene.ambient_light,
+                &scene.lights,
+                scene
+                    .environment
+                    .as_ref()
+                    .map(|environment| environment.intensity),
+            ),
+        );
+
+        self.environment = match images.environment.as_ref() {
+            Some(panorama) => self.environment_baker.bake(device, queue, panorama),
+            None => self.environment_baker.placeholder(device),
+        };
+        let skybox = scene
+            .skybox
+            .as_ref()
+            .zip(images.cubemap.as_ref())
+            .map(|(skybox, cubemap)| {
+                self.assets.textures.load(
+                    AssetKey::Paths(skybox.paths(scene.directory.as_deref())),
+                    || skybox::create_cube_texture(device, queue, "Skybox Texture", cubemap),
+                )
+            });
+        if let Some(previous) = std::mem::replace(&mut self.skybox_texture, skybox) {
+            self.assets.textures.release(previous);
+        }
+        self.skybox.set_texture(
+            device,
+            self.skybox_texture
+                .map(|skybox| &self.assets.textures[skybox])
+                .or(images
+                    .environment
+                    .as_ref()
+                    .map(|_| &self.environment.environment)),
+        );
+        self.images = images;
+        self.scene = scene;
+        self.source_meshes = meshes;
+        if barycentric {
+            self.upload_barycentric_meshes(device);
+        }
+        self.assets.unload_unused();
+    }
+
+    /// Rebuilds the GPU resources on another device, keeping the scene state.
+    pub fn recreate(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        target: TargetFormat,
+        uniform_layout: wgpu::BindGroupLayout,
+        skinning: bool,
+    ) -> Self {
+        let mut scene = Self {
+            transform: self.transform,
+            previous_transform: self.previous_transform,
+            interpolation: self.interpolation,
+            settings: self.settings,
+            ..Self::new(
+                device,
+                queue,
+                target,
+                uniform_layout,
+                skinning,
+                LoadedScene {
+                    scene: self.scene.clone(),
+                    images: self.images.clone(),
+                    meshes: self.source_meshes.clone(),
+                },
+            )
+        };
+        if self.deferred.is_some() {
+            scene.set_render_path(device, RenderPath::Deferred);
+        }
+        scene.prepare_settings(device);
+        for (object, previous) in scene.objects.iter_mut().zip(&self.objects) {
+            object.transform = previous.transform;
+            object.animation = previous.animation.clone();
+            if let (Some(skin), Some(previous)) = (&mut object.skin, &previous.skin) {
+                skin.pose.clone_from(&previous.pose);
+                skin.previous_pose.clone_from(&previous.previous_pose);
+            }
+        }
+        scene
+    }
+
+    /// Builds what the selected debug view, transparency mode and ambient
+    /// occlusion need and isn't there yet.
+    pub fn prepare_settings(&mut self, device: &wgpu::Device) {
+        if self.settings.ambient_occlusion.enabled && self.ambient_occlusion.is_none() {
+            match self.deferred {
+                Some(_) => {
+                    self.ambient_occlusion =
+                        Some(AmbientOcclusionRenderer::new(device, &self.uniform_layout));
+                }
+                None => tracing::warn!("Ambient occlusion needs the deferred render path"),
+            }
+        }
+
+        if self.settings.transparency == Transparency::Weighted && self.weighted_blended.is_none() {
+            self.weighted_blended = Some(WeightedBlendedRenderer::new(
+                device,
+                self.target,
+                Self::create_pipelines(
+                    device,
+                    &mut self.assets.shaders,
+                    self.target,
+                    DebugView::Shaded,
+                    ObjectPass::Accumulate,
+                    &self.uniform_layout,
+                    self.skin_layout.as_ref(),
+                    &self.environment_baker.layout,
+                ),
+            ));
+        }
+
+        let view = self.settings.debug_view;
+        match view {
+            DebugView::Shaded => {}
+            DebugView::Depth if !DepthViewRenderer::supports(self.target) => tracing::warn!(
+                "The depth view needs a depth buffer without multisampling or stencil"
+            ),
+            DebugView::Depth => {
+                if self.depth_renderer.is_none() {
+                    self.depth_renderer = Some(DepthViewRenderer::new(
+                        device,
+                        self.target,
+                        &self.uniform_layout,
+                    ));
+                }
+            }
+            _ => {
+                if self
+                    .debug_pipelines
+                    .as_ref()
+                    .map(|pipelines| pipelines.view)
+                    != Some(view)
+                {
+                    let pipelines = Self::create_pipelines(
+                        device,
+                        &mut self.assets.shaders,
+                        self.target,
+                        view,
+                        ObjectPass::Opaque,
+                        &self.uniform_layout,
+                        self.skin_layout.as_ref(),
+                        &self.environment_baker.layout,
+                    );
+                    if let Some(previous) = self.debug_pipelines.replace(pipelines) {
+                        previous.release(&mut self.assets.shaders);
+                        self.assets.unload_unused();
+                    }
+                }
+            }
+        }
+
+        if self.needs_barycentric_meshes(device) {
+            self.upload_barycentric_meshes(device);
+        }
+    }
+
+    /// Rebuilds the meshes that don't have the triangles for the wireframe
+    /// view without lines yet.
+    fn upload_barycentric_meshes(&mut self, device: &wgpu::Device) {
+        let skinning = self.skin_layout.is_some();
+        for (mesh, &handle) in self.source_meshes.iter().zip(&self.meshes) {
+            let gpu_mesh = &mut self.assets.meshes[handle];
+            if gpu_mesh
+                .levels
+                .iter()
+                .any(|level| level.barycentric.is_none())
+            {
+                *gpu_mesh = GpuMesh::new(device, mesh, skinning, true);
+            }
+        }
+    }
 
-        let surface = instance.create_surface(window).unwrap();
+    /// Whether the selected view is a wireframe the device can't draw with lines.
+    fn needs_barycentric_meshes(&self, device: &wgpu::Device) -> bool {
+        self.settings.debug_view == DebugView::Wireframe
+            && !device
+                .features()
+                .contains(wgpu::Features::POLYGON_MODE_LINE)
+    }
 
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .expect("Failed to request adapter!");
-        let (device, queue) = {
-            tracing::info!("WGPU Adapter Features: {:#?}", adapter.features());
-            adapter
-                .request_device(
-                    &wgpu::DeviceDescriptor {
-                        label: Some("WGPU Device"),
+    /// The scene's own clear color, or the one from the settings.
+    /// Debug views are drawn on black.
+    pub fn clear_color(&self) -> wgpu::Color {
+        if self.settings.debug_view != DebugView::Shaded {
+            return wgpu::Color::BLACK;
+        }
+        let [r, g, b, a] = self.scene.clear_color.unwrap_or(self.settings.clear_color);
+        wgpu::Color { r, g, b, a }
+    }
 
-                        #[cfg(not(target_arch = "wasm32"))]
-                        required_features: wgpu::Features::default(),
+    /// Writes the object transforms for the current interpolated state.
+    pub fn update_uniforms(&mut self, queue: &wgpu::Queue) {
+        if let Some(ambient_occlusion) = self.ambient_occlusion() {
+            ambient_occlusion.update(queue, self.settings.ambient_occlusion);
+        }
 
-                        #[cfg(all(target_arch = "wasm32", feature = "webgpu"))]
-                        required_features: wgpu::Features::default(),
+        let model = self.model();
+        for object in &mut self.objects {
+            let material = &self.assets.materials[object.material];
+            let transform = match &object.animation {
+                Some(animation) => animation
+                    .previous_transform
+                    .interpolate(&object.transform, self.interpolation),
+                None => object.transform,
+            };
+            let model = model * transform.matrix();
+            object.model = model;
+            object.uniform.update_buffer(
+                queue,
+                0,
+                ObjectUniform {
+                    model,
+                    normal: nalgebra_glm::inverse_transpose(model),
+                    base_color: material.base_color.into(),
+                    emissive: nalgebra_glm::vec4(
+                        material.emissive[0],
+                        material.emissive[1],
+                        material.emissive[2],
+                        0.0,
+                    ),
+                    material: nalgebra_glm::vec4(material.metallic, material.roughness, 0.0, 0.0),
+                },
+            );
+
+            if let Some(skin) = &object.skin {
+                let pose: Vec<Transform> = skin
+                    .previous_pose
+                    .iter()
+                    .zip(&skin.pose)
+                    .map(|(previous, current)| previous.interpolate(current, self.interpolation))
+                    .collect();
+                let matrices = self.scene.skeletons[skin.skeleton].joint_matrices(&pose);
+                queue.write_buffer(&skin.joint_buffer, 0, bytemuck::cast_slice(&matrices));
+            }
+        }
+    }
 
-                        #[cfg(all(target_arch = "wasm32", feature = "webgl"))]
-                        required_features: wgpu::Features::default(),
+    /// Draws the opaque objects, the skybox, and the translucent objects back
+    /// to front, unless those are left to [`Self::render_weighted_blended`].
+    /// Debug views draw all objects alike.
+    pub fn render<'rpass>(
+        &'rpass self,
+        renderpass: &mut wgpu::RenderPass<'rpass>,
+        uniform: &'rpass UniformBinding,
+        frustum: &Frustum,
+        lods: &[usize],
+    ) -> DrawStats {
+        let mut draw_stats = DrawStats::default();
+        if let Some(pipelines) = self
+            .debug_pipelines
+            .as_ref()
+            .filter(|pipelines| pipelines.view == self.settings.debug_view)
+        {
+            let objects = self.visible_objects(frustum, &mut draw_stats, |_| true);
+            draw_stats += self.draw_objects(renderpass, uniform, pipelines, &objects, lods);
+            return draw_stats;
+        }
 
-                        #[cfg(not(target_arch = "wasm32"))]
-                        required_limits: wgpu::Limits {
-                            max_texture_dimension_2d: 4096, // Allow higher resolutions on native
-                            ..wgpu::Limits::downlevel_defaults()
-                        },
+        draw_stats += self.render_opaque(renderpass, uniform, frustum, lods, &self.pipelines);
+        draw_stats += self.render_transparent(renderpass, uniform, frustum, lods);
+        draw_stats
+    }
 
-                        #[cfg(all(target_arch = "wasm32", feature = "webgpu"))]
-                        required_limits: wgpu::Limits::default(),
+    /// Draws the opaque objects with `pipelines`.
+    pub fn render_opaque<'rpass>(
+        &'rpass self,
+        renderpass: &mut wgpu::RenderPass<'rpass>,
+        uniform: &'rpass UniformBinding,
+        frustum: &Frustum,
+        lods: &[usize],
+        pipelines: &'rpass ScenePipelines,
+    ) -> DrawStats {
+        let mut draw_stats = DrawStats::default();
+        let objects = self.visible_objects(frustum, &mut draw_stats, |object| !object.transparent);
+        draw_stats += self.draw_objects(renderpass, uniform, pipelines, &objects, lods);
+        draw_stats
+    }
 
-                        #[cfg(all(target_arch = "wasm32", feature = "webgl"))]
-                        required_limits: wgpu::Limits::downlevel_webgl2_defaults(),
+    /// Draws the skybox behind the opaque objects, then the translucent
+    /// objects back to front, unless those are left to weighted blending.
+    pub fn render_transparent<'rpass>(
+        &'rpass self,
+        renderpass: &mut wgpu::RenderPass<'rpass>,
+        uniform: &'rpass UniformBinding,
+        frustum: &Frustum,
+        lods: &[usize],
+    ) -> DrawStats {
+        let mut draw_stats = DrawStats::default();
+        // After the opaque objects, so it is only shaded where none is in front of it
+        if self.settings.debug_view == DebugView::Shaded {
+            draw_stats += self.skybox.render(renderpass, uniform);
+        }
+        if self.weighted_blended().is_some() {
+            return draw_stats;
+        }
+        let objects = self.visible_objects(frustum, &mut draw_stats, |object| object.transparent);
+        draw_stats += self.draw_objects(
+            renderpass,
+            uniform,
+            &self.transparent_pipelines,
+            &self.back_to_front(objects, frustum),
+            lods,
+        );
+        draw_stats
+    }
 
-                        memory_hints: wgpu::MemoryHints::default(),
-                    },
-                    None,
-                )
-                .await
-                .expect("Failed to request a device!")
+    /// Draws the translucent objects into the pass begun by
+    /// [`WeightedBlendedRenderer::begin_accumulation`], in any order.
+    pub fn render_weighted_blended<'rpass>(
+        &'rpass self,
+        renderpass: &mut wgpu::RenderPass<'rpass>,
+        uniform: &'rpass UniformBinding,
+        frustum: &Frustum,
+        lods: &[usize],
+        weighted_blended: &'rpass WeightedBlendedRenderer,
+    ) -> DrawStats {
+        let mut draw_stats = DrawStats::default();
+        let objects = self.visible_objects(frustum, &mut draw_stats, |object| object.transparent);
+        draw_stats += self.draw_objects(
+            renderpass,
+            uniform,
+            &weighted_blended.pipelines,
+            &objects,
+            lods,
+        );
+        draw_stats
+    }
+
+    /// Shades the opaque objects deferred, if the target allows it, or forward.
+    pub fn set_render_path(&mut self, device: &wgpu::Device, render_path: RenderPath) {
+        if let Some(previous) = self.deferred.take() {
+            previous.pipelines.release(&mut self.assets.shaders);
+        }
+        self.deferred = match render_path {
+            RenderPath::Forward => None,
+            RenderPath::Deferred if !DeferredRenderer::supports(self.target) => {
+                tracing::warn!(
+                    "Deferred shading needs a depth buffer without multisampling or stencil, \
+                     falling back to forward shading"
+                );
+                None
+            }
+            RenderPath::Deferred => Some(DeferredRenderer::new(
+                device,
+                self.target,
+                Self::create_pipelines(
+                    device,
+                    &mut self.assets.shaders,
+                    self.target,
+                    DebugView::Shaded,
+                    ObjectPass::Geometry,
+                    &self.uniform_layout,
+                    self.skin_layout.as_ref(),
+                    &self.environment_baker.layout,
+                ),
+                [
+                    &self.uniform_layout,
+                    &self.uniform_layout,
+                    &self.environment_baker.layout,
+                ],
+            )),
         };
+        self.assets.unload_unused();
+    }
 
-        let surface_capabilities = surface.get_capabilities(&adapter);
+    /// The deferred renderer, if the opaque objects are shaded with it.
+    /// Debug views are always drawn forward.
+    fn deferred(&self) -> Option<&DeferredRenderer> {
+        self.deferred
+            .as_ref()
+            .filter(|_| self.settings.debug_view == DebugView::Shaded)
+    }
 
-        // This assumes an sRGB surface texture
-        let surface_format = surface_capabilities
-            .formats
-            .iter()
-            .copied()
-            .find(|f| !f.is_srgb()) // egui wants a non-srgb surface texture
-            .unwrap_or(surface_capabilities.formats[0]);
+    /// The ambient occlusion renderer, if it is enabled.
+    fn ambient_occlusion(&self) -> Option<&AmbientOcclusionRenderer> {
+        self.ambient_occlusion
+            .as_ref()
+            .filter(|_| self.settings.ambient_occlusion.enabled)
+    }
 
-        let surface_config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface_format,
-            width,
-            height,
-            present_mode: surface_capabilities.present_modes[0],
-            alpha_mode: surface_capabilities.alpha_modes[0],
-            view_formats: vec![],
-            desired_maximum_frame_latency: 2,
-        };
+    /// Sizes the targets of weighted-blended transparency, if it is used,
+    /// for a frame of `size`.
+    pub fn prepare_targets(&mut self, device: &wgpu::Device, size: (u32, u32)) {
+        let used = self.weighted_blended().is_some();
+        if let Some(weighted_blended) = self.weighted_blended.as_mut().filter(|_| used) {
+            weighted_blended.prepare(device, size);
+        }
+    }
 
-        surface.configure(&device, &surface_config);
+    /// The weighted-blended renderer, if translucent objects are drawn with it.
+    fn weighted_blended(&self) -> Option<&WeightedBlendedRenderer> {
+        self.weighted_blended.as_ref().filter(|_| {
+            self.settings.transparency == Transparency::Weighted
+                && self.settings.debug_view == DebugView::Shaded
+        })
+    }
 
-        Self {
-            surface,
-            device,
-            queue,
-            surface_config,
-            surface_format,
+    /// The objects matching `filter` that may be in view, counting the culled ones.
+    fn visible_objects(
+        &self,
+        frustum: &Frustum,
+        draw_stats: &mut DrawStats,
+        filter: impl Fn(&GpuObject) -> bool,
+    ) -> Vec<&GpuObject> {
+        self.objects
+            .iter()
+            .filter(|object| filter(object))
+            .filter(|object| {
+                // Skinned meshes can move anywhere, their bounds only hold in the bind pose
+                let visible = object.skin.is_some()
+                    || frustum.intersects(&self.assets.meshes[object.mesh].bounds, &object.model);
+                if !visible {
+                    draw_stats.culled_objects += 1;
+                }
+                visible
+            })
+            .collect()
+    }
+
+    /// Sorts `objects` by the view depth of their bounding sphere's center,
+    /// furthest first.
+    fn back_to_front<'a>(
+        &'a self,
+        objects: Vec<&'a GpuObject>,
+        frustum: &Frustum,
+    ) -> Vec<&'a GpuObject> {
+        let mut objects: Vec<(f32, &GpuObject)> = objects
+            .into_iter()
+            .map(|object| {
+                let center = self.assets.meshes[object.mesh]
+                    .bounds
+                    .sphere
+                    .transform(&object.model)
+                    .center;
+                (frustum.depth(&center), object)
+            })
+            .collect();
+        objects.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        objects.into_iter().map(|(_, object)| object).collect()
+    }
+
+    /// Updates the level of detail of every object as seen through `frustum`
+    /// in `lods`, which each viewport keeps for itself so the hysteresis
+    /// between levels is tracked per camera.
+    pub fn select_lods(&self, frustum: &Frustum, lods: &mut Vec<usize>) {
+        lods.resize(self.objects.len(), 0);
+        for (object, lod) in self.objects.iter().zip(lods.iter_mut()) {
+            let mesh = &self.assets.meshes[object.mesh];
+            let screen_size = frustum.screen_size(&mesh.bounds.sphere.transform(&object.model));
+            *lod = mesh.select_level(*lod, screen_size);
         }
     }
-}
 
-struct Scene {
-    pub model: nalgebra_glm::Mat4,
-    pub vertex_buffer: wgpu::Buffer,
-    pub index_buffer: wgpu::Buffer,
-    pub uniform: UniformBinding,
-    pub pipeline: wgpu::RenderPipeline,
-}
+    fn draw_objects<'rpass>(
+        &'rpass self,
+        renderpass: &mut wgpu::RenderPass<'rpass>,
+        uniform: &'rpass UniformBinding,
+        pipelines: &'rpass ScenePipelines,
+        objects: &[&'rpass GpuObject],
+        lods: &[usize],
+    ) -> DrawStats {
+        let mut draw_stats = DrawStats::default();
+        if objects.is_empty() {
+            return draw_stats;
+        }
+        renderpass.set_pipeline(&pipelines.pipeline);
+        renderpass.set_bind_group(0, &uniform.bind_group, &[]);
+        renderpass.set_bind_group(1, &self.lights.bind_group, &[]);
+        renderpass.set_bind_group(3, &self.environment.bind_group, &[]);
+
+        let mut skinned = false;
+        for object in objects {
+            let mesh = &self.assets.meshes[object.mesh];
+            let lod = lods.get(object.index).copied().unwrap_or_default();
+            let mut level = &mesh.levels[lod.min(mesh.levels.len() - 1)];
+            if pipelines.view == DebugView::Wireframe {
+                level = level.barycentric.as_deref().unwrap_or(level);
+            }
+            let skin = object.skin.as_ref().zip(level.skin_buffer.as_ref());
+            if skin.is_some() != skinned {
+                skinned = skin.is_some();
+                match &pipelines.skinned_pipeline {
+                    Some(pipeline) if skinned => renderpass.set_pipeline(pipeline),
+                    _ => renderpass.set_pipeline(&pipelines.pipeline),
+                }
+            }
+            match skin {
+                Some((skin, skin_buffer)) => {
+                    renderpass.set_bind_group(2, &skin.bind_group, &[]);
+                    renderpass.set_vertex_buffer(1, skin_buffer.slice(..));
+                }
+                None => renderpass.set_bind_group(2, &object.uniform.bind_group, &[]),
+            }
+            renderpass.set_vertex_buffer(0, level.vertex_buffer.slice(..));
+            renderpass.set_index_buffer(level.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            renderpass.draw_indexed(0..level.index_count, 0, 0..1);
+            draw_stats += DrawStats {
+                draw_calls: 1,
+                triangles: level.index_count / 3,
+                ..Default::default()
+            };
+        }
+        draw_stats
+    }
 
-impl Scene {
-    pub fn new(device: &wgpu::Device, surface_format: wgpu::TextureFormat) -> Self {
-        let vertex_buffer = wgpu::util::DeviceExt::create_buffer_init(
-            device,
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Vertex Buffer"),
-                contents: bytemuck::cast_slice(&VERTICES),
-                usage: wgpu::BufferUsages::VERTEX,
-            },
-        );
-        let index_buffer = wgpu::util::DeviceExt::create_buffer_init(
-            device,
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("index Buffer"),
-                contents: bytemuck::cast_slice(&INDICES),
-                usage: wgpu::BufferUsages::INDEX,
-            },
+    /// Advances the simulation by one fixed step of `timestep` seconds.
+    pub fn step(&mut self, timestep: f32) {
+        self.previous_transform = self.transform;
+        self.transform.rotate(
+            self.settings.rotation_speed.to_radians() * timestep,
+            &nalgebra_glm::Vec3::y(),
         );
-        let uniform = UniformBinding::new(device);
-        let pipeline = Self::create_pipeline(device, surface_format, &uniform);
-        Self {
-            model: nalgebra_glm::Mat4::identity(),
-            uniform,
-            pipeline,
-            vertex_buffer,
-            index_buffer,
+
+        for (object, base) in self.objects.iter_mut().zip(&self.scene.objects) {
+            if let Some(animation) = &mut object.animation {
+                let clip = &self.scene.animations[animation.clip];
+                animation.previous_transform = object.transform;
+                animation.player.advance(clip, timestep);
+                object.transform = clip.sample(animation.player.time, &base.transform);
+                if let Some(skin) = &mut object.skin {
+                    let pose = clip
+                        .sample_pose(animation.player.time, &self.scene.skeletons[skin.skeleton]);
+                    skin.previous_pose = std::mem::replace(&mut skin.pose, pose);
+                }
+            }
         }
     }
 
-    pub fn render<'rpass>(&'rpass self, renderpass: &mut wgpu::RenderPass<'rpass>) {
-        renderpass.set_pipeline(&self.pipeline);
-        renderpass.set_bind_group(0, &self.uniform.bind_group, &[]);
+    /// Model matrix interpolated between the last two simulation steps.
+    pub fn model(&self) -> nalgebra_glm::Mat4 {
+        self.previous_transform
+            .interpolate(&self.transform, self.interpolation)
+            .matrix()
+    }
 
-        renderpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        renderpass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+    /// Identifies materials with the same name and values.
+    fn material_key(material: &Material) -> AssetKey {
+        AssetKey::content(|hasher| {
+            hasher.write(material.name.as_bytes());
+            hasher.write(bytemuck::cast_slice(&material.base_color));
+            hasher.write(bytemuck::cast_slice(&material.emissive));
+            hasher.write_u32(material.metallic.to_bits());
+            hasher.write_u32(material.roughness.to_bits());
+        })
+    }
 
-        renderpass.draw_indexed(0..(INDICES.len() as _), 0, 0..1);
+    /// Joint matrices of a skinned object, bound to `uniform`'s buffer.
+    fn create_skin(
+        device: &wgpu::Device,
+        skin_layout: &wgpu::BindGroupLayout,
+        uniform: &UniformBinding,
+        skeleton: usize,
+        pose: Vec<Transform>,
+    ) -> GpuSkin {
+        let joint_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Joint Buffer"),
+            size: (pose.len() * std::mem::size_of::<nalgebra_glm::Mat4>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: skin_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform.buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: joint_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("skin_bind_group"),
+        });
+        GpuSkin {
+            skeleton,
+            previous_pose: pose.clone(),
+            pose,
+            joint_buffer,
+            bind_group,
+        }
     }
 
-    pub fn update(&mut self, queue: &wgpu::Queue, aspect_ratio: f32, delta_time: f32) {
-        let projection =
-            nalgebra_glm::perspective_lh_zo(aspect_ratio, 80_f32.to_radians(), 0.1, 1000.0);
-        let view = nalgebra_glm::look_at_lh(
-            &nalgebra_glm::vec3(0.0, 0.0, 3.0),
-            &nalgebra_glm::vec3(0.0, 0.0, 0.0),
-            &nalgebra_glm::Vec3::y(),
-        );
-        self.model = nalgebra_glm::rotate(
-            &self.model,
-            30_f32.to_radians() * delta_time,
-            &nalgebra_glm::Vec3::y(),
-        );
-        self.uniform.update_buffer(
-            queue,
-            0,
-            UniformBuffer {
-                mvp: projection * view * self.model,
+    fn create_skin_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("skin_bind_group_layout"),
+        })
+    }
+
+    /// Pipelines for static and skinned meshes drawn in `view`, which is
+    /// shaded for [`DebugView::Depth`].
+    #[allow(clippy::too_many_arguments)]
+    fn create_pipelines(
+        device: &wgpu::Device,
+        shaders: &mut AssetStore<wgpu::ShaderModule>,
+        target: TargetFormat,
+        view: DebugView,
+        pass: ObjectPass,
+        uniform_layout: &wgpu::BindGroupLayout,
+        skin_layout: Option<&wgpu::BindGroupLayout>,
+        environment_layout: &wgpu::BindGroupLayout,
+    ) -> ScenePipelines {
+        let barycentric = view == DebugView::Wireframe
+            && !device
+                .features()
+                .contains(wgpu::Features::POLYGON_MODE_LINE);
+        let (source, skinned_source) = match (view, pass) {
+            (_, ObjectPass::Accumulate) => (
+                format!("{SHADER_SOURCE}{ACCUMULATE_SHADER_SOURCE}"),
+                format!("{SHADER_SOURCE}{SKINNING_SHADER_SOURCE}{ACCUMULATE_SHADER_SOURCE}"),
+            ),
+            (_, ObjectPass::Geometry) => (
+                format!("{SHADER_SOURCE}{GBUFFER_SHADER_SOURCE}"),
+                format!("{SHADER_SOURCE}{SKINNING_SHADER_SOURCE}{GBUFFER_SHADER_SOURCE}"),
+            ),
+            (DebugView::Shaded | DebugView::Depth, _) => (
+                SHADER_SOURCE.to_string(),
+                format!("{SHADER_SOURCE}{SKINNING_SHADER_SOURCE}"),
+            ),
+            _ => (
+                format!("{SHADER_SOURCE}{DEBUG_SHADER_SOURCE}"),
+                format!(
+                    "{SHADER_SOURCE}{SKINNING_SHADER_SOURCE}{DEBUG_SHADER_SOURCE}{DEBUG_SKINNING_SHADER_SOURCE}"
+                ),
+            ),
+        };
+        let mut modules = Vec::new();
+        let mut shader = |source: &str| {
+            let module = shaders.load(
+                AssetKey::content(|hasher| hasher.write(source.as_bytes())),
+                || {
+                    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                        label: None,
+                        source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(source)),
+                    })
+                },
+            );
+            modules.push(module);
+            module
+        };
+        let module = shader(&source);
+        let skinned_module = skin_layout.map(|_| shader(&skinned_source));
+        let pipeline = Self::create_pipeline(
+            device,
+            target,
+            &shaders[module],
+            if barycentric {
+                "vertex_barycentric"
+            } else {
+                "vertex_main"
             },
+            view,
+            pass,
+            // Camera, lights and object uniforms all share the same layout
+            &[
+                uniform_layout,
+                uniform_layout,
+                uniform_layout,
+                environment_layout,
+            ],
+            &[Vertex::description(&Vertex::vertex_attributes())],
         );
+        let skinned_pipeline = skin_layout
+            .zip(skinned_module)
+            .map(|(skin_layout, module)| {
+                Self::create_pipeline(
+                    device,
+                    target,
+                    &shaders[module],
+                    if barycentric {
+                        "vertex_skinned_barycentric"
+                    } else {
+                        "vertex_skinned"
+                    },
+                    view,
+                    pass,
+                    &[
+                        uniform_layout,
+                        uniform_layout,
+                        skin_layout,
+                        environment_layout,
+                    ],
+                    &[
+                        Vertex::description(&Vertex::vertex_attributes()),
+                        SkinVertex::description(&SkinVertex::vertex_attributes()),
+                    ],
+                )
+            });
+        ScenePipelines {
+            view,
+            pipeline,
+            skinned_pipeline,
+            shaders: modules,
+        }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn create_pipeline(
         device: &wgpu::Device,
-        surface_format: wgpu::TextureFormat,
-        uniform: &UniformBinding,
+        target: TargetFormat,
+        shader_module: &wgpu::ShaderModule,
+        vertex_entry_point: &str,
+        view: DebugView,
+        pass: ObjectPass,
+        bind_group_layouts: &[&wgpu::BindGroupLayout],
+        buffers: &[wgpu::VertexBufferLayout],
     ) -> wgpu::RenderPipeline {
-        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: None,
-            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(SHADER_SOURCE)),
-        });
+        // Debug views replace the fragment stage, overdraw counts every layer
+        // instead of keeping the nearest
+        let lines = view == DebugView::Wireframe
+            && device
+                .features()
+                .contains(wgpu::Features::POLYGON_MODE_LINE);
+        let fragment_entry_point = match (view, pass) {
+            (_, ObjectPass::Accumulate) => "fragment_accumulate",
+            (_, ObjectPass::Geometry) => "fragment_gbuffer",
+            (DebugView::Shaded | DebugView::Depth, _) => "fragment_main",
+            (DebugView::Wireframe, _) if lines => "fragment_wireframe",
+            (DebugView::Wireframe, _) => "fragment_barycentric",
+            (DebugView::Normals, _) => "fragment_normals",
+            (DebugView::Uvs, _) => "fragment_uvs",
+            (DebugView::Overdraw, _) => "fragment_overdraw",
+        };
+        let (depth_write_enabled, depth_compare, blend) = match (view, pass) {
+            (DebugView::Overdraw, _) => (
+                false,
+                wgpu::CompareFunction::Always,
+                Some(wgpu::BlendState {
+                    color: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::One,
+                        dst_factor: wgpu::BlendFactor::One,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                    alpha: wgpu::BlendComponent::REPLACE,
+                }),
+            ),
+            (_, ObjectPass::Opaque | ObjectPass::Geometry) => {
+                (true, wgpu::CompareFunction::Less, None)
+            }
+            // Translucent surfaces are tested against the opaque ones, but
+            // mustn't hide each other
+            _ => (
+                false,
+                wgpu::CompareFunction::Less,
+                Some(wgpu::BlendState::ALPHA_BLENDING),
+            ),
+        };
+        let targets = match pass {
+            ObjectPass::Accumulate => WeightedBlendedRenderer::color_targets().to_vec(),
+            ObjectPass::Geometry => DeferredRenderer::color_targets().to_vec(),
+            _ => vec![Some(wgpu::ColorTargetState {
+                format: target.color,
+                blend,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        };
 
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: None,
-            bind_group_layouts: &[&uniform.bind_group_layout],
+            bind_group_layouts,
             push_constant_ranges: &[],
         });
 
@@ -499,40 +3162,40 @@ impl Scene {
             label: None,
             layout: Some(&pipeline_layout),
             vertex: wgpu::VertexState {
-                module: &shader_module,
-                entry_point: "vertex_main",
-                buffers: &[Vertex::description(&Vertex::vertex_attributes())],
+                module: shader_module,
+                entry_point: vertex_entry_point,
+                buffers,
                 compilation_options: Default::default(),
             },
             primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleStrip,
-                strip_index_format: Some(wgpu::IndexFormat::Uint32),
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
                 front_face: wgpu::FrontFace::Cw,
                 cull_mode: None,
-                polygon_mode: wgpu::PolygonMode::Fill,
+                polygon_mode: if lines {
+                    wgpu::PolygonMode::Line
+                } else {
+                    wgpu::PolygonMode::Fill
+                },
                 conservative: false,
                 unclipped_depth: false,
             },
             depth_stencil: Some(wgpu::DepthStencilState {
-                format: Renderer::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
+                format: target.depth,
+                depth_write_enabled,
+                depth_compare,
                 stencil: wgpu::StencilState::default(),
                 bias: wgpu::DepthBiasState::default(),
             }),
             multisample: wgpu::MultisampleState {
-                count: 1,
+                count: target.sample_count,
                 mask: !0,
                 alpha_to_coverage_enabled: false,
             },
             fragment: Some(wgpu::FragmentState {
-                module: &shader_module,
-                entry_point: "fragment_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: surface_format,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
+                module: shader_module,
+                entry_point: fragment_entry_point,
+                targets: &targets,
                 compilation_options: Default::default(),
             }),
             multiview: None,
@@ -542,53 +3205,102 @@ impl Scene {
 }
 
 #[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct Vertex {
-    position: [f32; 4],
-    color: [f32; 4],
+#[derive(Default, Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct UniformBuffer {
+    view_projection: nalgebra_glm::Mat4,
+    camera_position: nalgebra_glm::Vec4,
+    /// For reconstructing view rays from clip space
+    inverse_view_projection: nalgebra_glm::Mat4,
+    /// Distance to the near plane in x and to the far plane in y
+    depth_range: nalgebra_glm::Vec4,
 }
 
-impl Vertex {
-    pub fn vertex_attributes() -> Vec<wgpu::VertexAttribute> {
-        wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32x4].to_vec()
-    }
-
-    pub fn description(attributes: &[wgpu::VertexAttribute]) -> wgpu::VertexBufferLayout {
-        wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes,
+impl UniformBuffer {
+    fn new(camera: &Camera, aspect_ratio: f32) -> Self {
+        let view_projection = camera.view_projection(aspect_ratio);
+        Self {
+            view_projection,
+            camera_position: nalgebra_glm::vec3_to_vec4(&camera.eye),
+            inverse_view_projection: nalgebra_glm::inverse(&view_projection),
+            depth_range: nalgebra_glm::vec4(camera.z_near, camera.z_far, 0.0, 0.0),
         }
     }
 }
 
 #[repr(C)]
 #[derive(Default, Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-struct UniformBuffer {
-    mvp: nalgebra_glm::Mat4,
+struct ObjectUniform {
+    model: nalgebra_glm::Mat4,
+    /// Inverse transpose of `model`, for transforming normals
+    normal: nalgebra_glm::Mat4,
+    base_color: nalgebra_glm::Vec4,
+    emissive: nalgebra_glm::Vec4,
+    /// Metallic in x, roughness in y
+    material: nalgebra_glm::Vec4,
+}
+
+#[repr(C)]
+#[derive(Default, Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightUniform {
+    /// Direction the light shines in with w = 0, or position with w = 1
+    position: nalgebra_glm::Vec4,
+    /// Color premultiplied by intensity, range of point lights in w
+    color: nalgebra_glm::Vec4,
+}
+
+#[repr(C)]
+#[derive(Default, Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightsUniform {
+    ambient: nalgebra_glm::Vec4,
+    /// Number of lights in x, the other components are padding
+    count: [u32; 4],
+    /// Intensity of the environment in x, the last specular level in y,
+    /// and 1 in z if the scene has an environment
+    environment: nalgebra_glm::Vec4,
+    lights: [LightUniform; GpuScene::MAX_LIGHTS],
+}
+
+impl LightsUniform {
+    fn new(ambient: [f32; 3], lights: &[Light], environment_intensity: Option<f32>) -> Self {
+        let mut uniform = Self {
+            ambient: nalgebra_glm::vec4(ambient[0], ambient[1], ambient[2], 0.0),
+            count: [lights.len().min(GpuScene::MAX_LIGHTS) as u32, 0, 0, 0],
+            environment: nalgebra_glm::vec4(
+                environment_intensity.unwrap_or(0.0),
+                (EnvironmentBaker::SPECULAR_LEVELS - 1) as f32,
+                environment_intensity.map_or(0.0, |_| 1.0),
+                0.0,
+            ),
+            ..Default::default()
+        };
+        for (light, uniform) in lights.iter().zip(uniform.lights.iter_mut()) {
+            let [r, g, b] = light.color.map(|channel| channel * light.intensity);
+            *uniform = match light.kind {
+                LightKind::Directional { direction } => LightUniform {
+                    position: nalgebra_glm::vec3_to_vec4(&direction.normalize()),
+                    color: nalgebra_glm::vec4(r, g, b, 0.0),
+                },
+                LightKind::Point { position, range } => LightUniform {
+                    position: nalgebra_glm::vec4(position.x, position.y, position.z, 1.0),
+                    color: nalgebra_glm::vec4(r, g, b, range),
+                },
+            };
+        }
+        uniform
+    }
 }
 
 struct UniformBinding {
     pub buffer: wgpu::Buffer,
     pub bind_group: wgpu::BindGroup,
-    pub bind_group_layout: wgpu::BindGroupLayout,
 }
 
 impl UniformBinding {
-    pub fn new(device: &wgpu::Device) -> Self {
-        let buffer = wgpu::util::DeviceExt::create_buffer_init(
-            device,
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Uniform Buffer"),
-                contents: bytemuck::cast_slice(&[UniformBuffer::default()]),
-                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            },
-        );
-
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+    pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             entries: &[wgpu::BindGroupLayoutEntry {
                 binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
                 ty: wgpu::BindingType::Buffer {
                     ty: wgpu::BufferBindingType::Uniform,
                     has_dynamic_offset: false,
@@ -597,10 +3309,29 @@ impl UniformBinding {
                 count: None,
             }],
             label: Some("uniform_bind_group_layout"),
-        });
+        })
+    }
+
+    pub fn new(device: &wgpu::Device, bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+        Self::with_contents(device, bind_group_layout, UniformBuffer::default())
+    }
+
+    pub fn with_contents(
+        device: &wgpu::Device,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        contents: impl bytemuck::Pod,
+    ) -> Self {
+        let buffer = wgpu::util::DeviceExt::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Uniform Buffer"),
+                contents: bytemuck::cast_slice(&[contents]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            },
+        );
 
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &bind_group_layout,
+            layout: bind_group_layout,
             entries: &[wgpu::BindGroupEntry {
                 binding: 0,
                 resource: buffer.as_entire_binding(),
@@ -608,18 +3339,14 @@ impl UniformBinding {
             label: Some("uniform_bind_group"),
         });
 
-        Self {
-            buffer,
-            bind_group,
-            bind_group_layout,
-        }
+        Self { buffer, bind_group }
     }
 
     pub fn update_buffer(
         &mut self,
         queue: &wgpu::Queue,
         offset: wgpu::BufferAddress,
-        uniform_buffer: UniformBuffer,
+        uniform_buffer: impl bytemuck::Pod,
     ) {
         queue.write_buffer(
             &self.buffer,
@@ -629,50 +3356,209 @@ impl UniformBinding {
     }
 }
 
-const VERTICES: [Vertex; 3] = [
-    Vertex {
-        position: [1.0, -1.0, 0.0, 1.0],
-        color: [1.0, 0.0, 0.0, 1.0],
-    },
-    Vertex {
-        position: [-1.0, -1.0, 0.0, 1.0],
-        color: [0.0, 1.0, 0.0, 1.0],
-    },
-    Vertex {
-        position: [0.0, 1.0, 0.0, 1.0],
-        color: [0.0, 0.0, 1.0, 1.0],
-    },
-];
-
-const INDICES: [u32; 3] = [0, 1, 2]; // Clockwise winding order
-
 const SHADER_SOURCE: &str = "
-struct Uniform {
-    mvp: mat4x4<f32>,
+const MAX_LIGHTS: u32 = 8u;
+const PI: f32 = 3.14159265359;
+
+struct Camera {
+    view_projection: mat4x4<f32>,
+    position: vec4<f32>,
+    inverse_view_projection: mat4x4<f32>,
+};
+struct Light {
+    position: vec4<f32>,
+    color: vec4<f32>,
+};
+struct Lights {
+    ambient: vec4<f32>,
+    count: vec4<u32>,
+    environment: vec4<f32>,
+    lights: array<Light, MAX_LIGHTS>,
+};
+struct Object {
+    model: mat4x4<f32>,
+    normal: mat4x4<f32>,
+    base_color: vec4<f32>,
+    emissive: vec4<f32>,
+    material: vec4<f32>,
 };
 
 @group(0) @binding(0)
-var<uniform> ubo: Uniform;
+var<uniform> camera: Camera;
+@group(1) @binding(0)
+var<uniform> lights: Lights;
+@group(2) @binding(0)
+var<uniform> object: Object;
+@group(3) @binding(0)
+var irradiance_map: texture_cube<f32>;
+@group(3) @binding(1)
+var specular_map: texture_cube<f32>;
+@group(3) @binding(2)
+var brdf_lut: texture_2d<f32>;
+@group(3) @binding(3)
+var environment_sampler: sampler;
 
 struct VertexInput {
-    @location(0) position: vec4<f32>,
-    @location(1) color: vec4<f32>,
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) uv: vec2<f32>,
 };
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
-    @location(0) color: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) uv: vec2<f32>,
 };
 
 @vertex
 fn vertex_main(vert: VertexInput) -> VertexOutput {
+    let world_position = object.model * vec4<f32>(vert.position, 1.0);
     var out: VertexOutput;
-    out.color = vert.color;
-    out.position = ubo.mvp * vert.position;
+    out.position = camera.view_projection * world_position;
+    out.world_position = world_position.xyz;
+    out.normal = (object.normal * vec4<f32>(vert.normal, 0.0)).xyz;
+    out.color = vert.color * object.base_color;
+    out.uv = vert.uv;
     return out;
 };
 
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a2 = roughness * roughness * roughness * roughness;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+    let k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+    return n_dot_v / (n_dot_v * (1.0 - k) + k) * n_dot_l / (n_dot_l * (1.0 - k) + k);
+}
+
+fn fresnel_schlick(cos_theta: f32, f0: vec3<f32>, roughness: f32) -> vec3<f32> {
+    let f90 = max(vec3<f32>(1.0 - roughness), f0);
+    return f0 + (f90 - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// Light reflected towards the camera, scenes without lights or environment are unlit.
+// Only the ambient and environment light is scaled by the occlusion.
+fn shade(
+    world_position: vec3<f32>,
+    surface_normal: vec3<f32>,
+    albedo: vec3<f32>,
+    metallic: f32,
+    surface_roughness: f32,
+    occlusion: f32,
+) -> vec3<f32> {
+    let count = min(lights.count.x, MAX_LIGHTS);
+    let has_environment = lights.environment.z > 0.0;
+    if count == 0u && !has_environment {
+        return albedo;
+    }
+
+    // Light both sides of a surface, as seen from the camera
+    let view = normalize(camera.position.xyz - world_position);
+    var normal = normalize(surface_normal);
+    if dot(normal, view) < 0.0 {
+        normal = -normal;
+    }
+
+    let roughness = clamp(surface_roughness, 0.04, 1.0);
+    let n_dot_v = max(dot(normal, view), 0.0001);
+    let f0 = mix(vec3<f32>(0.04), albedo, metallic);
+
+    // Light colors are scaled so a white, matte surface facing a light
+    // reflects exactly its color
+    var color = vec3<f32>(0.0);
+    for (var index = 0u; index < count; index++) {
+        let light = lights.lights[index];
+        var direction = -light.position.xyz;
+        var attenuation = 1.0;
+        if light.position.w > 0.0 {
+            let to_light = light.position.xyz - world_position;
+            direction = normalize(to_light);
+            let falloff = clamp(1.0 - length(to_light) / light.color.w, 0.0, 1.0);
+            attenuation = falloff * falloff;
+        }
+        let n_dot_l = max(dot(normal, direction), 0.0);
+        let half_vector = normalize(view + direction);
+        let fresnel = fresnel_schlick(max(dot(half_vector, view), 0.0), f0, 0.0);
+        let specular = distribution_ggx(max(dot(normal, half_vector), 0.0), roughness)
+            * geometry_smith(n_dot_v, n_dot_l, roughness) * fresnel
+            / (4.0 * n_dot_v * n_dot_l + 0.0001);
+        let diffuse = (1.0 - fresnel) * (1.0 - metallic) * albedo;
+        color += (diffuse + PI * specular) * light.color.rgb * attenuation * n_dot_l;
+    }
+
+    if has_environment {
+        let fresnel = fresnel_schlick(n_dot_v, f0, roughness);
+        let irradiance = textureSampleLevel(irradiance_map, environment_sampler, normal, 0.0).rgb;
+        let prefiltered = textureSampleLevel(
+            specular_map,
+            environment_sampler,
+            reflect(-view, normal),
+            roughness * lights.environment.y,
+        ).rgb;
+        let brdf = textureSampleLevel(
+            brdf_lut,
+            environment_sampler,
+            vec2<f32>(n_dot_v, roughness),
+            0.0,
+        ).rg;
+        let diffuse = (1.0 - fresnel) * (1.0 - metallic) * irradiance * albedo;
+        let specular = prefiltered * (fresnel * brdf.x + brdf.y);
+        color += (diffuse + specular) * lights.environment.x * occlusion;
+    } else {
+        color += lights.ambient.rgb * albedo * occlusion;
+    }
+    return color;
+}
+
 @fragment
 fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return vec4<f32>(in.color);
+    let color = shade(
+        in.world_position,
+        in.normal,
+        in.color.rgb,
+        object.material.x,
+        object.material.y,
+        1.0,
+    ) + object.emissive.rgb;
+    return vec4<f32>(color, in.color.a);
+}
+";
+
+/// Vertex stage for skinned meshes, appended to [`SHADER_SOURCE`].
+const SKINNING_SHADER_SOURCE: &str = "
+struct SkinnedVertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) uv: vec2<f32>,
+    @location(4) joints: vec4<u32>,
+    @location(5) weights: vec4<f32>,
+};
+
+@group(2) @binding(1)
+var<storage, read> joint_matrices: array<mat4x4<f32>>;
+
+fn skin_matrix(joints: vec4<u32>, weights: vec4<f32>) -> mat4x4<f32> {
+    return joint_matrices[joints.x] * weights.x
+        + joint_matrices[joints.y] * weights.y
+        + joint_matrices[joints.z] * weights.z
+        + joint_matrices[joints.w] * weights.w;
+}
+
+@vertex
+fn vertex_skinned(vert: SkinnedVertexInput) -> VertexOutput {
+    let skin = skin_matrix(vert.joints, vert.weights);
+    let world_position = object.model * skin * vec4<f32>(vert.position, 1.0);
+    var out: VertexOutput;
+    out.position = camera.view_projection * world_position;
+    out.world_position = world_position.xyz;
+    out.normal = (object.normal * skin * vec4<f32>(vert.normal, 0.0)).xyz;
+    out.color = vert.color * object.base_color;
+    out.uv = vert.uv;
+    return out;
 }
 ";
@@ -0,0 +1,197 @@
+use std::path::PathBuf;
+#[cfg(target_arch = "wasm32")]
+use std::{collections::HashMap, path::Path};
+
+use winit::event_loop::EventLoopProxy;
+
+use crate::{
+    skybox::{read_file, ReadFile},
+    AppEvent, Lod, LodSource, Mesh, Scene, SceneError, SceneImages,
+};
+
+/// A validated scene with its images decoded and its meshes built, all the
+/// work short of the GPU upload, so it can be done away from the event loop.
+pub struct LoadedScene {
+    pub(crate) scene: Scene,
+    pub(crate) images: SceneImages,
+    /// The scene's meshes with their levels of detail built, followed by the
+    /// terrain chunks
+    pub(crate) meshes: Vec<Mesh>,
+}
+
+impl Default for LoadedScene {
+    fn default() -> Self {
+        Self::new(Scene::default()).expect("The default scene is invalid!")
+    }
+}
+
+impl LoadedScene {
+    /// Validates `scene` and loads what it references, relative paths
+    /// against its directory.
+    pub fn new(scene: Scene) -> Result<Self, SceneError> {
+        Self::with_files(scene, &read_file)
+    }
+
+    /// Reads the RON or glTF scene at `path` and loads what it references.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self, SceneError> {
+        Scene::load(path).and_then(Self::new)
+    }
+
+    /// Fetches the RON scene at `url` and everything it references, relative
+    /// paths against the scene's URL. glTF files are rejected, importing them
+    /// needs a file system.
+    #[cfg(target_arch = "wasm32")]
+    pub async fn fetch(url: &Path) -> Result<Self, SceneError> {
+        let extension = url.extension().and_then(|extension| extension.to_str());
+        if matches!(extension, Some("gltf" | "glb")) {
+            return Err(SceneError::Invalid(format!(
+                "{} is a glTF file, only RON scenes can be loaded on the web",
+                url.display()
+            )));
+        }
+        let source = String::from_utf8(fetch(url).await?).map_err(|error| {
+            SceneError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, error))
+        })?;
+        let mut scene = Scene::from_ron(&source)?;
+        scene.directory = url.parent().map(PathBuf::from);
+
+        let mut files = HashMap::new();
+        for path in referenced_files(&scene) {
+            let bytes = fetch(&path).await?;
+            files.insert(path, bytes);
+        }
+        Self::with_files(scene, &|path: &Path| {
+            files
+                .get(path)
+                .cloned()
+                .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotFound))
+        })
+    }
+
+    fn with_files(scene: Scene, read: ReadFile) -> Result<Self, SceneError> {
+        scene.validate()?;
+        let images = SceneImages::load(&scene, read)?;
+        let terrain = match &scene.terrain {
+            Some(terrain) => terrain.chunks(&terrain.load(scene.directory.as_deref(), read)?),
+            None => Vec::new(),
+        };
+        let meshes = scene.meshes.iter().map(build_lods).chain(terrain).collect();
+        Ok(Self {
+            scene,
+            images,
+            meshes,
+        })
+    }
+
+    pub fn scene(&self) -> &Scene {
+        &self.scene
+    }
+}
+
+/// `mesh` with its simplified levels of detail generated ahead of the upload.
+fn build_lods(mesh: &Mesh) -> Mesh {
+    Mesh {
+        lods: mesh
+            .lods
+            .iter()
+            .zip(mesh.lod_meshes())
+            .map(|(lod, lod_mesh)| Lod {
+                screen_size: lod.screen_size,
+                source: LodSource::Authored {
+                    vertices: lod_mesh.vertices,
+                    indices: lod_mesh.indices,
+                    skin: lod_mesh.skin,
+                },
+            })
+            .collect(),
+        ..mesh.clone()
+    }
+}
+
+/// Loads the scene at `path` in the background, on a worker thread natively
+/// and fetched on the web, where `path` is a URL relative to the page. The
+/// result is sent to the event loop as [`AppEvent::SceneLoaded`], tagged
+/// with `request`.
+pub(crate) fn spawn_load(path: PathBuf, request: u64, proxy: EventLoopProxy<AppEvent>) {
+    let send = move |path, result| {
+        if proxy
+            .send_event(AppEvent::SceneLoaded {
+                path,
+                request,
+                result,
+            })
+            .is_err()
+        {
+            tracing::warn!("The event loop closed before the scene was loaded");
+        }
+    };
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let spawned = std::thread::Builder::new()
+            .name("scene loader".to_string())
+            .spawn(move || {
+                let result = LoadedScene::load(&path);
+                send(path, result);
+            });
+        if let Err(error) = spawned {
+            tracing::error!("Failed to start loading the scene: {error}");
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_futures::spawn_local(async move {
+        let result = LoadedScene::fetch(&path).await;
+        send(path, result);
+    });
+}
+
+/// Every file `scene` references, resolved against its directory.
+#[cfg(target_arch = "wasm32")]
+fn referenced_files(scene: &Scene) -> Vec<PathBuf> {
+    use crate::skybox::resolve_path;
+
+    let directory = scene.directory.as_deref();
+    let mut paths = Vec::new();
+    if let Some(skybox) = &scene.skybox {
+        paths.extend(skybox.paths(directory));
+    }
+    if let Some(environment) = &scene.environment {
+        paths.push(resolve_path(&environment.path, directory));
+    }
+    if let Some(terrain) = &scene.terrain {
+        paths.push(resolve_path(&terrain.heightmap, directory));
+    }
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+/// The body of the response to a request for `path`, relative to the page.
+#[cfg(target_arch = "wasm32")]
+async fn fetch(path: &Path) -> Result<Vec<u8>, SceneError> {
+    use wasm_bindgen::JsCast;
+    use wasm_bindgen_futures::JsFuture;
+
+    let error = |error: wasm_bindgen::JsValue| {
+        SceneError::Io(std::io::Error::other(format!(
+            "failed to fetch {}: {error:?}",
+            path.display()
+        )))
+    };
+    let window = web_sys::window().ok_or_else(|| error("no window".into()))?;
+    let response: web_sys::Response =
+        JsFuture::from(window.fetch_with_str(&path.to_string_lossy()))
+            .await
+            .map_err(error)?
+            .dyn_into()
+            .map_err(error)?;
+    if !response.ok() {
+        return Err(error(format!("status {}", response.status()).into()));
+    }
+    let buffer = JsFuture::from(response.array_buffer().map_err(error)?)
+        .await
+        .map_err(error)?;
+    Ok(web_sys::js_sys::Uint8Array::new(&buffer).to_vec())
+}
@@ -7,24 +7,86 @@ use winit::event_loop::EventLoop;
 #[path = "util/tracing.rs"]
 mod tracing;
 
+#[cfg(not(web_platform))]
+#[path = "util/cli.rs"]
+mod cli;
+
 fn main() -> Result<(), Box<dyn Error>> {
     #[cfg(web_platform)]
-    console_error_panic_hook::set_once();
-    tracing::init();
+    {
+        console_error_panic_hook::set_once();
+        tracing::init(tracing_subscriber::filter::LevelFilter::INFO);
+    }
+
+    // The page's `scene` query parameter names the scene to fetch
+    #[cfg(web_platform)]
+    let (config, config_store, scene_path) = {
+        let config_store = main_core::ConfigStore::platform_default();
+        let config = config_store.load().unwrap_or_else(|error| {
+            ::tracing::error!("Failed to load config, using defaults: {error}");
+            Default::default()
+        });
+        let scene_path = web_sys::window()
+            .and_then(|window| window.location().search().ok())
+            .and_then(|search| web_sys::UrlSearchParams::new_with_str(&search).ok())
+            .and_then(|parameters| parameters.get("scene"))
+            .map(std::path::PathBuf::from);
+        (config, config_store, scene_path)
+    };
+
+    #[cfg(not(web_platform))]
+    let args = <cli::Args as clap::Parser>::parse();
 
-    let event_loop = EventLoop::with_user_event().build()?;
+    #[cfg(not(web_platform))]
+    let (config, config_store, scene_path) = {
+        tracing::init(args.log_level);
+
+        let config_store = args.config_store();
+        let saved_config = config_store.load().unwrap_or_else(|error| {
+            ::tracing::error!("Failed to load config, using defaults: {error}");
+            Default::default()
+        });
+        let mut config = saved_config.clone();
+        args.apply(&mut config);
+
+        // Recordings wait for the scene, windows show the default one until it's loaded
+        if let Some(recording) = args.recording() {
+            let scene = match args.scene.as_ref() {
+                Some(path) => main_core::Scene::load(path)
+                    .map_err(|error| format!("{}: {error}", path.display()))?,
+                None => main_core::Scene::default(),
+            };
+            return main_core::render_frames(
+                &config,
+                &scene,
+                config.window.size.unwrap_or(cli::Args::DEFAULT_SIZE),
+                &recording,
+            );
+        }
+        (saved_config, config_store, args.scene.clone())
+    };
+
+    let event_loop = EventLoop::<main_core::AppEvent>::with_user_event().build()?;
+    let mut state =
+        main_core::App::new(config, config_store).with_event_proxy(event_loop.create_proxy());
+    // Command line options apply to this run without being saved to the config
+    #[cfg(not(web_platform))]
+    {
+        state = state.with_overrides(move |config| args.apply(config));
+    }
+    if let Some(path) = scene_path {
+        state.load_scene(path);
+    }
 
     #[cfg(web_platform)]
     {
         use winit::platform::web::EventLoopExtWebSys;
-        let state = main_core::App::default();
         event_loop.spawn_app(state);
         Ok(())
     }
 
     #[cfg(not(web_platform))]
     {
-        let mut state = main_core::App::default();
         event_loop.run_app(&mut state).map_err(Into::into)
     }
-}
\ No newline at end of file
+}
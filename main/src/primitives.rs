@@ -0,0 +1,415 @@
+use std::{collections::HashMap, f32::consts::PI};
+
+use nalgebra_glm::Vec3;
+
+use crate::{Mesh, Vertex};
+
+/// Generated shapes, centered on the origin with +y up. Vertices are white,
+/// so objects show their material's base color, and texture coordinates run
+/// from 0 to 1 with v growing downwards.
+impl Mesh {
+    /// A cube with edges of `size`, each face split into `subdivisions` squared quads.
+    pub fn cube(size: f32, subdivisions: u32) -> Self {
+        let subdivisions = subdivisions.max(1);
+        let mut builder = MeshBuilder::default();
+        // Normal, then the directions u and v grow in on that face
+        let faces = [
+            (Vec3::x(), -Vec3::z(), -Vec3::y()),
+            (-Vec3::x(), Vec3::z(), -Vec3::y()),
+            (Vec3::y(), Vec3::x(), Vec3::z()),
+            (-Vec3::y(), Vec3::x(), -Vec3::z()),
+            (Vec3::z(), Vec3::x(), -Vec3::y()),
+            (-Vec3::z(), -Vec3::x(), -Vec3::y()),
+        ];
+        for (normal, u_axis, v_axis) in faces {
+            builder.grid(subdivisions, subdivisions, |u, v| {
+                let position =
+                    (normal + (u * 2.0 - 1.0) * u_axis + (v * 2.0 - 1.0) * v_axis) * size * 0.5;
+                (position, normal)
+            });
+        }
+        builder.finish("cube")
+    }
+
+    /// A flat grid of `width` along x by `depth` along z facing +y, with
+    /// `subdivisions` quads along each side.
+    pub fn plane(width: f32, depth: f32, subdivisions: u32) -> Self {
+        let subdivisions = subdivisions.max(1);
+        let mut builder = MeshBuilder::default();
+        builder.grid(subdivisions, subdivisions, |u, v| {
+            (
+                Vec3::new((u - 0.5) * width, 0.0, (v - 0.5) * depth),
+                Vec3::y(),
+            )
+        });
+        builder.finish("plane")
+    }
+
+    /// A sphere of `segments` around the y axis and `rings` from pole to pole.
+    pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> Self {
+        let mut builder = MeshBuilder::default();
+        builder.grid(segments.max(3), rings.max(2), |u, v| {
+            let normal = spherical(u * 2.0 * PI, v * PI);
+            (normal * radius, normal)
+        });
+        builder.finish("uv_sphere")
+    }
+
+    /// A sphere from an icosahedron whose triangles are split in four
+    /// `subdivisions` times, evenly spread unlike [`Mesh::uv_sphere`].
+    pub fn icosphere(radius: f32, subdivisions: u32) -> Self {
+        let t = (1.0 + 5.0f32.sqrt()) / 2.0;
+        let mut positions: Vec<Vec3> = [
+            [-1.0, t, 0.0],
+            [1.0, t, 0.0],
+            [-1.0, -t, 0.0],
+            [1.0, -t, 0.0],
+            [0.0, -1.0, t],
+            [0.0, 1.0, t],
+            [0.0, -1.0, -t],
+            [0.0, 1.0, -t],
+            [t, 0.0, -1.0],
+            [t, 0.0, 1.0],
+            [-t, 0.0, -1.0],
+            [-t, 0.0, 1.0],
+        ]
+        .iter()
+        .map(|position| Vec3::from(*position).normalize())
+        .collect();
+        let mut triangles: Vec<[u32; 3]> = vec![
+            [0, 11, 5],
+            [0, 5, 1],
+            [0, 1, 7],
+            [0, 7, 10],
+            [0, 10, 11],
+            [1, 5, 9],
+            [5, 11, 4],
+            [11, 10, 2],
+            [10, 7, 6],
+            [7, 1, 8],
+            [3, 9, 4],
+            [3, 4, 2],
+            [3, 2, 6],
+            [3, 6, 8],
+            [3, 8, 9],
+            [4, 9, 5],
+            [2, 4, 11],
+            [6, 2, 10],
+            [8, 6, 7],
+            [9, 8, 1],
+        ];
+
+        for _ in 0..subdivisions {
+            // Edges are shared by two triangles, which have to share the midpoint too
+            let mut midpoints = HashMap::new();
+            let mut midpoint = |a: u32, b: u32| {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    let position = (positions[a as usize] + positions[b as usize]).normalize();
+                    positions.push(position);
+                    positions.len() as u32 - 1
+                })
+            };
+            triangles = triangles
+                .iter()
+                .flat_map(|&[a, b, c]| {
+                    let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                    [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+                })
+                .collect();
+        }
+
+        let mut builder = MeshBuilder::default();
+        for normal in &positions {
+            let u = 0.5 + normal.z.atan2(normal.x) / (2.0 * PI);
+            let v = normal.y.clamp(-1.0, 1.0).acos() / PI;
+            builder.vertex(normal * radius, *normal, [u, v]);
+        }
+        // Triangles crossing the seam where u wraps around get their own
+        // vertices on the far side, so the texture doesn't run backwards
+        let mut wrapped = HashMap::new();
+        for mut triangle in triangles {
+            let uvs = triangle.map(|index| builder.vertices[index as usize].uv[0]);
+            if uvs.iter().copied().fold(f32::MIN, f32::max)
+                - uvs.iter().copied().fold(f32::MAX, f32::min)
+                > 0.5
+            {
+                for (index, u) in triangle.iter_mut().zip(uvs) {
+                    if u < 0.5 {
+                        *index = *wrapped.entry(*index).or_insert_with(|| {
+                            let mut vertex = builder.vertices[*index as usize];
+                            vertex.uv[0] += 1.0;
+                            builder.vertices.push(vertex);
+                            builder.vertices.len() as u32 - 1
+                        });
+                    }
+                }
+            }
+            builder.triangle(triangle);
+        }
+        builder.finish("icosphere")
+    }
+
+    /// A closed cylinder along y, with `segments` around its side.
+    pub fn cylinder(radius: f32, height: f32, segments: u32) -> Self {
+        let segments = segments.max(3);
+        let mut builder = MeshBuilder::default();
+        builder.grid(segments, 1, |u, v| {
+            let normal = spherical(u * 2.0 * PI, PI / 2.0);
+            (normal * radius + Vec3::y() * (0.5 - v) * height, normal)
+        });
+        builder.disk(radius, height * 0.5, Vec3::y(), segments);
+        builder.disk(radius, -height * 0.5, -Vec3::y(), segments);
+        builder.finish("cylinder")
+    }
+
+    /// A cone along y with its tip at the top, with `segments` around its side.
+    pub fn cone(radius: f32, height: f32, segments: u32) -> Self {
+        let segments = segments.max(3);
+        let mut builder = MeshBuilder::default();
+        // The side leans in by the ratio of the radius to the height
+        let slope = radius / height.max(f32::EPSILON);
+        builder.grid(segments, 1, |u, v| {
+            let outwards = spherical(u * 2.0 * PI, PI / 2.0);
+            (
+                outwards * radius * v + Vec3::y() * (0.5 - v) * height,
+                (outwards + Vec3::y() * slope).normalize(),
+            )
+        });
+        builder.disk(radius, -height * 0.5, -Vec3::y(), segments);
+        builder.finish("cone")
+    }
+
+    /// A ring of `major_radius` around the y axis, with a tube of
+    /// `minor_radius` split into `segments` along the ring and `sides` around it.
+    pub fn torus(major_radius: f32, minor_radius: f32, segments: u32, sides: u32) -> Self {
+        let mut builder = MeshBuilder::default();
+        builder.grid(segments.max(3), sides.max(3), |u, v| {
+            let center = spherical(u * 2.0 * PI, PI / 2.0) * major_radius;
+            let angle = v * 2.0 * PI;
+            let normal = center.normalize() * angle.cos() + Vec3::y() * angle.sin();
+            (center + normal * minor_radius, normal)
+        });
+        builder.finish("torus")
+    }
+
+    /// A cylinder of `height` along y capped by hemispheres, `segments` around
+    /// and `rings` from each pole to the cylinder.
+    pub fn capsule(radius: f32, height: f32, segments: u32, rings: u32) -> Self {
+        let rings = rings.max(1);
+        let mut builder = MeshBuilder::default();
+        // The rows are the top hemisphere's rings, then the bottom one's, so
+        // the row between them is the cylinder
+        let rows = rings * 2 + 1;
+        builder.grid(segments.max(3), rows, |u, v| {
+            let row = (v * rows as f32).round() as u32;
+            let (polar, offset) = if row <= rings {
+                (row as f32 / rings as f32, 0.5)
+            } else {
+                ((row - 1) as f32 / rings as f32, -0.5)
+            };
+            let normal = spherical(u * 2.0 * PI, polar * PI / 2.0);
+            (normal * radius + Vec3::y() * height * offset, normal)
+        });
+        builder.finish("capsule")
+    }
+
+    /// Sets the color of every vertex.
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        for vertex in &mut self.vertices {
+            vertex.color = color;
+        }
+        self
+    }
+}
+
+/// Unit vector at `azimuth` around the y axis from +x towards +z, and
+/// `polar` down from +y.
+fn spherical(azimuth: f32, polar: f32) -> Vec3 {
+    Vec3::new(
+        polar.sin() * azimuth.cos(),
+        polar.cos(),
+        polar.sin() * azimuth.sin(),
+    )
+}
+
+#[derive(Default)]
+struct MeshBuilder {
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+}
+
+impl MeshBuilder {
+    fn vertex(&mut self, position: Vec3, normal: Vec3, uv: [f32; 2]) -> u32 {
+        self.vertices.push(Vertex {
+            position: position.into(),
+            normal: normal.into(),
+            uv,
+            ..Default::default()
+        });
+        self.vertices.len() as u32 - 1
+    }
+
+    /// Adds a triangle, wound clockwise as seen from the side its normals
+    /// point to. Triangles without area, like the ones at a pole, are skipped.
+    fn triangle(&mut self, corners: [u32; 3]) {
+        let [a, b, c] = corners.map(|index| self.vertices[index as usize]);
+        let [pa, pb, pc] = [a, b, c].map(|vertex| Vec3::from(vertex.position));
+        let face = (pb - pa).cross(&(pc - pa));
+        if face.norm_squared() <= f32::EPSILON * f32::EPSILON {
+            return;
+        }
+        let normal = Vec3::from(a.normal) + Vec3::from(b.normal) + Vec3::from(c.normal);
+        let [a, b, c] = corners;
+        if face.dot(&normal) > 0.0 {
+            self.indices.extend([a, c, b]);
+        } else {
+            self.indices.extend([a, b, c]);
+        }
+    }
+
+    /// Adds `columns` by `rows` quads, with the position and normal at the
+    /// texture coordinates given by `point`.
+    fn grid(&mut self, columns: u32, rows: u32, point: impl Fn(f32, f32) -> (Vec3, Vec3)) {
+        let first = self.vertices.len() as u32;
+        for row in 0..=rows {
+            for column in 0..=columns {
+                let uv = [column as f32 / columns as f32, row as f32 / rows as f32];
+                let (position, normal) = point(uv[0], uv[1]);
+                self.vertex(position, normal, uv);
+            }
+        }
+        let index = |column, row| first + row * (columns + 1) + column;
+        for row in 0..rows {
+            for column in 0..columns {
+                let corners = [
+                    index(column, row),
+                    index(column + 1, row),
+                    index(column + 1, row + 1),
+                    index(column, row + 1),
+                ];
+                self.triangle([corners[0], corners[1], corners[2]]);
+                self.triangle([corners[0], corners[2], corners[3]]);
+            }
+        }
+    }
+
+    /// Adds a flat disk at `height` on the y axis, facing `normal`.
+    fn disk(&mut self, radius: f32, height: f32, normal: Vec3, segments: u32) {
+        let center = self.vertex(Vec3::y() * height, normal, [0.5, 0.5]);
+        for segment in 0..segments {
+            let direction = spherical(segment as f32 / segments as f32 * 2.0 * PI, PI / 2.0);
+            self.vertex(
+                direction * radius + Vec3::y() * height,
+                normal,
+                [0.5 + direction.x * 0.5, 0.5 + direction.z * 0.5],
+            );
+        }
+        for segment in 0..segments {
+            let next = (segment + 1) % segments;
+            self.triangle([center, center + 1 + segment, center + 1 + next]);
+        }
+    }
+
+    fn finish(self, name: &str) -> Mesh {
+        Mesh {
+            name: name.to_string(),
+            vertices: self.vertices,
+            indices: self.indices,
+            skin: Vec::new(),
+            lods: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every generator with a few different sizes and resolutions, and
+    /// whether its surface is closed.
+    fn shapes() -> Vec<(Mesh, bool)> {
+        vec![
+            (Mesh::cube(1.0, 1), true),
+            (Mesh::cube(2.0, 3), true),
+            (Mesh::plane(2.0, 1.0, 4), false),
+            (Mesh::uv_sphere(1.0, 16, 8), true),
+            (Mesh::icosphere(1.0, 0), true),
+            (Mesh::icosphere(2.0, 2), true),
+            (Mesh::cylinder(0.5, 2.0, 12), true),
+            (Mesh::cone(0.5, 1.0, 12), true),
+            (Mesh::torus(1.0, 0.25, 24, 8), true),
+            (Mesh::capsule(0.5, 1.0, 12, 4), true),
+        ]
+    }
+
+    /// A position rounded so copies of a vertex along a seam compare equal.
+    fn quantize(position: [f32; 3]) -> [i32; 3] {
+        position.map(|coordinate| (coordinate * 1e4).round() as i32)
+    }
+
+    #[test]
+    fn indices_are_in_bounds() {
+        for (mesh, _) in shapes() {
+            assert!(!mesh.indices.is_empty(), "{}", mesh.name);
+            assert_eq!(mesh.indices.len() % 3, 0, "{}", mesh.name);
+            assert!(
+                mesh.indices
+                    .iter()
+                    .all(|&index| (index as usize) < mesh.vertices.len()),
+                "{}",
+                mesh.name
+            );
+        }
+    }
+
+    #[test]
+    fn normals_have_unit_length() {
+        for (mesh, _) in shapes() {
+            for vertex in &mesh.vertices {
+                let length = Vec3::from(vertex.normal).norm();
+                assert!((length - 1.0).abs() < 1e-5, "{}: {length}", mesh.name);
+            }
+        }
+    }
+
+    #[test]
+    fn resolutions_below_the_minimum_are_raised() {
+        assert_eq!(Mesh::cube(1.0, 0), Mesh::cube(1.0, 1));
+        assert_eq!(Mesh::plane(1.0, 1.0, 0), Mesh::plane(1.0, 1.0, 1));
+        assert_eq!(Mesh::uv_sphere(1.0, 0, 0), Mesh::uv_sphere(1.0, 3, 2));
+        assert_eq!(Mesh::cylinder(1.0, 1.0, 1), Mesh::cylinder(1.0, 1.0, 3));
+        assert_eq!(Mesh::cone(1.0, 1.0, 2), Mesh::cone(1.0, 1.0, 3));
+        assert_eq!(Mesh::torus(1.0, 0.5, 0, 0), Mesh::torus(1.0, 0.5, 3, 3));
+        assert_eq!(Mesh::capsule(1.0, 1.0, 0, 0), Mesh::capsule(1.0, 1.0, 3, 1));
+        // An icosahedron isn't subdivided at all
+        assert_eq!(Mesh::icosphere(1.0, 0).indices.len(), 20 * 3);
+    }
+
+    #[test]
+    fn closed_shapes_have_no_seams() {
+        for (mesh, closed) in shapes() {
+            if !closed {
+                continue;
+            }
+            // Each edge between two positions is walked once in either
+            // direction, by the two triangles sharing it
+            let mut edges = HashMap::new();
+            for triangle in mesh.indices.chunks(3) {
+                let corners = [0, 1, 2]
+                    .map(|corner| quantize(mesh.vertices[triangle[corner] as usize].position));
+                for (a, b) in [(0, 1), (1, 2), (2, 0)] {
+                    *edges.entry((corners[a], corners[b])).or_insert(0) += 1;
+                }
+            }
+            for (&(a, b), &count) in &edges {
+                assert_eq!(count, 1, "{} walks an edge twice", mesh.name);
+                assert_eq!(
+                    edges.get(&(b, a)),
+                    Some(&1),
+                    "{} has an open edge from {a:?} to {b:?}",
+                    mesh.name
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,829 @@
+use std::{collections::HashSet, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AnimationClip, AnimationPlayer, Bounds, Camera, Environment, Skeleton, Skybox, Terrain,
+    Transform,
+};
+
+/// A declarative description of everything that is rendered, stored as RON.
+///
+/// Meshes and materials are referenced by name from the objects, so they can
+/// be shared. The camera and clear color override the ones from the app config,
+/// a skybox replaces the clear color.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+    /// Format version the file was written for, see [`Scene::VERSION`]
+    pub version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clear_color: Option<[f64; 4]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub camera: Option<Camera>,
+    /// Light reaching every surface when the scene has lights but no environment
+    #[serde(default = "Scene::default_ambient_light")]
+    pub ambient_light: [f32; 3],
+    #[serde(default)]
+    pub meshes: Vec<Mesh>,
+    #[serde(default)]
+    pub materials: Vec<Material>,
+    #[serde(default)]
+    pub lights: Vec<Light>,
+    #[serde(default)]
+    pub objects: Vec<Object>,
+    #[serde(default)]
+    pub animations: Vec<AnimationClip>,
+    #[serde(default)]
+    pub skeletons: Vec<Skeleton>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skybox: Option<Skybox>,
+    /// Image-based lighting, also drawn as the background if there is no skybox
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<Environment>,
+    /// Heightmap drawn in chunks next to the objects
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub terrain: Option<Terrain>,
+    /// Directory relative asset paths are resolved against, set when loading from a file
+    #[serde(skip)]
+    pub directory: Option<PathBuf>,
+}
+
+/// Indexed triangle list.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Mesh {
+    pub name: String,
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    /// Joint influences of every vertex, empty unless the mesh is skinned
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skin: Vec<SkinVertex>,
+    /// Coarser versions drawn as the mesh gets smaller on screen, most detailed first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lods: Vec<Lod>,
+}
+
+/// A level of detail, replacing its mesh below a size on screen.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Lod {
+    /// Diameter of the mesh's bounding sphere as a fraction of the viewport
+    /// height, below which this level is drawn
+    pub screen_size: f32,
+    pub source: LodSource,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum LodSource {
+    /// Generated by simplifying the mesh down to this fraction of its triangles
+    Simplified(f32),
+    /// Modeled separately, with the skin too if the mesh is skinned
+    Authored {
+        vertices: Vec<Vertex>,
+        indices: Vec<u32>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        skin: Vec<SkinVertex>,
+    },
+}
+
+#[repr(C)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Serialize, Deserialize, bytemuck::Pod, bytemuck::Zeroable,
+)]
+#[serde(default)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    /// Linear RGBA color, multiplied with the material's base color
+    pub color: [f32; 4],
+    /// Texture coordinates, only shown by the UV debug view so far
+    pub uv: [f32; 2],
+}
+
+/// Up to four joints moving a vertex of a skinned mesh.
+#[repr(C)]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    bytemuck::Pod,
+    bytemuck::Zeroable,
+)]
+pub struct SkinVertex {
+    /// Indices into the joints of the object's skeleton
+    pub joints: [u32; 4],
+    /// Influence of each joint, adding up to 1
+    pub weights: [f32; 4],
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Material {
+    pub name: String,
+    /// Linear RGBA color
+    pub base_color: [f32; 4],
+    /// Linear RGB color added after lighting
+    pub emissive: [f32; 3],
+    /// 0 for dielectrics, 1 for metals
+    pub metallic: f32,
+    /// Microfacet roughness from 0, a perfect mirror, to 1
+    pub roughness: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Light {
+    pub kind: LightKind,
+    /// Linear RGB color
+    #[serde(default = "Light::default_color")]
+    pub color: [f32; 3],
+    #[serde(default = "Light::default_intensity")]
+    pub intensity: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum LightKind {
+    /// Infinitely far away light shining along `direction`
+    Directional { direction: nalgebra_glm::Vec3 },
+    /// Light radiating from `position`, fading out completely at `range`
+    Point {
+        position: nalgebra_glm::Vec3,
+        range: f32,
+    },
+}
+
+/// An instance of a mesh placed in the scene.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Object {
+    pub name: String,
+    /// Name of the mesh to draw
+    pub mesh: String,
+    /// Name of the material to draw the mesh with, white if not given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub material: Option<String>,
+    pub transform: Transform,
+    /// Clip animating the transform
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub animation: Option<AnimationPlayer>,
+    /// Name of the skeleton deforming the mesh, required for skinned meshes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skeleton: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+    /// An image referenced by the scene couldn't be loaded
+    Image(PathBuf, image::ImageError),
+    /// The file doesn't declare the format version it was written for
+    MissingVersion,
+    /// The file was written for a newer version of the format
+    UnsupportedVersion(u32),
+    /// The file is well-formed but inconsistent, e.g. references a missing mesh
+    Invalid(String),
+    /// A glTF file couldn't be read
+    #[cfg(not(target_arch = "wasm32"))]
+    Gltf(gltf::Error),
+}
+
+impl std::fmt::Display for SceneError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "failed to access scene file: {error}"),
+            Self::Parse(error) => write!(f, "invalid scene at {error}"),
+            Self::Serialize(error) => write!(f, "failed to serialize scene: {error}"),
+            Self::Image(path, error) => {
+                write!(f, "failed to load image {}: {error}", path.display())
+            }
+            Self::MissingVersion => write!(
+                f,
+                "scene has no version, add `version: {}` to the top level",
+                Scene::VERSION
+            ),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "scene version {version} is not supported, the latest version is {}",
+                Scene::VERSION
+            ),
+            Self::Invalid(error) => write!(f, "invalid scene: {error}"),
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::Gltf(error) => write!(f, "failed to import glTF file: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl Scene {
+    /// Version of the format written by [`Scene::to_ron`].
+    pub const VERSION: u32 = 1;
+
+    pub fn from_ron(source: &str) -> Result<Self, SceneError> {
+        // Check the version first, so files from newer versions fail with a
+        // useful message instead of an error about some unknown field
+        #[derive(Deserialize)]
+        #[serde(rename = "Scene")]
+        struct Header {
+            #[serde(default)]
+            version: Option<u32>,
+        }
+        let header: Header = Self::ron_options()
+            .from_str(source)
+            .map_err(SceneError::Parse)?;
+        match header.version {
+            None => return Err(SceneError::MissingVersion),
+            Some(version) if version > Self::VERSION => {
+                return Err(SceneError::UnsupportedVersion(version))
+            }
+            Some(_) => {}
+        }
+
+        let scene: Self = Self::ron_options()
+            .from_str(source)
+            .map_err(SceneError::Parse)?;
+        scene.validate()?;
+        Ok(scene)
+    }
+
+    pub fn to_ron(&self) -> Result<String, SceneError> {
+        let config = ron::ser::PrettyConfig::new()
+            .struct_names(false)
+            .compact_arrays(true);
+        Self::ron_options()
+            .to_string_pretty(self, config)
+            .map_err(SceneError::Serialize)
+    }
+
+    /// Optional values like the camera can be written without `Some(...)`.
+    fn ron_options() -> ron::Options {
+        ron::Options::default().with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+    }
+
+    /// Checks that every reference in the scene resolves and all meshes are well-formed.
+    pub fn validate(&self) -> Result<(), SceneError> {
+        let mut mesh_names = HashSet::new();
+        for mesh in &self.meshes {
+            if !mesh_names.insert(mesh.name.as_str()) {
+                return Err(SceneError::Invalid(format!(
+                    "mesh '{}' is defined more than once",
+                    mesh.name
+                )));
+            }
+            mesh.validate()
+                .map_err(|error| SceneError::Invalid(format!("mesh '{}' {error}", mesh.name)))?;
+        }
+
+        let mut material_names = HashSet::new();
+        for material in &self.materials {
+            if !material_names.insert(material.name.as_str()) {
+                return Err(SceneError::Invalid(format!(
+                    "material '{}' is defined more than once",
+                    material.name
+                )));
+            }
+            if !(0.0..=1.0).contains(&material.metallic)
+                || !(0.0..=1.0).contains(&material.roughness)
+            {
+                return Err(SceneError::Invalid(format!(
+                    "material '{}' has metallic and roughness outside of 0 to 1",
+                    material.name
+                )));
+            }
+        }
+
+        let mut animation_names = HashSet::new();
+        for animation in &self.animations {
+            if !animation_names.insert(animation.name.as_str()) {
+                return Err(SceneError::Invalid(format!(
+                    "animation '{}' is defined more than once",
+                    animation.name
+                )));
+            }
+            animation.validate().map_err(|error| {
+                SceneError::Invalid(format!("animation '{}': {error}", animation.name))
+            })?;
+        }
+
+        let mut skeleton_names = HashSet::new();
+        for skeleton in &self.skeletons {
+            if !skeleton_names.insert(skeleton.name.as_str()) {
+                return Err(SceneError::Invalid(format!(
+                    "skeleton '{}' is defined more than once",
+                    skeleton.name
+                )));
+            }
+            skeleton.validate().map_err(|error| {
+                SceneError::Invalid(format!("skeleton '{}' {error}", skeleton.name))
+            })?;
+        }
+
+        for object in &self.objects {
+            let Some(mesh) = self.mesh(&object.mesh) else {
+                return Err(SceneError::Invalid(format!(
+                    "object '{}' uses unknown mesh '{}'",
+                    object.name, object.mesh
+                )));
+            };
+            let skeleton = match object.skeleton.as_deref() {
+                Some(name) => Some(self.skeleton(name).ok_or_else(|| {
+                    SceneError::Invalid(format!(
+                        "object '{}' uses unknown skeleton '{name}'",
+                        object.name
+                    ))
+                })?),
+                None => None,
+            };
+            match skeleton {
+                None if !mesh.skin.is_empty() => {
+                    return Err(SceneError::Invalid(format!(
+                        "object '{}' has a skinned mesh but no skeleton",
+                        object.name
+                    )))
+                }
+                Some(skeleton) => {
+                    let joint_count = skeleton.joints.len() as u32;
+                    if mesh
+                        .skin
+                        .iter()
+                        .any(|vertex| vertex.joints.iter().any(|joint| *joint >= joint_count))
+                    {
+                        return Err(SceneError::Invalid(format!(
+                            "mesh '{}' uses joints beyond the {joint_count} of skeleton '{}'",
+                            mesh.name, skeleton.name
+                        )));
+                    }
+                }
+                None => {}
+            }
+            if let Some(material) = object.material.as_deref() {
+                if !material_names.contains(material) {
+                    return Err(SceneError::Invalid(format!(
+                        "object '{}' uses unknown material '{material}'",
+                        object.name
+                    )));
+                }
+            }
+            if let Some(player) = &object.animation {
+                let Some(clip) = self.animation(&player.clip) else {
+                    return Err(SceneError::Invalid(format!(
+                        "object '{}' plays unknown animation '{}'",
+                        object.name, player.clip
+                    )));
+                };
+                if let Some(animation) = clip.joints.iter().find(|animation| {
+                    skeleton.is_none_or(|skeleton| skeleton.joint(&animation.joint).is_none())
+                }) {
+                    return Err(SceneError::Invalid(format!(
+                        "object '{}' plays animation '{}' of joint '{}', which its skeleton lacks",
+                        object.name, clip.name, animation.joint
+                    )));
+                }
+            }
+        }
+
+        if let Some(terrain) = &self.terrain {
+            terrain
+                .validate()
+                .map_err(|error| SceneError::Invalid(format!("terrain {error}")))?;
+            if let Some(material) = terrain.material.as_deref() {
+                if !material_names.contains(material) {
+                    return Err(SceneError::Invalid(format!(
+                        "terrain uses unknown material '{material}'"
+                    )));
+                }
+            }
+        }
+
+        for (index, light) in self.lights.iter().enumerate() {
+            if let LightKind::Point { range, .. } = light.kind {
+                if range <= 0.0 {
+                    return Err(SceneError::Invalid(format!(
+                        "point light {index} has range {range}, expected a positive range"
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn mesh(&self, name: &str) -> Option<&Mesh> {
+        self.meshes.iter().find(|mesh| mesh.name == name)
+    }
+
+    pub fn material(&self, name: &str) -> Option<&Material> {
+        self.materials.iter().find(|material| material.name == name)
+    }
+
+    pub fn animation(&self, name: &str) -> Option<&AnimationClip> {
+        self.animations
+            .iter()
+            .find(|animation| animation.name == name)
+    }
+
+    pub fn skeleton(&self, name: &str) -> Option<&Skeleton> {
+        self.skeletons.iter().find(|skeleton| skeleton.name == name)
+    }
+
+    fn default_ambient_light() -> [f32; 3] {
+        [0.1, 0.1, 0.1]
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Scene {
+    /// Reads a RON scene, or imports a `.gltf` or `.glb` file.
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self, SceneError> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        if matches!(extension, Some("gltf" | "glb")) {
+            return crate::gltf_import::import(path);
+        }
+        let mut scene = Self::from_ron(&std::fs::read_to_string(path).map_err(SceneError::Io)?)?;
+        scene.directory = path.parent().map(PathBuf::from);
+        Ok(scene)
+    }
+
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> Result<(), SceneError> {
+        std::fs::write(path, self.to_ron()?).map_err(SceneError::Io)
+    }
+}
+
+/// The colored triangle the app has always shown.
+impl Default for Scene {
+    fn default() -> Self {
+        Self {
+            version: Self::VERSION,
+            clear_color: None,
+            camera: None,
+            ambient_light: Self::default_ambient_light(),
+            meshes: vec![Mesh::triangle()],
+            materials: Vec::new(),
+            lights: Vec::new(),
+            animations: Vec::new(),
+            skeletons: Vec::new(),
+            objects: vec![Object {
+                name: "triangle".to_string(),
+                mesh: "triangle".to_string(),
+                ..Default::default()
+            }],
+            skybox: None,
+            environment: None,
+            terrain: None,
+            directory: None,
+        }
+    }
+}
+
+impl Mesh {
+    /// A triangle facing the default camera with red, green and blue corners.
+    pub fn triangle() -> Self {
+        let vertex = |position: [f32; 3], color| Vertex {
+            position,
+            normal: [0.0, 0.0, 1.0],
+            color,
+            uv: [(position[0] + 1.0) * 0.5, (1.0 - position[1]) * 0.5],
+        };
+        Self {
+            name: "triangle".to_string(),
+            vertices: vec![
+                vertex([1.0, -1.0, 0.0], [1.0, 0.0, 0.0, 1.0]),
+                vertex([-1.0, -1.0, 0.0], [0.0, 1.0, 0.0, 1.0]),
+                vertex([0.0, 1.0, 0.0], [0.0, 0.0, 1.0, 1.0]),
+            ],
+            indices: vec![0, 1, 2],
+            skin: Vec::new(),
+            lods: Vec::new(),
+        }
+    }
+
+    /// A copy with `ratio` of the triangles, keeping only the vertices still in use.
+    pub fn simplified(&self, ratio: f32) -> Mesh {
+        let target = ((self.indices.len() / 3) as f32 * ratio.clamp(0.0, 1.0)).ceil() as usize;
+        let indices = crate::simplify::simplify(&self.vertices, &self.indices, target.max(1));
+
+        let mut remap = vec![u32::MAX; self.vertices.len()];
+        let mut mesh = Mesh {
+            name: self.name.clone(),
+            vertices: Vec::new(),
+            indices: Vec::with_capacity(indices.len()),
+            skin: Vec::new(),
+            lods: Vec::new(),
+        };
+        for index in indices {
+            let vertex = index as usize;
+            if remap[vertex] == u32::MAX {
+                remap[vertex] = mesh.vertices.len() as u32;
+                mesh.vertices.push(self.vertices[vertex]);
+                if let Some(skin) = self.skin.get(vertex) {
+                    mesh.skin.push(*skin);
+                }
+            }
+            mesh.indices.push(remap[vertex]);
+        }
+        mesh
+    }
+
+    /// Describes the first problem with the indices, the skin or the levels of detail.
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        validate_geometry(&self.vertices, &self.indices, &self.skin)?;
+        let mut screen_size = f32::INFINITY;
+        for (level, lod) in self.lods.iter().enumerate() {
+            let level = level + 1;
+            if !(lod.screen_size > 0.0 && lod.screen_size < screen_size) {
+                return Err(format!(
+                    "has LOD {level} at screen size {}, expected a positive size below the previous LOD's",
+                    lod.screen_size
+                ));
+            }
+            screen_size = lod.screen_size;
+            match &lod.source {
+                LodSource::Simplified(ratio) if !(*ratio > 0.0 && *ratio <= 1.0) => {
+                    return Err(format!(
+                        "has LOD {level} simplified to {ratio}, expected a fraction above 0 and up to 1"
+                    ))
+                }
+                LodSource::Simplified(_) => {}
+                LodSource::Authored {
+                    vertices,
+                    indices,
+                    skin,
+                } => {
+                    if skin.is_empty() != self.skin.is_empty() {
+                        return Err(format!(
+                            "has LOD {level} that has to be skinned exactly if the mesh is"
+                        ));
+                    }
+                    validate_geometry(vertices, indices, skin)
+                        .map_err(|error| format!("LOD {level} {error}"))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The mesh of every level of detail, excluding the mesh itself.
+    pub fn lod_meshes(&self) -> Vec<Mesh> {
+        self.lods
+            .iter()
+            .map(|lod| match &lod.source {
+                LodSource::Simplified(ratio) => self.simplified(*ratio),
+                LodSource::Authored {
+                    vertices,
+                    indices,
+                    skin,
+                } => Mesh {
+                    name: self.name.clone(),
+                    vertices: vertices.clone(),
+                    indices: indices.clone(),
+                    skin: skin.clone(),
+                    lods: Vec::new(),
+                },
+            })
+            .collect()
+    }
+
+    /// A copy with its own vertices for every triangle corner, in order.
+    pub fn unindexed(&self) -> Mesh {
+        let corners = |index: &u32| *index as usize;
+        Mesh {
+            name: self.name.clone(),
+            vertices: self
+                .indices
+                .iter()
+                .map(|index| self.vertices[corners(index)])
+                .collect(),
+            indices: (0..self.indices.len() as u32).collect(),
+            skin: if self.skin.is_empty() {
+                Vec::new()
+            } else {
+                self.indices
+                    .iter()
+                    .map(|index| self.skin[corners(index)])
+                    .collect()
+            },
+            lods: Vec::new(),
+        }
+    }
+
+    /// Bounding volumes of the vertex positions.
+    pub fn bounds(&self) -> Bounds {
+        let positions: Vec<nalgebra_glm::Vec3> = self
+            .vertices
+            .iter()
+            .map(|vertex| vertex.position.into())
+            .collect();
+        Bounds::from_points(&positions)
+    }
+}
+
+fn validate_geometry(
+    vertices: &[Vertex],
+    indices: &[u32],
+    skin: &[SkinVertex],
+) -> Result<(), String> {
+    if indices.is_empty() || !indices.len().is_multiple_of(3) {
+        return Err(format!(
+            "has {} indices, expected a non-zero multiple of 3",
+            indices.len()
+        ));
+    }
+    if let Some(index) = indices
+        .iter()
+        .find(|index| **index as usize >= vertices.len())
+    {
+        return Err(format!(
+            "has index {index} but only {} vertices",
+            vertices.len()
+        ));
+    }
+    if !skin.is_empty() && skin.len() != vertices.len() {
+        return Err(format!(
+            "has {} skin vertices for {} vertices",
+            skin.len(),
+            vertices.len()
+        ));
+    }
+    Ok(())
+}
+
+impl Vertex {
+    pub fn vertex_attributes() -> Vec<wgpu::VertexAttribute> {
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x4, 3 => Float32x2]
+            .to_vec()
+    }
+
+    pub fn description(attributes: &[wgpu::VertexAttribute]) -> wgpu::VertexBufferLayout<'_> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes,
+        }
+    }
+}
+
+impl SkinVertex {
+    pub fn vertex_attributes() -> Vec<wgpu::VertexAttribute> {
+        wgpu::vertex_attr_array![4 => Uint32x4, 5 => Float32x4].to_vec()
+    }
+
+    pub fn description(attributes: &[wgpu::VertexAttribute]) -> wgpu::VertexBufferLayout<'_> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<SkinVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes,
+        }
+    }
+}
+
+impl Default for Vertex {
+    fn default() -> Self {
+        Self {
+            position: [0.0; 3],
+            normal: [0.0, 0.0, 1.0],
+            color: [1.0; 4],
+            uv: [0.0; 2],
+        }
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            base_color: [1.0; 4],
+            emissive: [0.0; 3],
+            metallic: 0.0,
+            roughness: 0.5,
+        }
+    }
+}
+
+impl Light {
+    fn default_color() -> [f32; 3] {
+        [1.0; 3]
+    }
+
+    fn default_intensity() -> f32 {
+        1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The error `source` fails validation with.
+    fn invalid(source: &str) -> String {
+        match Scene::from_ron(source) {
+            Err(SceneError::Invalid(message)) => message,
+            result => panic!("expected a validation error, got {result:?}"),
+        }
+    }
+
+    const QUAD: &str = "(
+        name: \"quad\",
+        vertices: [(position: (0.0, 0.0, 0.0)), (position: (1.0, 0.0, 0.0)), (position: (0.0, 1.0, 0.0))],
+        indices: [0, 1, 2],
+    )";
+
+    #[test]
+    fn default_scene_round_trips() {
+        let scene = Scene::default();
+        let source = scene.to_ron().unwrap();
+        assert_eq!(Scene::from_ron(&source).unwrap(), scene);
+    }
+
+    #[test]
+    fn bundled_scenes_load() {
+        for source in [
+            include_str!("../scenes/animation.ron"),
+            include_str!("../scenes/glass.ron"),
+            include_str!("../scenes/lights.ron"),
+        ] {
+            let scene = Scene::from_ron(source).unwrap();
+            assert_eq!(Scene::from_ron(&scene.to_ron().unwrap()).unwrap(), scene);
+        }
+    }
+
+    #[test]
+    fn scenes_without_a_version_are_rejected() {
+        assert!(matches!(
+            Scene::from_ron("(meshes: [])"),
+            Err(SceneError::MissingVersion)
+        ));
+    }
+
+    #[test]
+    fn scenes_from_newer_versions_are_rejected() {
+        // Before complaining about fields this version doesn't know
+        assert!(matches!(
+            Scene::from_ron("(version: 2, portals: [])"),
+            Err(SceneError::UnsupportedVersion(2))
+        ));
+    }
+
+    #[test]
+    fn current_version_may_leave_everything_else_out() {
+        let scene = Scene::from_ron("(version: 1)").unwrap();
+        assert!(scene.meshes.is_empty() && scene.objects.is_empty());
+    }
+
+    #[test]
+    fn malformed_scenes_fail_to_parse() {
+        assert!(matches!(
+            Scene::from_ron("(version: 1, meshes: 3)"),
+            Err(SceneError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn unknown_references_are_invalid() {
+        assert!(
+            invalid("(version: 1, objects: [(name: \"a\", mesh: \"missing\")])")
+                .contains("unknown mesh 'missing'")
+        );
+        assert!(invalid(&format!(
+            "(version: 1, meshes: [{QUAD}], objects: [(name: \"a\", mesh: \"quad\", material: \"missing\")])"
+        ))
+        .contains("unknown material 'missing'"));
+        assert!(invalid(&format!(
+            "(version: 1, meshes: [{QUAD}], objects: [(name: \"a\", mesh: \"quad\", animation: (clip: \"missing\"))])"
+        ))
+        .contains("unknown animation 'missing'"));
+    }
+
+    #[test]
+    fn duplicate_names_are_invalid() {
+        assert!(invalid(&format!("(version: 1, meshes: [{QUAD}, {QUAD}])"))
+            .contains("mesh 'quad' is defined more than once"));
+    }
+
+    #[test]
+    fn out_of_range_values_are_invalid() {
+        assert!(
+            invalid("(version: 1, materials: [(name: \"m\", metallic: 2.0)])")
+                .contains("outside of 0 to 1")
+        );
+        assert!(invalid(
+            "(version: 1, lights: [(kind: Point(position: (0.0, 0.0, 0.0), range: 0.0))])"
+        )
+        .contains("expected a positive range"));
+        assert!(invalid(
+            "(version: 1, meshes: [(name: \"m\", vertices: [(position: (0.0, 0.0, 0.0))], indices: [0, 0, 1])])"
+        )
+        .starts_with("mesh 'm'"));
+    }
+
+    #[test]
+    fn empty_animation_tracks_are_invalid() {
+        assert!(
+            invalid("(version: 1, animations: [(name: \"a\", translation: (keyframes: []))])")
+                .contains("translation track has no keyframes")
+        );
+    }
+}
@@ -0,0 +1,461 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{DebugView, Duration, Instant, RenderPath, Transparency};
+
+/// Presentation settings that can be changed while the app is running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplaySettings {
+    /// Requested present mode, resolved against the surface capabilities.
+    pub present_mode: wgpu::PresentMode,
+    /// Upper bound on the number of frames rendered per second.
+    /// `None` renders as fast as the present mode allows.
+    pub target_fps: Option<u32>,
+}
+
+impl DisplaySettings {
+    /// Present modes cycled through at runtime.
+    pub const PRESENT_MODES: [wgpu::PresentMode; 4] = [
+        wgpu::PresentMode::AutoVsync,
+        wgpu::PresentMode::Fifo,
+        wgpu::PresentMode::Mailbox,
+        wgpu::PresentMode::Immediate,
+    ];
+
+    /// Frame limit used when the limiter is toggled on without a configured rate.
+    pub const DEFAULT_TARGET_FPS: u32 = 60;
+
+    /// The present mode after the current one that `supported` can apply
+    /// without falling back.
+    pub fn next_present_mode(&self, supported: &[wgpu::PresentMode]) -> wgpu::PresentMode {
+        let start = Self::PRESENT_MODES
+            .iter()
+            .position(|mode| *mode == self.present_mode)
+            .map_or(0, |index| index + 1);
+        (0..Self::PRESENT_MODES.len())
+            .map(|offset| Self::PRESENT_MODES[(start + offset) % Self::PRESENT_MODES.len()])
+            .find(|mode| is_present_mode_supported(*mode, supported))
+            .unwrap_or(wgpu::PresentMode::Fifo)
+    }
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self {
+            present_mode: wgpu::PresentMode::AutoVsync,
+            target_fps: None,
+        }
+    }
+}
+
+fn is_present_mode_supported(mode: wgpu::PresentMode, supported: &[wgpu::PresentMode]) -> bool {
+    // The automatic modes are resolved by wgpu itself
+    matches!(
+        mode,
+        wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync
+    ) || supported.contains(&mode)
+}
+
+/// Picks the closest present mode to `requested` that the surface supports.
+pub fn select_present_mode(
+    requested: wgpu::PresentMode,
+    supported: &[wgpu::PresentMode],
+) -> wgpu::PresentMode {
+    use wgpu::PresentMode::*;
+
+    if is_present_mode_supported(requested, supported) {
+        return requested;
+    }
+
+    let fallbacks: &[wgpu::PresentMode] = match requested {
+        Immediate => &[Mailbox, Fifo],
+        _ => &[Fifo],
+    };
+    let selected = fallbacks
+        .iter()
+        .copied()
+        .find(|mode| supported.contains(mode))
+        // Fifo is the only mode every surface has to support
+        .unwrap_or(Fifo);
+    tracing::warn!("Present mode {requested:?} is not supported, falling back to {selected:?}");
+    selected
+}
+
+/// Paces redraws to a target frame rate using `ControlFlow::WaitUntil`.
+pub struct FrameLimiter {
+    frame_interval: Option<Duration>,
+    next_frame: Instant,
+}
+
+impl FrameLimiter {
+    pub fn new(target_fps: Option<u32>) -> Self {
+        let mut limiter = Self {
+            frame_interval: None,
+            next_frame: Instant::now(),
+        };
+        limiter.set_target_fps(target_fps);
+        limiter
+    }
+
+    pub fn set_target_fps(&mut self, target_fps: Option<u32>) {
+        self.frame_interval = target_fps
+            .filter(|fps| *fps > 0)
+            .map(|fps| Duration::from_secs_f64(1.0 / fps as f64));
+        self.next_frame = Instant::now();
+    }
+
+    /// The instant the next frame is due, or `None` when unlimited.
+    pub fn next_frame(&self) -> Option<Instant> {
+        self.frame_interval.map(|_| self.next_frame)
+    }
+
+    pub fn is_frame_due(&self, now: Instant) -> bool {
+        self.frame_interval.is_none() || now >= self.next_frame
+    }
+
+    pub fn frame_rendered(&mut self, now: Instant) {
+        if let Some(interval) = self.frame_interval {
+            // Don't try to catch up on frames that were missed
+            self.next_frame = (self.next_frame + interval).max(now);
+        }
+    }
+}
+
+impl Default for FrameLimiter {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+/// Options fixed for the lifetime of a [`crate::Renderer`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RendererSettings {
+    /// Backends to pick an adapter from, falls back to `WGPU_BACKEND` or all backends
+    pub backends: Option<wgpu::Backends>,
+    /// Case-insensitive part of the name of the adapter to use
+    pub adapter: Option<String>,
+    /// Requested MSAA sample count, lowered to what the device supports
+    pub sample_count: u32,
+    pub depth_format: wgpu::TextureFormat,
+    /// Deferred shading needs a depth format without stencil and no multisampling,
+    /// it falls back to forward shading otherwise
+    pub render_path: RenderPath,
+}
+
+impl Default for RendererSettings {
+    fn default() -> Self {
+        Self {
+            backends: None,
+            adapter: None,
+            sample_count: 1,
+            depth_format: wgpu::TextureFormat::Depth32Float,
+            render_path: RenderPath::Forward,
+        }
+    }
+}
+
+/// Scene parameters that can be changed without recreating any GPU resources.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SceneSettings {
+    /// Linear RGBA color the frame is cleared to
+    pub clear_color: [f64; 4],
+    /// Rotation of the model around the Y axis, in degrees per second
+    pub rotation_speed: f32,
+    /// Replaces the shaded surfaces for inspecting geometry
+    pub debug_view: DebugView,
+    /// How overlapping translucent objects are blended
+    pub transparency: Transparency,
+    pub ambient_occlusion: AmbientOcclusionSettings,
+}
+
+impl SceneSettings {
+    pub fn clear_color(&self) -> wgpu::Color {
+        let [r, g, b, a] = self.clear_color;
+        wgpu::Color { r, g, b, a }
+    }
+}
+
+impl Default for SceneSettings {
+    fn default() -> Self {
+        Self {
+            clear_color: [0.19, 0.24, 0.42, 1.0],
+            rotation_speed: 30.0,
+            debug_view: DebugView::Shaded,
+            transparency: Transparency::Sorted,
+            ambient_occlusion: AmbientOcclusionSettings::default(),
+        }
+    }
+}
+
+/// Screen-space ambient occlusion, darkening the ambient and environment light
+/// where surfaces are close to each other. Only applied with deferred shading.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AmbientOcclusionSettings {
+    pub enabled: bool,
+    /// World-space radius around a surface point that is searched for occluders
+    pub radius: f32,
+    /// Exponent applied to the unoccluded fraction, 0 leaves the light unchanged
+    pub intensity: f32,
+}
+
+impl Default for AmbientOcclusionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            radius: 0.5,
+            intensity: 1.5,
+        }
+    }
+}
+
+/// Rate and speed of the fixed-timestep scene simulation.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SimulationSettings {
+    /// Simulation steps per second, independent of the frame rate
+    pub tick_rate: u32,
+    /// Multiplier applied to the elapsed time, 1 is real time
+    pub time_scale: f32,
+}
+
+impl SimulationSettings {
+    pub const MIN_TIME_SCALE: f32 = 1.0 / 16.0;
+    pub const MAX_TIME_SCALE: f32 = 16.0;
+
+    /// Accepts any finite, non-negative time scale, the clock clamps it to
+    /// the supported range.
+    pub fn validate_time_scale(time_scale: f32) -> Result<f32, String> {
+        if time_scale.is_finite() && time_scale >= 0.0 {
+            Ok(time_scale)
+        } else {
+            Err(format!(
+                "time scale must be a finite, non-negative number, got {time_scale}"
+            ))
+        }
+    }
+}
+
+impl Default for SimulationSettings {
+    fn default() -> Self {
+        Self {
+            tick_rate: 60,
+            time_scale: 1.0,
+        }
+    }
+}
+
+/// Accumulates frame time and hands it out in fixed simulation steps.
+pub struct SimulationClock {
+    timestep: Duration,
+    time_scale: f32,
+    accumulator: Duration,
+    paused: bool,
+    pending_steps: u32,
+}
+
+impl SimulationClock {
+    /// Bounds the work done after a hitch, the remaining time is dropped.
+    const MAX_STEPS_PER_FRAME: u32 = 8;
+
+    pub fn new(settings: SimulationSettings) -> Self {
+        let mut clock = Self {
+            timestep: Duration::ZERO,
+            time_scale: 1.0,
+            accumulator: Duration::ZERO,
+            paused: false,
+            pending_steps: 0,
+        };
+        clock.apply_settings(settings);
+        clock
+    }
+
+    pub fn apply_settings(&mut self, settings: SimulationSettings) {
+        self.timestep = Duration::from_secs_f64(1.0 / settings.tick_rate.max(1) as f64);
+        self.time_scale = match settings.time_scale {
+            time_scale if time_scale.is_nan() => 1.0,
+            time_scale => time_scale.clamp(
+                SimulationSettings::MIN_TIME_SCALE,
+                SimulationSettings::MAX_TIME_SCALE,
+            ),
+        };
+        self.accumulator = self.accumulator.min(self.timestep);
+    }
+
+    pub fn timestep(&self) -> Duration {
+        self.timestep
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.pending_steps = 0;
+    }
+
+    /// Queues a single step to run on the next frame while paused.
+    pub fn step(&mut self) {
+        if self.paused {
+            self.pending_steps += 1;
+        }
+    }
+
+    /// Adds the time elapsed since the last frame and returns how many
+    /// fixed steps the simulation has to advance by.
+    pub fn advance(&mut self, frame_time: Duration) -> u32 {
+        if self.paused {
+            return std::mem::take(&mut self.pending_steps);
+        }
+
+        // Scaling round-trips through floats, keep real time exact
+        self.accumulator += if self.time_scale == 1.0 {
+            frame_time
+        } else {
+            frame_time.mul_f64(self.time_scale as f64)
+        };
+
+        let mut steps = 0;
+        while self.accumulator >= self.timestep {
+            self.accumulator -= self.timestep;
+            steps += 1;
+            if steps == Self::MAX_STEPS_PER_FRAME {
+                self.accumulator = self.accumulator.min(self.timestep);
+                break;
+            }
+        }
+        steps
+    }
+
+    /// How far the current frame lies between the last two simulation steps, from 0 to 1.
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator.as_secs_f64() / self.timestep.as_secs_f64()).min(1.0) as f32
+    }
+}
+
+impl Default for SimulationClock {
+    fn default() -> Self {
+        Self::new(SimulationSettings::default())
+    }
+}
+
+/// Appearance of the performance overlay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HudSettings {
+    pub visible: bool,
+    /// Size of a font pixel in physical pixels
+    pub scale: u32,
+}
+
+impl Default for HudSettings {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            scale: 2,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A clock stepping every 100 ms.
+    fn clock(time_scale: f32) -> SimulationClock {
+        SimulationClock::new(SimulationSettings {
+            tick_rate: 10,
+            time_scale,
+        })
+    }
+
+    fn millis(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn clock_steps_and_interpolates_between_steps() {
+        let mut clock = clock(1.0);
+        assert_eq!(clock.advance(millis(50)), 0);
+        assert!((clock.alpha() - 0.5).abs() < 1e-6);
+        assert_eq!(clock.advance(millis(200)), 2);
+        assert!((clock.alpha() - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn clock_scales_time() {
+        let mut clock = clock(2.0);
+        assert_eq!(clock.advance(millis(100)), 2);
+        assert_eq!(clock.alpha(), 0.0);
+    }
+
+    #[test]
+    fn clock_drops_time_beyond_the_step_limit() {
+        let mut clock = clock(1.0);
+        assert_eq!(
+            clock.advance(Duration::from_secs(10)),
+            SimulationClock::MAX_STEPS_PER_FRAME
+        );
+        // At most one step of the backlog is kept for the next frame
+        assert_eq!(clock.alpha(), 1.0);
+        assert_eq!(clock.advance(Duration::ZERO), 1);
+        assert!(clock.alpha() < 1.0);
+        assert_eq!(clock.advance(Duration::ZERO), 0);
+    }
+
+    #[test]
+    fn clock_clamps_invalid_time_scales() {
+        for time_scale in [-1.0, f32::NAN, f32::INFINITY, f32::NEG_INFINITY, 1000.0] {
+            let mut clock = clock(time_scale);
+            assert!(clock.advance(millis(100)) <= SimulationClock::MAX_STEPS_PER_FRAME);
+        }
+        assert_eq!(clock(f32::NAN).advance(millis(100)), 1);
+        assert_eq!(clock(1000.0).advance(millis(100)), 8);
+        assert!(SimulationSettings::validate_time_scale(0.5).is_ok());
+        assert!(SimulationSettings::validate_time_scale(-1.0).is_err());
+        assert!(SimulationSettings::validate_time_scale(f32::NAN).is_err());
+        assert!(SimulationSettings::validate_time_scale(f32::INFINITY).is_err());
+    }
+
+    #[test]
+    fn paused_clock_only_runs_queued_steps() {
+        let mut clock = clock(1.0);
+        clock.set_paused(true);
+        assert_eq!(clock.advance(Duration::from_secs(1)), 0);
+        clock.step();
+        clock.step();
+        assert_eq!(clock.advance(millis(10)), 2);
+        assert_eq!(clock.advance(millis(10)), 0);
+
+        // Steps queued while paused don't carry over
+        clock.step();
+        clock.set_paused(false);
+        assert_eq!(clock.advance(millis(10)), 0);
+    }
+
+    #[test]
+    fn unsupported_present_modes_fall_back_to_fifo() {
+        use wgpu::PresentMode::*;
+
+        assert_eq!(select_present_mode(Immediate, &[Fifo, Mailbox]), Mailbox);
+        assert_eq!(select_present_mode(Mailbox, &[FifoRelaxed, Fifo]), Fifo);
+        assert_eq!(select_present_mode(Mailbox, &[FifoRelaxed]), Fifo);
+        assert_eq!(select_present_mode(AutoNoVsync, &[Fifo]), AutoNoVsync);
+    }
+
+    #[test]
+    fn cycling_present_modes_skips_unsupported_ones() {
+        let display = DisplaySettings {
+            present_mode: wgpu::PresentMode::Fifo,
+            target_fps: None,
+        };
+        let supported = [wgpu::PresentMode::Fifo, wgpu::PresentMode::Immediate];
+        assert_eq!(
+            display.next_present_mode(&supported),
+            wgpu::PresentMode::Immediate
+        );
+    }
+}
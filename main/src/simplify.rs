@@ -0,0 +1,519 @@
+use std::{cmp::Ordering, collections::BinaryHeap, collections::HashMap};
+
+use crate::Vertex;
+
+/// Reduces an indexed triangle list to at most `target_triangles` triangles,
+/// returning indices into the original `vertices`.
+///
+/// Edges are collapsed cheapest first by their quadric error, always onto one
+/// of their ends, so no new vertices are needed. Vertices sharing a position
+/// move together, keeping UV and normal seams closed, and mesh borders are
+/// weighted to stay in place. Collapses that would flip a triangle are skipped,
+/// so the result can stay above the target.
+pub fn simplify(vertices: &[Vertex], indices: &[u32], target_triangles: usize) -> Vec<u32> {
+    let mut simplifier = Simplifier::new(vertices, indices);
+    simplifier.run(target_triangles);
+    simplifier.indices()
+}
+
+/// Symmetric 4x4 matrix measuring the squared distance to a set of planes.
+#[derive(Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    /// Squared distance to the plane `normal · p + distance = 0`, times `weight`.
+    fn plane(normal: [f64; 3], distance: f64, weight: f64) -> Self {
+        let [a, b, c] = normal;
+        let d = distance;
+        Self(
+            [
+                a * a,
+                a * b,
+                a * c,
+                a * d,
+                b * b,
+                b * c,
+                b * d,
+                c * c,
+                c * d,
+                d * d,
+            ]
+            .map(|value| value * weight),
+        )
+    }
+
+    fn add(&mut self, other: &Self) {
+        for (value, other) in self.0.iter_mut().zip(other.0) {
+            *value += other;
+        }
+    }
+
+    fn error(&self, [x, y, z]: [f64; 3]) -> f64 {
+        let q = &self.0;
+        q[0] * x * x
+            + 2.0 * q[1] * x * y
+            + 2.0 * q[2] * x * z
+            + 2.0 * q[3] * x
+            + q[4] * y * y
+            + 2.0 * q[5] * y * z
+            + 2.0 * q[6] * y
+            + q[7] * z * z
+            + 2.0 * q[8] * z
+            + q[9]
+    }
+}
+
+/// A candidate collapse of `from` onto `to`, ordered cheapest first.
+struct Collapse {
+    cost: f64,
+    from: usize,
+    to: usize,
+    /// Versions of both ends when the candidate was queued, stale ones are skipped
+    versions: (u32, u32),
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+struct Simplifier<'a> {
+    vertices: &'a [Vertex],
+    /// Position shared by each vertex
+    vertex_positions: Vec<usize>,
+    positions: Vec<[f64; 3]>,
+    /// Vertices at each position
+    position_vertices: Vec<Vec<usize>>,
+    /// Position each position was collapsed onto, itself while it is alive
+    collapsed: Vec<usize>,
+    quadrics: Vec<Quadric>,
+    versions: Vec<u32>,
+    /// Triangles touching each position, possibly already removed
+    position_triangles: Vec<Vec<usize>>,
+    triangles: Vec<[usize; 3]>,
+    removed: Vec<bool>,
+    triangle_count: usize,
+    queue: BinaryHeap<Collapse>,
+}
+
+impl<'a> Simplifier<'a> {
+    /// Border edges count this much more than interior surface
+    const BORDER_WEIGHT: f64 = 10.0;
+
+    fn new(vertices: &'a [Vertex], indices: &[u32]) -> Self {
+        let mut position_ids: HashMap<[u32; 3], usize> = HashMap::new();
+        let mut positions = Vec::new();
+        let mut position_vertices: Vec<Vec<usize>> = Vec::new();
+        let vertex_positions = vertices
+            .iter()
+            .enumerate()
+            .map(|(index, vertex)| {
+                let id = *position_ids
+                    .entry(vertex.position.map(f32::to_bits))
+                    .or_insert_with(|| {
+                        positions.push(vertex.position.map(f64::from));
+                        position_vertices.push(Vec::new());
+                        positions.len() - 1
+                    });
+                position_vertices[id].push(index);
+                id
+            })
+            .collect();
+
+        let triangles: Vec<[usize; 3]> = indices
+            .chunks_exact(3)
+            .map(|triangle| [0, 1, 2].map(|corner| triangle[corner] as usize))
+            .collect();
+        let count = positions.len();
+        let mut simplifier = Self {
+            vertices,
+            vertex_positions,
+            positions,
+            position_vertices,
+            collapsed: (0..count).collect(),
+            quadrics: vec![Quadric::default(); count],
+            versions: vec![0; count],
+            position_triangles: vec![Vec::new(); count],
+            removed: vec![false; triangles.len()],
+            triangle_count: triangles.len(),
+            triangles,
+            queue: BinaryHeap::new(),
+        };
+        simplifier.initialize();
+        simplifier
+    }
+
+    fn initialize(&mut self) {
+        // Edges used by a single triangle are on the border
+        let mut edge_uses: HashMap<(usize, usize), u32> = HashMap::new();
+        for triangle in 0..self.triangles.len() {
+            let corners = self.corners(triangle);
+            if corners[0] == corners[1] || corners[1] == corners[2] || corners[0] == corners[2] {
+                self.removed[triangle] = true;
+                self.triangle_count -= 1;
+                continue;
+            }
+            for position in corners {
+                self.position_triangles[position].push(triangle);
+            }
+            let Some((normal, area)) = self.normal(corners) else {
+                continue;
+            };
+            let distance = -dot(normal, self.positions[corners[0]]);
+            let quadric = Quadric::plane(normal, distance, area);
+            for position in corners {
+                self.quadrics[position].add(&quadric);
+            }
+            for edge in 0..3 {
+                let (a, b) = (corners[edge], corners[(edge + 1) % 3]);
+                *edge_uses.entry((a.min(b), a.max(b))).or_default() += 1;
+            }
+        }
+
+        for triangle in 0..self.triangles.len() {
+            if self.removed[triangle] {
+                continue;
+            }
+            let corners = self.corners(triangle);
+            let Some((normal, _)) = self.normal(corners) else {
+                continue;
+            };
+            for edge in 0..3 {
+                let (a, b) = (corners[edge], corners[(edge + 1) % 3]);
+                if edge_uses.get(&(a.min(b), a.max(b))) != Some(&1) {
+                    continue;
+                }
+                // Plane through the border edge, perpendicular to the triangle
+                let direction = sub(self.positions[b], self.positions[a]);
+                let length = dot(direction, direction).sqrt();
+                let Some(border_normal) = normalize(cross(direction, normal)) else {
+                    continue;
+                };
+                let quadric = Quadric::plane(
+                    border_normal,
+                    -dot(border_normal, self.positions[a]),
+                    length * length * Self::BORDER_WEIGHT,
+                );
+                self.quadrics[a].add(&quadric);
+                self.quadrics[b].add(&quadric);
+            }
+        }
+
+        for position in 0..self.positions.len() {
+            self.queue_collapses(position);
+        }
+    }
+
+    fn run(&mut self, target_triangles: usize) {
+        while self.triangle_count > target_triangles {
+            let Some(collapse) = self.queue.pop() else {
+                break;
+            };
+            if collapse.versions != (self.versions[collapse.from], self.versions[collapse.to]) {
+                continue;
+            }
+            if self.flips(collapse.from, collapse.to) {
+                continue;
+            }
+            self.collapse(collapse.from, collapse.to);
+        }
+    }
+
+    /// Positions of a triangle's corners, following collapses.
+    fn corners(&self, triangle: usize) -> [usize; 3] {
+        self.triangles[triangle].map(|vertex| self.resolve(self.vertex_positions[vertex]))
+    }
+
+    fn resolve(&self, mut position: usize) -> usize {
+        while self.collapsed[position] != position {
+            position = self.collapsed[position];
+        }
+        position
+    }
+
+    /// Unit normal and area of a triangle, `None` if it is degenerate.
+    fn normal(&self, corners: [usize; 3]) -> Option<([f64; 3], f64)> {
+        let [a, b, c] = corners.map(|position| self.positions[position]);
+        let normal = cross(sub(b, a), sub(c, a));
+        let length = dot(normal, normal).sqrt();
+        normalize(normal).map(|normal| (normal, length * 0.5))
+    }
+
+    /// Queues the collapses of every edge around `position`, in both directions.
+    fn queue_collapses(&mut self, position: usize) {
+        let mut neighbors: Vec<usize> = self.position_triangles[position]
+            .iter()
+            .filter(|triangle| !self.removed[**triangle])
+            .flat_map(|triangle| self.corners(*triangle))
+            .filter(|neighbor| *neighbor != position)
+            .collect();
+        neighbors.sort_unstable();
+        neighbors.dedup();
+
+        // Only costs of edges around `position` changed, the others stay valid
+        self.versions[position] += 1;
+        for neighbor in neighbors {
+            let mut quadric = self.quadrics[position];
+            quadric.add(&self.quadrics[neighbor]);
+            for (from, to) in [(position, neighbor), (neighbor, position)] {
+                self.queue.push(Collapse {
+                    cost: quadric.error(self.positions[to]),
+                    from,
+                    to,
+                    versions: (self.versions[from], self.versions[to]),
+                });
+            }
+        }
+    }
+
+    /// Whether moving `from` onto `to` turns any remaining triangle around.
+    fn flips(&self, from: usize, to: usize) -> bool {
+        self.position_triangles[from].iter().any(|triangle| {
+            if self.removed[*triangle] {
+                return false;
+            }
+            let corners = self.corners(*triangle);
+            if corners.contains(&to) {
+                return false;
+            }
+            let moved = corners.map(|corner| if corner == from { to } else { corner });
+            match (self.normal(corners), self.normal(moved)) {
+                (Some((before, _)), Some((after, _))) => dot(before, after) < 0.2,
+                _ => true,
+            }
+        })
+    }
+
+    fn collapse(&mut self, from: usize, to: usize) {
+        self.collapsed[from] = to;
+        // Invalidates the remaining collapses of `from`
+        self.versions[from] += 1;
+        let quadric = self.quadrics[from];
+        self.quadrics[to].add(&quadric);
+
+        let triangles = std::mem::take(&mut self.position_triangles[from]);
+        for triangle in triangles {
+            if self.removed[triangle] {
+                continue;
+            }
+            let corners = self.corners(triangle);
+            if corners[0] == corners[1] || corners[1] == corners[2] || corners[0] == corners[2] {
+                self.removed[triangle] = true;
+                self.triangle_count -= 1;
+            } else {
+                self.position_triangles[to].push(triangle);
+            }
+        }
+        self.queue_collapses(to);
+    }
+
+    /// Indices of the remaining triangles, each corner replaced by the vertex
+    /// at its final position that best matches its normal.
+    fn indices(&self) -> Vec<u32> {
+        let remap: Vec<u32> = (0..self.vertices.len())
+            .map(|vertex| {
+                let position = self.resolve(self.vertex_positions[vertex]);
+                if position == self.vertex_positions[vertex] {
+                    return vertex as u32;
+                }
+                let normal = self.vertices[vertex].normal;
+                self.position_vertices[position]
+                    .iter()
+                    .copied()
+                    .max_by(|a, b| {
+                        let similarity = |other: usize| {
+                            let other = self.vertices[other].normal;
+                            normal[0] * other[0] + normal[1] * other[1] + normal[2] * other[2]
+                        };
+                        similarity(*a).total_cmp(&similarity(*b))
+                    })
+                    .unwrap_or(vertex) as u32
+            })
+            .collect();
+
+        self.triangles
+            .iter()
+            .zip(&self.removed)
+            .filter(|(_, removed)| !**removed)
+            .flat_map(|(triangle, _)| triangle.map(|vertex| remap[vertex]))
+            .collect()
+    }
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(vector: [f64; 3]) -> Option<[f64; 3]> {
+    let length = dot(vector, vector).sqrt();
+    (length > f64::EPSILON).then(|| vector.map(|component| component / length))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /// A `size` by `size` grid of quads in the xz plane between 0 and 1, facing
+    /// up, raised by `height`. With `seam`, the vertices of the middle column
+    /// are duplicated with different UVs, splitting the grid in two.
+    fn grid(size: u32, seam: bool, height: impl Fn(f32, f32) -> f32) -> (Vec<Vertex>, Vec<u32>) {
+        let mut vertices = Vec::new();
+        let mut ids = HashMap::new();
+        let mut indices = Vec::new();
+        let mut vertex = |column: u32, row: u32, side: u32| {
+            let side = if seam && column == size / 2 { side } else { 0 };
+            *ids.entry((column, row, side)).or_insert_with(|| {
+                let (x, z) = (column as f32 / size as f32, row as f32 / size as f32);
+                vertices.push(Vertex {
+                    position: [x, height(x, z), z],
+                    normal: [0.0, 1.0, 0.0],
+                    color: [1.0; 4],
+                    uv: [x + side as f32, z],
+                });
+                vertices.len() as u32 - 1
+            })
+        };
+        for row in 0..size {
+            for column in 0..size {
+                // Quads left of the seam use its first copy, those right of it the second
+                let side = u32::from(column >= size / 2);
+                let corner = |dx, dz| (column + dx, row + dz);
+                for [a, b, c] in [
+                    [corner(0, 0), corner(0, 1), corner(1, 0)],
+                    [corner(1, 0), corner(0, 1), corner(1, 1)],
+                ] {
+                    for (column, row) in [a, b, c] {
+                        indices.push(vertex(column, row, side));
+                    }
+                }
+            }
+        }
+        (vertices, indices)
+    }
+
+    fn positions(vertices: &[Vertex], triangle: &[u32]) -> [nalgebra_glm::Vec3; 3] {
+        [0, 1, 2]
+            .map(|corner| nalgebra_glm::Vec3::from(vertices[triangle[corner] as usize].position))
+    }
+
+    fn normal(vertices: &[Vertex], triangle: &[u32]) -> nalgebra_glm::Vec3 {
+        let [a, b, c] = positions(vertices, triangle);
+        (b - a).cross(&(c - a))
+    }
+
+    /// Edges used by a single triangle, compared by position so seams don't count.
+    fn border_edges(vertices: &[Vertex], indices: &[u32]) -> Vec<[[u32; 3]; 2]> {
+        let mut uses: HashMap<[[u32; 3]; 2], u32> = HashMap::new();
+        for triangle in indices.chunks_exact(3) {
+            let corners = positions(vertices, triangle).map(|position| position.map(f32::to_bits));
+            for edge in 0..3 {
+                let mut key = [corners[edge], corners[(edge + 1) % 3]].map(|p| [p.x, p.y, p.z]);
+                key.sort_unstable();
+                *uses.entry(key).or_default() += 1;
+            }
+        }
+        uses.into_iter()
+            .filter(|(_, uses)| *uses == 1)
+            .map(|(edge, _)| edge)
+            .collect()
+    }
+
+    fn on_grid_border(position: [u32; 3]) -> bool {
+        let [x, _, z] = position.map(f32::from_bits);
+        [x, z].iter().any(|value| *value == 0.0 || *value == 1.0)
+    }
+
+    #[test]
+    fn flat_plane_reduces_to_target() {
+        let (vertices, indices) = grid(8, false, |_, _| 0.0);
+        let simplified = simplify(&vertices, &indices, 40);
+        assert!(
+            simplified.len() / 3 <= 40,
+            "{} triangles",
+            simplified.len() / 3
+        );
+        assert!(!simplified.is_empty());
+        for triangle in simplified.chunks_exact(3) {
+            let normal = normal(&vertices, triangle);
+            assert!(normal.y > 0.0 && normal.x == 0.0 && normal.z == 0.0);
+        }
+        let area: f32 = simplified
+            .chunks_exact(3)
+            .map(|triangle| normal(&vertices, triangle).norm() * 0.5)
+            .sum();
+        assert!((area - 1.0).abs() < 1e-5, "area {area}");
+    }
+
+    #[test]
+    fn seams_stay_closed() {
+        let (vertices, indices) = grid(8, true, |_, _| 0.0);
+        let simplified = simplify(&vertices, &indices, 40);
+        assert!(simplified.len() < indices.len());
+        for edge in border_edges(&vertices, &simplified) {
+            assert!(edge.into_iter().all(on_grid_border), "open edge {edge:?}");
+        }
+    }
+
+    #[test]
+    fn border_vertices_stay_in_place() {
+        let height = |x: f32, z: f32| (x * 7.0).sin() * (z * 5.0).cos() * 0.05;
+        let (vertices, indices) = grid(8, false, height);
+        let original: Vec<[u32; 3]> = vertices
+            .iter()
+            .map(|vertex| vertex.position.map(f32::to_bits))
+            .filter(|position| on_grid_border(*position))
+            .collect();
+        let simplified = simplify(&vertices, &indices, 60);
+        assert!(simplified.len() < indices.len());
+        let mut border = border_edges(&vertices, &simplified);
+        for edge in &border {
+            assert!(edge.iter().all(|position| original.contains(position)));
+        }
+        // The outline still goes through every corner
+        border.sort_unstable();
+        for corner in [[0.0, 0.0], [0.0, 1.0], [1.0, 0.0], [1.0, 1.0]] {
+            let corner = [corner[0], height(corner[0], corner[1]), corner[1]].map(f32::to_bits);
+            assert!(border.iter().flatten().any(|position| *position == corner));
+        }
+    }
+
+    #[test]
+    fn no_triangle_is_flipped() {
+        let height = |x: f32, z: f32| ((x * 9.0).sin() + (z * 11.0).cos()) * 0.08;
+        let (vertices, indices) = grid(12, false, height);
+        let simplified = simplify(&vertices, &indices, 30);
+        assert!(simplified.len() < indices.len());
+        for triangle in simplified.chunks_exact(3) {
+            assert!(normal(&vertices, triangle).y > 0.0, "{triangle:?}");
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Transform;
+
+/// A hierarchy of joints that skinned meshes are bound to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Skeleton {
+    pub name: String,
+    /// Parents come before their children
+    pub joints: Vec<Joint>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Joint {
+    pub name: String,
+    /// Index of the parent joint, `None` for joints attached to the object
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<usize>,
+    /// Rest pose relative to the parent
+    #[serde(default)]
+    pub transform: Transform,
+    /// Transforms mesh vertices into the joint's space at bind time
+    #[serde(default = "Joint::default_inverse_bind_matrix")]
+    pub inverse_bind_matrix: nalgebra_glm::Mat4,
+}
+
+impl Skeleton {
+    /// The local transform of every joint when nothing is animated.
+    pub fn rest_pose(&self) -> Vec<Transform> {
+        self.joints.iter().map(|joint| joint.transform).collect()
+    }
+
+    /// Skinning matrices for `pose`, one local transform per joint.
+    pub fn joint_matrices(&self, pose: &[Transform]) -> Vec<nalgebra_glm::Mat4> {
+        let mut globals: Vec<nalgebra_glm::Mat4> = Vec::with_capacity(self.joints.len());
+        for (joint, local) in self.joints.iter().zip(pose) {
+            let global = match joint.parent {
+                Some(parent) => globals[parent] * local.matrix(),
+                None => local.matrix(),
+            };
+            globals.push(global);
+        }
+        globals
+            .iter()
+            .zip(&self.joints)
+            .map(|(global, joint)| global * joint.inverse_bind_matrix)
+            .collect()
+    }
+
+    pub fn joint(&self, name: &str) -> Option<usize> {
+        self.joints.iter().position(|joint| joint.name == name)
+    }
+
+    /// Describes the first joint with a duplicate name or a parent after it.
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.joints.is_empty() {
+            return Err("has no joints".to_string());
+        }
+        let mut names = HashSet::new();
+        for (index, joint) in self.joints.iter().enumerate() {
+            if !names.insert(joint.name.as_str()) {
+                return Err(format!("joint '{}' is defined more than once", joint.name));
+            }
+            if joint.parent.is_some_and(|parent| parent >= index) {
+                return Err(format!(
+                    "joint '{}' has to come after its parent",
+                    joint.name
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Joint {
+    fn default_inverse_bind_matrix() -> nalgebra_glm::Mat4 {
+        nalgebra_glm::Mat4::identity()
+    }
+}
@@ -0,0 +1,424 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{DrawStats, SceneError, TargetFormat, UniformBinding};
+
+/// Images the background of a scene is made of. Relative paths are resolved
+/// against the directory of the scene file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Skybox {
+    /// One image per face of the cube, all square and of the same size
+    Faces {
+        positive_x: PathBuf,
+        negative_x: PathBuf,
+        positive_y: PathBuf,
+        negative_y: PathBuf,
+        positive_z: PathBuf,
+        negative_z: PathBuf,
+    },
+    /// A panorama covering 360 degrees horizontally and 180 degrees vertically
+    Equirectangular(PathBuf),
+}
+
+impl Skybox {
+    /// The images the skybox is made of, relative to `directory` if given.
+    pub(crate) fn paths(&self, directory: Option<&Path>) -> Vec<PathBuf> {
+        let paths = match self {
+            Self::Faces {
+                positive_x,
+                negative_x,
+                positive_y,
+                negative_y,
+                positive_z,
+                negative_z,
+            } => vec![
+                positive_x, negative_x, positive_y, negative_y, positive_z, negative_z,
+            ],
+            Self::Equirectangular(path) => vec![path],
+        };
+        paths
+            .into_iter()
+            .map(|path| resolve_path(path, directory))
+            .collect()
+    }
+}
+
+/// The six faces of a cube map in +X, -X, +Y, -Y, +Z, -Z order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cubemap {
+    pub size: u32,
+    pub faces: [image::RgbaImage; 6],
+}
+
+impl Cubemap {
+    pub fn load(
+        source: &Skybox,
+        directory: Option<&Path>,
+        read: ReadFile,
+    ) -> Result<Self, SceneError> {
+        let load_image =
+            |path: &Path| load_image(path, directory, read).map(|image| image.to_rgba8());
+        match source {
+            Skybox::Faces {
+                positive_x,
+                negative_x,
+                positive_y,
+                negative_y,
+                positive_z,
+                negative_z,
+            } => Self::from_faces([
+                load_image(positive_x)?,
+                load_image(negative_x)?,
+                load_image(positive_y)?,
+                load_image(negative_y)?,
+                load_image(positive_z)?,
+                load_image(negative_z)?,
+            ]),
+            Skybox::Equirectangular(path) => {
+                let panorama = load_image(path)?;
+                Ok(Self::from_equirectangular(
+                    &panorama,
+                    (panorama.width() / 4).max(1),
+                ))
+            }
+        }
+    }
+
+    pub fn from_faces(faces: [image::RgbaImage; 6]) -> Result<Self, SceneError> {
+        let size = faces[0].width();
+        if faces
+            .iter()
+            .any(|face| face.width() != size || face.height() != size)
+        {
+            return Err(SceneError::Invalid(
+                "skybox faces have to be square and of the same size".to_string(),
+            ));
+        }
+        Ok(Self { size, faces })
+    }
+
+    /// Resamples a panorama into cube faces of `size` x `size` pixels.
+    pub fn from_equirectangular(panorama: &image::RgbaImage, size: u32) -> Self {
+        let faces = std::array::from_fn(|face| {
+            image::RgbaImage::from_fn(size, size, |x, y| {
+                // Texel centers from -1 to 1, with v pointing down
+                let u = 2.0 * (x as f32 + 0.5) / size as f32 - 1.0;
+                let v = 2.0 * (y as f32 + 0.5) / size as f32 - 1.0;
+                let direction = match face {
+                    0 => nalgebra_glm::vec3(1.0, -v, -u),
+                    1 => nalgebra_glm::vec3(-1.0, -v, u),
+                    2 => nalgebra_glm::vec3(u, 1.0, v),
+                    3 => nalgebra_glm::vec3(u, -1.0, -v),
+                    4 => nalgebra_glm::vec3(u, -v, 1.0),
+                    _ => nalgebra_glm::vec3(-u, -v, -1.0),
+                }
+                .normalize();
+
+                let longitude = direction.x.atan2(direction.z);
+                let latitude = direction.y.asin();
+                sample_bilinear(
+                    panorama,
+                    (longitude / std::f32::consts::TAU + 0.5) * panorama.width() as f32,
+                    (0.5 - latitude / std::f32::consts::PI) * panorama.height() as f32,
+                )
+            })
+        });
+        Self { size, faces }
+    }
+}
+
+/// `path` relative to `directory` if given.
+pub(crate) fn resolve_path(path: &Path, directory: Option<&Path>) -> PathBuf {
+    match directory {
+        Some(directory) => directory.join(path),
+        None => path.to_path_buf(),
+    }
+}
+
+/// Reads a file referenced by a scene, given its resolved path.
+pub type ReadFile<'a> = &'a dyn Fn(&Path) -> std::io::Result<Vec<u8>>;
+
+/// Reads files from disk.
+pub(crate) fn read_file(path: &Path) -> std::io::Result<Vec<u8>> {
+    std::fs::read(path)
+}
+
+/// Decodes the image at `path`, relative to `directory` if given, with the
+/// bytes from `read`.
+pub(crate) fn load_image(
+    path: &Path,
+    directory: Option<&Path>,
+    read: ReadFile,
+) -> Result<image::DynamicImage, SceneError> {
+    let path = resolve_path(path, directory);
+    read(&path)
+        .map_err(image::ImageError::IoError)
+        .and_then(|bytes| image::load_from_memory(&bytes))
+        .map_err(|error| SceneError::Image(path, error))
+}
+
+/// Samples `image` at pixel coordinates, wrapping horizontally and clamping vertically.
+fn sample_bilinear(image: &image::RgbaImage, x: f32, y: f32) -> image::Rgba<u8> {
+    let (width, height) = (image.width() as i64, image.height() as i64);
+    let (x, y) = (x - 0.5, y - 0.5);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (tx, ty) = (x - x0, y - y0);
+    let texel = |x: i64, y: i64| {
+        let pixel = image.get_pixel(x.rem_euclid(width) as u32, y.clamp(0, height - 1) as u32);
+        nalgebra_glm::vec4(
+            pixel[0] as f32,
+            pixel[1] as f32,
+            pixel[2] as f32,
+            pixel[3] as f32,
+        )
+    };
+    let (x0, y0) = (x0 as i64, y0 as i64);
+    let top = nalgebra_glm::lerp(&texel(x0, y0), &texel(x0 + 1, y0), tx);
+    let bottom = nalgebra_glm::lerp(&texel(x0, y0 + 1), &texel(x0 + 1, y0 + 1), tx);
+    let color = nalgebra_glm::lerp(&top, &bottom, ty);
+    image::Rgba(
+        color
+            .map(|channel| channel.round().clamp(0.0, 255.0) as u8)
+            .into(),
+    )
+}
+
+/// Draws a cube map behind everything else in the scene pass.
+pub(crate) struct SkyboxRenderer {
+    pipeline: wgpu::RenderPipeline,
+    texture_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    /// `None` when the scene has no skybox
+    bind_group: Option<wgpu::BindGroup>,
+}
+
+impl SkyboxRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        target: TargetFormat,
+        camera_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let texture_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Skybox Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::Cube,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Skybox Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        Self {
+            pipeline: Self::create_pipeline(device, target, camera_layout, &texture_layout),
+            texture_layout,
+            sampler,
+            bind_group: None,
+        }
+    }
+
+    /// Draws the cube `view` as the background, or nothing if `None`.
+    pub fn set_texture(&mut self, device: &wgpu::Device, view: Option<&wgpu::TextureView>) {
+        self.bind_group = view.map(|view| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Skybox Bind Group"),
+                layout: &self.texture_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+            })
+        });
+    }
+
+    /// Fills every pixel the scene left at the far plane.
+    pub fn render<'rpass>(
+        &'rpass self,
+        renderpass: &mut wgpu::RenderPass<'rpass>,
+        camera: &'rpass UniformBinding,
+    ) -> DrawStats {
+        let Some(bind_group) = self.bind_group.as_ref() else {
+            return DrawStats::default();
+        };
+        renderpass.set_pipeline(&self.pipeline);
+        renderpass.set_bind_group(0, &camera.bind_group, &[]);
+        renderpass.set_bind_group(1, bind_group, &[]);
+        renderpass.draw(0..3, 0..1);
+        DrawStats {
+            draw_calls: 1,
+            triangles: 1,
+            ..Default::default()
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        target: TargetFormat,
+        camera_layout: &wgpu::BindGroupLayout,
+        texture_layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::RenderPipeline {
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Skybox Shader"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(SKYBOX_SHADER_SOURCE)),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Skybox Pipeline Layout"),
+            bind_group_layouts: &[camera_layout, texture_layout],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Skybox Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: "vertex_main",
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            // Drawn at the far plane, so only where the depth buffer is still clear
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: target.depth,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: target.sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: "fragment_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: target.color,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            multiview: None,
+            cache: None,
+        })
+    }
+}
+
+/// Uploads the faces of `cubemap` and returns a cube view of them.
+pub(crate) fn create_cube_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    label: &str,
+    cubemap: &Cubemap,
+) -> wgpu::TextureView {
+    let size = wgpu::Extent3d {
+        width: cubemap.size,
+        height: cubemap.size,
+        depth_or_array_layers: 6,
+    };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    for (layer, face) in cubemap.faces.iter().enumerate() {
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: 0,
+                    y: 0,
+                    z: layer as u32,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            face.as_raw(),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * cubemap.size),
+                rows_per_image: Some(cubemap.size),
+            },
+            wgpu::Extent3d {
+                depth_or_array_layers: 1,
+                ..size
+            },
+        );
+    }
+    texture.create_view(&wgpu::TextureViewDescriptor {
+        label: Some(label),
+        dimension: Some(wgpu::TextureViewDimension::Cube),
+        ..Default::default()
+    })
+}
+
+const SKYBOX_SHADER_SOURCE: &str = "
+struct Camera {
+    view_projection: mat4x4<f32>,
+    position: vec4<f32>,
+    inverse_view_projection: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+@group(1) @binding(0)
+var skybox_texture: texture_cube<f32>;
+@group(1) @binding(1)
+var skybox_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) clip_position: vec2<f32>,
+};
+
+@vertex
+fn vertex_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    // A triangle covering the whole screen, at the far plane
+    let clip_position = vec2<f32>(
+        f32(vertex_index == 1u) * 4.0 - 1.0,
+        f32(vertex_index == 2u) * 4.0 - 1.0,
+    );
+    var out: VertexOutput;
+    out.position = vec4<f32>(clip_position, 1.0, 1.0);
+    out.clip_position = clip_position;
+    return out;
+};
+
+@fragment
+fn fragment_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let far = camera.inverse_view_projection * vec4<f32>(in.clip_position, 1.0, 1.0);
+    let direction = far.xyz / far.w - camera.position.xyz;
+    return textureSample(skybox_texture, skybox_sampler, direction);
+}
+";
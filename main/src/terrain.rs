@@ -0,0 +1,434 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    skybox::{load_image, ReadFile},
+    Lod, LodSource, Mesh, Object, SceneError, Vertex,
+};
+
+/// Decoded heightmap, 8-bit images are widened to the full 16-bit range.
+pub(crate) type Heightmap = image::ImageBuffer<image::Luma<u16>, Vec<u16>>;
+
+/// Elevation from a grayscale heightmap, drawn as a grid of chunks centered
+/// on the origin. Every chunk is culled and switches its level of detail on
+/// its own, with the edges of all levels matching so neighbors don't crack.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Terrain {
+    /// 8 or 16-bit grayscale image, black at the lowest point. A relative
+    /// path is resolved against the directory of the scene file.
+    pub heightmap: PathBuf,
+    /// Extent along x and z, the image's rows run along z
+    #[serde(default = "Terrain::default_size")]
+    pub size: [f32; 2],
+    /// Height of white above black
+    #[serde(default = "Terrain::default_height")]
+    pub height: f32,
+    /// Quads along each side of a chunk, a power of two
+    #[serde(default = "Terrain::default_chunk_size")]
+    pub chunk_size: u32,
+    /// Coarser versions of every chunk, each with half the quads along a side
+    #[serde(default = "Terrain::default_lod_levels")]
+    pub lod_levels: u32,
+    /// Linear RGB colors at fractions of the height, ascending, blended in between
+    #[serde(default = "Terrain::default_colors")]
+    pub colors: Vec<(f32, [f32; 3])>,
+    /// Name of the material the chunks are drawn with, white if not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub material: Option<String>,
+}
+
+impl Terrain {
+    /// Screen size below which the first coarser level is drawn, halving
+    /// for every further level.
+    const LOD_SCREEN_SIZE: f32 = 0.5;
+
+    pub(crate) fn load(
+        &self,
+        directory: Option<&Path>,
+        read: ReadFile,
+    ) -> Result<Heightmap, SceneError> {
+        load_image(&self.heightmap, directory, read).map(|image| image.into_luma16())
+    }
+
+    /// Describes the first problem with the settings.
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if !self.chunk_size.is_power_of_two() || self.chunk_size < 2 {
+            return Err(format!(
+                "has chunk size {}, expected a power of two of at least 2",
+                self.chunk_size
+            ));
+        }
+        if self.chunk_size >> self.lod_levels == 0 {
+            return Err(format!(
+                "has {} LOD levels, but chunks of {} quads can only be halved {} times",
+                self.lod_levels,
+                self.chunk_size,
+                self.chunk_size.ilog2()
+            ));
+        }
+        if !(self.size[0] > 0.0 && self.size[1] > 0.0) {
+            return Err(format!(
+                "has size {:?}, expected a positive extent",
+                self.size
+            ));
+        }
+        if self.colors.is_empty() {
+            return Err("has no colors".to_string());
+        }
+        if self.colors.windows(2).any(|pair| pair[0].0 > pair[1].0) {
+            return Err("has colors at descending heights".to_string());
+        }
+        Ok(())
+    }
+
+    /// The mesh of every chunk of `heightmap`, row by row.
+    pub(crate) fn chunks(&self, heightmap: &Heightmap) -> Vec<Mesh> {
+        let grid = TerrainGrid::new(self, heightmap);
+        let quads = [heightmap.width() - 1, heightmap.height() - 1].map(|quads| quads.max(1));
+        let chunks = quads.map(|quads| quads.div_ceil(self.chunk_size));
+
+        let mut meshes = Vec::new();
+        for chunk_z in 0..chunks[1] {
+            for chunk_x in 0..chunks[0] {
+                let origin = [chunk_x * self.chunk_size, chunk_z * self.chunk_size];
+                let (vertices, indices) = grid.chunk(origin, 1);
+                let lods = (1..=self.lod_levels)
+                    .map(|level| {
+                        let (vertices, indices) = grid.chunk(origin, 1 << level);
+                        Lod {
+                            screen_size: Self::LOD_SCREEN_SIZE / (1 << (level - 1)) as f32,
+                            source: LodSource::Authored {
+                                vertices,
+                                indices,
+                                skin: Vec::new(),
+                            },
+                        }
+                    })
+                    .collect();
+                meshes.push(Mesh {
+                    name: format!("terrain chunk {chunk_x} {chunk_z}"),
+                    vertices,
+                    indices,
+                    skin: Vec::new(),
+                    lods,
+                });
+            }
+        }
+        meshes
+    }
+
+    /// An object drawing the chunk `mesh`.
+    pub(crate) fn object(&self, mesh: &Mesh) -> Object {
+        Object {
+            name: mesh.name.clone(),
+            mesh: mesh.name.clone(),
+            material: self.material.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Color at `height`, from 0 at the lowest point to 1 at the highest.
+    fn color(&self, height: f32) -> [f32; 4] {
+        let above = self.colors.iter().position(|(stop, _)| *stop > height);
+        let [r, g, b] = match above {
+            Some(0) => self.colors[0].1,
+            None => self.colors[self.colors.len() - 1].1,
+            Some(index) => {
+                let (low, low_color) = self.colors[index - 1];
+                let (high, high_color) = self.colors[index];
+                let t = (height - low) / (high - low);
+                std::array::from_fn(|channel| {
+                    low_color[channel] + (high_color[channel] - low_color[channel]) * t
+                })
+            }
+        };
+        [r, g, b, 1.0]
+    }
+
+    fn default_size() -> [f32; 2] {
+        [100.0, 100.0]
+    }
+
+    fn default_height() -> f32 {
+        10.0
+    }
+
+    fn default_chunk_size() -> u32 {
+        32
+    }
+
+    fn default_lod_levels() -> u32 {
+        3
+    }
+
+    /// Water, sand, grass, rock and snow.
+    fn default_colors() -> Vec<(f32, [f32; 3])> {
+        vec![
+            (0.0, [0.02, 0.08, 0.3]),
+            (0.12, [0.05, 0.2, 0.45]),
+            (0.15, [0.65, 0.6, 0.35]),
+            (0.2, [0.15, 0.4, 0.08]),
+            (0.55, [0.08, 0.25, 0.05]),
+            (0.7, [0.3, 0.25, 0.2]),
+            (0.85, [0.5, 0.5, 0.5]),
+            (0.9, [0.95, 0.95, 0.95]),
+        ]
+    }
+}
+
+/// Vertices of a heightmap at full resolution, for building the chunks.
+struct TerrainGrid {
+    width: u32,
+    height: u32,
+    vertices: Vec<Vertex>,
+    chunk_size: u32,
+}
+
+impl TerrainGrid {
+    fn new(terrain: &Terrain, heightmap: &Heightmap) -> Self {
+        let (width, height) = heightmap.dimensions();
+        let elevation = |x: u32, z: u32| {
+            let x = x.min(width - 1);
+            let z = z.min(height - 1);
+            heightmap.get_pixel(x, z)[0] as f32 / u16::MAX as f32
+        };
+        // Distance between neighboring samples along x and z
+        let spacing = [
+            terrain.size[0] / (width - 1).max(1) as f32,
+            terrain.size[1] / (height - 1).max(1) as f32,
+        ];
+
+        let mut vertices = Vec::with_capacity((width * height) as usize);
+        for z in 0..height {
+            for x in 0..width {
+                let fraction = elevation(x, z);
+                // Central differences, one-sided at the borders
+                let (left, right) = (x.saturating_sub(1), x + 1);
+                let (back, front) = (z.saturating_sub(1), z + 1);
+                let slope_x = (elevation(right, z) - elevation(left, z)) * terrain.height
+                    / ((right.min(width - 1) - left) as f32 * spacing[0]).max(f32::EPSILON);
+                let slope_z = (elevation(x, front) - elevation(x, back)) * terrain.height
+                    / ((front.min(height - 1) - back) as f32 * spacing[1]).max(f32::EPSILON);
+                let normal = nalgebra_glm::vec3(-slope_x, 1.0, -slope_z).normalize();
+                let uv = [
+                    x as f32 / (width - 1).max(1) as f32,
+                    z as f32 / (height - 1).max(1) as f32,
+                ];
+                vertices.push(Vertex {
+                    position: [
+                        (uv[0] - 0.5) * terrain.size[0],
+                        fraction * terrain.height,
+                        (uv[1] - 0.5) * terrain.size[1],
+                    ],
+                    normal: normal.into(),
+                    color: terrain.color(fraction),
+                    uv,
+                });
+            }
+        }
+        Self {
+            width,
+            height,
+            vertices,
+            chunk_size: terrain.chunk_size,
+        }
+    }
+
+    /// The vertices and indices of the chunk starting at the sample `origin`,
+    /// using every `step`th sample inside it but all samples along its edges.
+    /// Samples past the heightmap collapse onto its last row or column.
+    fn chunk(&self, origin: [u32; 2], step: u32) -> (Vec<Vertex>, Vec<u32>) {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        let mut remap = HashMap::new();
+        let mut index = |x: u32, z: u32| {
+            let sample = (x.min(self.width - 1), z.min(self.height - 1));
+            *remap.entry(sample).or_insert_with(|| {
+                vertices.push(self.vertices[(sample.1 * self.width + sample.0) as usize]);
+                vertices.len() as u32 - 1
+            })
+        };
+
+        let size = self.chunk_size;
+        let end = [origin[0] + size, origin[1] + size];
+        for cell_z in (origin[1]..end[1]).step_by(step as usize) {
+            for cell_x in (origin[0]..end[0]).step_by(step as usize) {
+                let on_edge = cell_x == origin[0]
+                    || cell_z == origin[1]
+                    || cell_x + step == end[0]
+                    || cell_z + step == end[1];
+                if step == 1 || !on_edge {
+                    let corners = [
+                        index(cell_x, cell_z),
+                        index(cell_x + step, cell_z),
+                        index(cell_x + step, cell_z + step),
+                        index(cell_x, cell_z + step),
+                    ];
+                    indices.extend([corners[0], corners[1], corners[2]]);
+                    indices.extend([corners[0], corners[2], corners[3]]);
+                    continue;
+                }
+
+                // Cells on the chunk's edge fan out from their center to
+                // every sample along the edge, matching the full resolution
+                // neighbors, and to the corners elsewhere
+                let mut outline = Vec::new();
+                let sides = [
+                    ([cell_x, cell_z], [1, 0], cell_z == origin[1]),
+                    ([cell_x + step, cell_z], [0, 1], cell_x + step == end[0]),
+                    (
+                        [cell_x + step, cell_z + step],
+                        [-1, 0],
+                        cell_z + step == end[1],
+                    ),
+                    ([cell_x, cell_z + step], [0, -1], cell_x == origin[0]),
+                ];
+                for (start, direction, edge) in sides {
+                    let side_step = if edge { 1 } else { step };
+                    for offset in (0..step).step_by(side_step as usize) {
+                        outline.push(index(
+                            start[0].wrapping_add_signed(direction[0] * offset as i32),
+                            start[1].wrapping_add_signed(direction[1] * offset as i32),
+                        ));
+                    }
+                }
+                let center = index(cell_x + step / 2, cell_z + step / 2);
+                for (corner, next) in outline.iter().zip(outline.iter().cycle().skip(1)) {
+                    if corner != next {
+                        indices.extend([center, *corner, *next]);
+                    }
+                }
+            }
+        }
+        // Chunks past the heightmap collapse completely in places
+        let degenerate = |triangle: &[u32]| {
+            triangle[0] == triangle[1] || triangle[1] == triangle[2] || triangle[0] == triangle[2]
+        };
+        let indices = indices
+            .chunks(3)
+            .filter(|triangle| !degenerate(triangle))
+            .flatten()
+            .copied()
+            .collect();
+        (vertices, indices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    fn terrain(chunk_size: u32, lod_levels: u32) -> Terrain {
+        Terrain {
+            heightmap: PathBuf::from("heightmap.png"),
+            size: [16.0, 16.0],
+            height: 4.0,
+            chunk_size,
+            lod_levels,
+            colors: Terrain::default_colors(),
+            material: None,
+        }
+    }
+
+    /// Rolling hills, so no two neighboring triangles are coplanar.
+    fn heightmap(width: u32, height: u32) -> Heightmap {
+        Heightmap::from_fn(width, height, |x, z| {
+            let hills = (x as f32 * 0.7).sin() * (z as f32 * 0.45).cos();
+            image::Luma([((hills * 0.5 + 0.5) * u16::MAX as f32) as u16])
+        })
+    }
+
+    /// Vertices and indices of every level of detail of `mesh`, finest first.
+    fn levels(mesh: &Mesh) -> Vec<(&[Vertex], &[u32])> {
+        let mut levels = vec![(&mesh.vertices[..], &mesh.indices[..])];
+        for lod in &mesh.lods {
+            match &lod.source {
+                LodSource::Authored {
+                    vertices, indices, ..
+                } => levels.push((vertices, indices)),
+                LodSource::Simplified(_) => panic!("terrain levels are authored"),
+            }
+        }
+        levels
+    }
+
+    /// Triangle edges lying on the plane x = `x`, as their z coordinates.
+    fn edges_at_x(vertices: &[Vertex], indices: &[u32], x: f32) -> HashSet<(u32, u32)> {
+        let mut edges = HashSet::new();
+        for triangle in indices.chunks(3) {
+            for (a, b) in [(0, 1), (1, 2), (2, 0)] {
+                let (a, b) = (
+                    vertices[triangle[a] as usize].position,
+                    vertices[triangle[b] as usize].position,
+                );
+                if a[0] == x && b[0] == x {
+                    let (low, high) = (a[2].min(b[2]), a[2].max(b[2]));
+                    edges.insert((low.to_bits(), high.to_bits()));
+                }
+            }
+        }
+        edges
+    }
+
+    #[test]
+    fn neighboring_chunks_share_their_edges_at_every_level() {
+        let terrain = terrain(8, 2);
+        let meshes = terrain.chunks(&heightmap(17, 17));
+        assert_eq!(meshes.len(), 4);
+        assert!(meshes.iter().all(|mesh| mesh.lods.len() == 2));
+
+        // The first two chunks of the first row meet at x = 0
+        let (left, right) = (levels(&meshes[0]), levels(&meshes[1]));
+        for (left_vertices, left_indices) in &left {
+            let expected = edges_at_x(left_vertices, left_indices, 0.0);
+            assert_eq!(expected.len(), 8);
+            for (right_vertices, right_indices) in &right {
+                assert_eq!(edges_at_x(right_vertices, right_indices, 0.0), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn chunks_have_no_degenerate_triangles() {
+        let terrain = terrain(8, 3);
+        // Chunks past the last row and column collapse onto it
+        for (width, height) in [(17, 17), (13, 11), (2, 2)] {
+            for mesh in terrain.chunks(&heightmap(width, height)) {
+                for (vertices, indices) in levels(&mesh) {
+                    assert!(indices
+                        .iter()
+                        .all(|&index| (index as usize) < vertices.len()));
+                    for triangle in indices.chunks(3) {
+                        let [a, b, c] = [0, 1, 2].map(|corner| {
+                            nalgebra_glm::Vec3::from(vertices[triangle[corner] as usize].position)
+                        });
+                        let area = (b - a).cross(&(c - a)).norm();
+                        assert!(area > 1e-6, "{} has a degenerate triangle", mesh.name);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_chunk_sizes_are_rejected() {
+        for chunk_size in [0, 1, 3, 24] {
+            assert!(terrain(chunk_size, 0).validate().is_err(), "{chunk_size}");
+        }
+        assert!(terrain(2, 0).validate().is_ok());
+    }
+
+    #[test]
+    fn chunks_can_only_be_halved_so_often() {
+        assert!(terrain(8, 3).validate().is_ok());
+        assert!(terrain(8, 4).validate().is_err());
+        assert!(terrain(32, 5).validate().is_ok());
+        assert!(terrain(32, 6).validate().is_err());
+    }
+}
@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+
+/// Translation, rotation and scale of an object, applied in reverse order.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Transform {
+    pub translation: nalgebra_glm::Vec3,
+    pub rotation: nalgebra_glm::Quat,
+    pub scale: nalgebra_glm::Vec3,
+}
+
+impl Transform {
+    pub fn matrix(&self) -> nalgebra_glm::Mat4 {
+        nalgebra_glm::translation(&self.translation)
+            * nalgebra_glm::quat_to_mat4(&self.rotation)
+            * nalgebra_glm::scaling(&self.scale)
+    }
+
+    /// Splits an affine matrix without shear into its parts.
+    pub fn from_matrix(matrix: &nalgebra_glm::Mat4) -> Self {
+        let scale = nalgebra_glm::vec3(
+            matrix.column(0).xyz().norm(),
+            matrix.column(1).xyz().norm(),
+            matrix.column(2).xyz().norm(),
+        );
+        let mut rotation = nalgebra_glm::Mat3::identity();
+        for axis in 0..3 {
+            if scale[axis] > 0.0 {
+                rotation.set_column(axis, &(matrix.column(axis).xyz() / scale[axis]));
+            }
+        }
+        Self {
+            translation: matrix.column(3).xyz(),
+            rotation: nalgebra_glm::quat_normalize(&nalgebra_glm::mat3_to_quat(&rotation)),
+            scale,
+        }
+    }
+
+    /// Rotates by `angle` radians around `axis`, in the transform's local space.
+    pub fn rotate(&mut self, angle: f32, axis: &nalgebra_glm::Vec3) {
+        self.rotation =
+            nalgebra_glm::quat_normalize(&nalgebra_glm::quat_rotate(&self.rotation, angle, axis));
+    }
+
+    /// Blends towards `other`, with `t` = 0 giving `self` and `t` = 1 giving `other`.
+    pub fn interpolate(&self, other: &Self, t: f32) -> Self {
+        Self {
+            translation: nalgebra_glm::lerp(&self.translation, &other.translation, t),
+            rotation: nalgebra_glm::quat_slerp(&self.rotation, &other.rotation, t),
+            scale: nalgebra_glm::lerp(&self.scale, &other.scale, t),
+        }
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: nalgebra_glm::Vec3::zeros(),
+            rotation: nalgebra_glm::Quat::identity(),
+            scale: nalgebra_glm::vec3(1.0, 1.0, 1.0),
+        }
+    }
+}
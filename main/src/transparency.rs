@@ -0,0 +1,407 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{DrawStats, ScenePipelines, TargetFormat};
+
+/// How translucent objects are blended over the opaque ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transparency {
+    /// Blended back to front by the view depth of their centers, which is
+    /// exact unless objects overlap in depth
+    #[default]
+    Sorted,
+    /// Weighted-blended order-independent transparency, approximate but
+    /// stable for intersecting meshes
+    Weighted,
+}
+
+impl Transparency {
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Sorted => Self::Weighted,
+            Self::Weighted => Self::Sorted,
+        }
+    }
+}
+
+impl std::str::FromStr for Transparency {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().replace('_', "-").as_str() {
+            "sorted" => Ok(Self::Sorted),
+            "weighted" | "weighted-blended" | "oit" => Ok(Self::Weighted),
+            _ => Err(format!(
+                "unknown transparency mode '{value}', expected sorted or weighted"
+            )),
+        }
+    }
+}
+
+/// Draws translucent objects into an accumulation and a revealage target,
+/// then composites their weighted average over the frame.
+pub(crate) struct WeightedBlendedRenderer {
+    /// Accumulation pipelines of the scene's objects
+    pub pipelines: ScenePipelines,
+    composite_pipeline: wgpu::RenderPipeline,
+    composite_layout: wgpu::BindGroupLayout,
+    target: TargetFormat,
+    /// Sized for the frame drawn last
+    targets: Option<WeightedBlendedTargets>,
+}
+
+struct WeightedBlendedTargets {
+    size: (u32, u32),
+    /// Multisampled attachments, resolved into the sampled ones
+    msaa_views: Option<[wgpu::TextureView; 2]>,
+    accumulation_view: wgpu::TextureView,
+    revealage_view: wgpu::TextureView,
+    bind_group: wgpu::BindGroup,
+}
+
+impl WeightedBlendedRenderer {
+    /// Premultiplied color times weight in rgb, alpha times weight in a.
+    pub const ACCUMULATION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+    /// Product of one minus each layer's alpha.
+    pub const REVEALAGE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+
+    pub fn new(device: &wgpu::Device, target: TargetFormat, pipelines: ScenePipelines) -> Self {
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let composite_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Transparency Composite Bind Group Layout"),
+            entries: &[texture_entry(0), texture_entry(1)],
+        });
+
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Transparency Composite Shader"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(COMPOSITE_SHADER_SOURCE)),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Transparency Composite Pipeline Layout"),
+            bind_group_layouts: &[&composite_layout],
+            push_constant_ranges: &[],
+        });
+        let composite_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Transparency Composite Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: "vertex_main",
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: target.sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: "fragment_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: target.color,
+                    // Keeps the frame's own alpha
+                    blend: Some(wgpu::BlendState {
+                        color: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::SrcAlpha,
+                            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                        alpha: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::Zero,
+                            dst_factor: wgpu::BlendFactor::One,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                    }),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            pipelines,
+            composite_pipeline,
+            composite_layout,
+            target,
+            targets: None,
+        }
+    }
+
+    /// Blend states of the accumulation and revealage targets, in that order.
+    pub fn color_targets() -> [Option<wgpu::ColorTargetState>; 2] {
+        let additive = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        let revealage = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::Zero,
+            dst_factor: wgpu::BlendFactor::OneMinusSrc,
+            operation: wgpu::BlendOperation::Add,
+        };
+        [
+            Some(wgpu::ColorTargetState {
+                format: Self::ACCUMULATION_FORMAT,
+                blend: Some(wgpu::BlendState {
+                    color: additive,
+                    alpha: additive,
+                }),
+                write_mask: wgpu::ColorWrites::ALL,
+            }),
+            Some(wgpu::ColorTargetState {
+                format: Self::REVEALAGE_FORMAT,
+                blend: Some(wgpu::BlendState {
+                    color: revealage,
+                    alpha: revealage,
+                }),
+                write_mask: wgpu::ColorWrites::ALL,
+            }),
+        ]
+    }
+
+    /// Makes sure the targets match a frame of `size`.
+    pub fn prepare(&mut self, device: &wgpu::Device, size: (u32, u32)) {
+        if self.targets.as_ref().map(|targets| targets.size) != Some(size) {
+            self.targets = Some(self.create_targets(device, size));
+        }
+    }
+
+    fn create_targets(
+        &self,
+        device: &wgpu::Device,
+        (width, height): (u32, u32),
+    ) -> WeightedBlendedTargets {
+        let create_view = |label, format, sample_count| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size: wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: match sample_count {
+                        1 => {
+                            wgpu::TextureUsages::RENDER_ATTACHMENT
+                                | wgpu::TextureUsages::TEXTURE_BINDING
+                        }
+                        _ => wgpu::TextureUsages::RENDER_ATTACHMENT,
+                    },
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        let accumulation_view = create_view("Accumulation Texture", Self::ACCUMULATION_FORMAT, 1);
+        let revealage_view = create_view("Revealage Texture", Self::REVEALAGE_FORMAT, 1);
+        let msaa_views = (self.target.sample_count > 1).then(|| {
+            [
+                create_view(
+                    "MSAA Accumulation Texture",
+                    Self::ACCUMULATION_FORMAT,
+                    self.target.sample_count,
+                ),
+                create_view(
+                    "MSAA Revealage Texture",
+                    Self::REVEALAGE_FORMAT,
+                    self.target.sample_count,
+                ),
+            ]
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Transparency Composite Bind Group"),
+            layout: &self.composite_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&accumulation_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&revealage_view),
+                },
+            ],
+        });
+        WeightedBlendedTargets {
+            size: (width, height),
+            msaa_views,
+            accumulation_view,
+            revealage_view,
+            bind_group,
+        }
+    }
+
+    /// Starts the pass the translucent objects are drawn into, testing against
+    /// but not writing `depth_view`. Needs [`Self::prepare`] first.
+    pub fn begin_accumulation<'encoder>(
+        &'encoder self,
+        encoder: &'encoder mut wgpu::CommandEncoder,
+        depth_view: &'encoder wgpu::TextureView,
+    ) -> wgpu::RenderPass<'encoder> {
+        let targets = self
+            .targets
+            .as_ref()
+            .expect("Weighted-blended targets were not prepared!");
+        let attachment = |view, resolve_target, clear| {
+            Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(clear),
+                    store: match resolve_target {
+                        Some(_) => wgpu::StoreOp::Discard,
+                        None => wgpu::StoreOp::Store,
+                    },
+                },
+            })
+        };
+        let color_attachments = match &targets.msaa_views {
+            Some([accumulation, revealage]) => [
+                attachment(
+                    accumulation,
+                    Some(&targets.accumulation_view),
+                    wgpu::Color::TRANSPARENT,
+                ),
+                attachment(revealage, Some(&targets.revealage_view), wgpu::Color::WHITE),
+            ],
+            None => [
+                attachment(&targets.accumulation_view, None, wgpu::Color::TRANSPARENT),
+                attachment(&targets.revealage_view, None, wgpu::Color::WHITE),
+            ],
+        };
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Transparency Accumulation Pass"),
+            color_attachments: &color_attachments,
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        })
+    }
+
+    /// Blends the accumulated layers over `color_view`, resolving into
+    /// `resolve_view` when multisampling.
+    pub fn composite(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        color_view: &wgpu::TextureView,
+        resolve_view: Option<&wgpu::TextureView>,
+    ) -> DrawStats {
+        let targets = self
+            .targets
+            .as_ref()
+            .expect("Weighted-blended targets were not prepared!");
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Transparency Composite Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color_view,
+                resolve_target: resolve_view,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: match resolve_view {
+                        Some(_) => wgpu::StoreOp::Discard,
+                        None => wgpu::StoreOp::Store,
+                    },
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.composite_pipeline);
+        render_pass.set_bind_group(0, &targets.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+        DrawStats {
+            draw_calls: 1,
+            triangles: 1,
+            ..Default::default()
+        }
+    }
+}
+
+/// Fragment stage writing the weighted-blended targets, appended to the scene shader.
+pub(crate) const ACCUMULATE_SHADER_SOURCE: &str = "
+struct AccumulateOutput {
+    @location(0) accumulation: vec4<f32>,
+    @location(1) revealage: vec4<f32>,
+};
+
+// Weights nearby layers higher, following equation 7 of McGuire and Bavoil's
+// Weighted Blended Order-Independent Transparency
+@fragment
+fn fragment_accumulate(in: VertexOutput) -> AccumulateOutput {
+    let color = shade(
+        in.world_position,
+        in.normal,
+        in.color.rgb,
+        object.material.x,
+        object.material.y,
+        1.0,
+    ) + object.emissive.rgb;
+    let alpha = in.color.a;
+    let distance = length(in.world_position - camera.position.xyz);
+    let weight = alpha * clamp(
+        10.0 / (0.00001 + pow(distance / 5.0, 2.0) + pow(distance / 200.0, 6.0)),
+        0.01,
+        3000.0,
+    );
+    var out: AccumulateOutput;
+    out.accumulation = vec4<f32>(color * alpha, alpha) * weight;
+    out.revealage = vec4<f32>(alpha);
+    return out;
+}
+";
+
+const COMPOSITE_SHADER_SOURCE: &str = "
+@group(0) @binding(0)
+var accumulation_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var revealage_texture: texture_2d<f32>;
+
+@vertex
+fn vertex_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    // A triangle covering the whole screen
+    return vec4<f32>(
+        f32(vertex_index == 1u) * 4.0 - 1.0,
+        f32(vertex_index == 2u) * 4.0 - 1.0,
+        0.0,
+        1.0,
+    );
+};
+
+// The weighted average color, covering the frame by one minus the revealage
+@fragment
+fn fragment_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let coords = vec2<i32>(position.xy);
+    let revealage = textureLoad(revealage_texture, coords, 0).r;
+    if revealage >= 1.0 {
+        discard;
+    }
+    let accumulation = textureLoad(accumulation_texture, coords, 0);
+    let color = accumulation.rgb / clamp(accumulation.a, 0.0001, 50000.0);
+    return vec4<f32>(color, 1.0 - revealage);
+}
+";
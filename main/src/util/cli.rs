@@ -0,0 +1,219 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use main_core::{
+    AppConfig, ConfigStore, DebugView, Recording, RecordingFormat, RenderPath, SimulationSettings,
+    Transparency, WindowMode,
+};
+use tracing_subscriber::filter::LevelFilter;
+
+/// Standalone Winit/Wgpu Example
+///
+/// Options given on the command line override the ones in the config file.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Args {
+    /// Config file to load and save settings, defaults to the platform config directory
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// Initial window mode: windowed, borderless or exclusive
+    #[arg(long)]
+    pub window_mode: Option<WindowMode>,
+
+    /// Initial window size in physical pixels, e.g. 1280x720
+    #[arg(long, value_parser = parse_size)]
+    pub size: Option<(u32, u32)>,
+
+    /// Index of the monitor to open the window on
+    #[arg(long)]
+    pub monitor: Option<usize>,
+
+    /// Preferred refresh rate in hertz for exclusive fullscreen
+    #[arg(long)]
+    pub refresh_rate: Option<u32>,
+
+    /// Comma separated wgpu backends, e.g. vulkan,gl. Defaults to WGPU_BACKEND or all backends
+    #[arg(long, value_parser = parse_backends)]
+    pub backend: Option<wgpu::Backends>,
+
+    /// Part of the name of the adapter to use
+    #[arg(long)]
+    pub adapter: Option<String>,
+
+    /// Scene file to load, in RON or glTF
+    #[arg(long)]
+    pub scene: Option<PathBuf>,
+
+    /// MSAA sample count
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=16))]
+    pub msaa: Option<u32>,
+
+    /// Shading of opaque objects: forward, or deferred through a G-buffer
+    #[arg(long)]
+    pub render_path: Option<RenderPath>,
+
+    /// Present mode: auto-vsync, auto-no-vsync, fifo, fifo-relaxed, mailbox or immediate
+    #[arg(long, value_parser = parse_present_mode)]
+    pub present_mode: Option<wgpu::PresentMode>,
+
+    /// Maximum frames per second
+    #[arg(long)]
+    pub fps: Option<u32>,
+
+    /// Simulation steps per second
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub tick_rate: Option<u32>,
+
+    /// Simulation speed multiplier, 1 is real time
+    #[arg(long, value_parser = parse_time_scale)]
+    pub time_scale: Option<f32>,
+
+    /// Draw the scene as shaded, wireframe, normals, depth, uvs or overdraw
+    #[arg(long)]
+    pub debug_view: Option<DebugView>,
+
+    /// Blending of translucent objects: sorted back to front, or weighted for
+    /// order-independent transparency
+    #[arg(long)]
+    pub transparency: Option<Transparency>,
+
+    /// Darken the ambient light where surfaces meet, needs --render-path deferred
+    #[arg(long)]
+    pub ambient_occlusion: bool,
+
+    /// Default log level, RUST_LOG directives still apply on top of it
+    #[arg(long, default_value = "info")]
+    pub log_level: LevelFilter,
+
+    /// Render this many frames without a window and exit
+    #[arg(long, requires = "output", value_parser = clap::value_parser!(u32).range(1..))]
+    pub frames: Option<u32>,
+
+    /// Where the frames rendered with --frames are written: a .gif or .png file
+    /// for an animation, otherwise a directory for a PNG sequence
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+
+    /// Recording format overriding the one picked from --output: png-sequence, gif or apng
+    #[arg(long)]
+    pub format: Option<RecordingFormat>,
+
+    /// Simulated frames per second of the frames rendered with --frames
+    #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u32).range(1..))]
+    pub frame_rate: u32,
+}
+
+impl Args {
+    /// Size of the frames rendered with `--frames` when no `--size` is given.
+    pub const DEFAULT_SIZE: (u32, u32) = (800, 600);
+
+    /// The recording requested with `--frames` and `--output`, if any.
+    pub fn recording(&self) -> Option<Recording> {
+        let (frames, output) = (self.frames?, self.output.clone()?);
+        Some(Recording {
+            frames,
+            frame_rate: self.frame_rate,
+            format: self
+                .format
+                .unwrap_or_else(|| RecordingFormat::from_path(&output)),
+            output,
+        })
+    }
+
+    pub fn config_store(&self) -> ConfigStore {
+        match self.config.as_ref() {
+            Some(path) => ConfigStore::File(path.clone()),
+            None => ConfigStore::platform_default(),
+        }
+    }
+
+    /// Overrides the settings in `config` with the ones given on the command line.
+    pub fn apply(&self, config: &mut AppConfig) {
+        if let Some(mode) = self.window_mode {
+            config.window.mode = mode;
+        }
+        if self.size.is_some() {
+            config.window.size = self.size;
+        }
+        if self.monitor.is_some() {
+            config.window.monitor = self.monitor;
+        }
+        if self.refresh_rate.is_some() {
+            config.window.refresh_rate = self.refresh_rate;
+        }
+        if self.backend.is_some() {
+            config.renderer.backends = self.backend;
+        }
+        if self.adapter.is_some() {
+            config.renderer.adapter = self.adapter.clone();
+        }
+        if let Some(msaa) = self.msaa {
+            config.renderer.sample_count = msaa;
+        }
+        if let Some(render_path) = self.render_path {
+            config.renderer.render_path = render_path;
+        }
+        if let Some(present_mode) = self.present_mode {
+            config.display.present_mode = present_mode;
+        }
+        if self.fps.is_some() {
+            config.display.target_fps = self.fps;
+        }
+        if let Some(tick_rate) = self.tick_rate {
+            config.simulation.tick_rate = tick_rate;
+        }
+        if let Some(time_scale) = self.time_scale {
+            config.simulation.time_scale = time_scale;
+        }
+        if let Some(debug_view) = self.debug_view {
+            config.scene.debug_view = debug_view;
+        }
+        if let Some(transparency) = self.transparency {
+            config.scene.transparency = transparency;
+        }
+        if self.ambient_occlusion {
+            config.scene.ambient_occlusion.enabled = true;
+        }
+    }
+}
+
+fn parse_size(value: &str) -> Result<(u32, u32), String> {
+    let (width, height) = value
+        .split_once(['x', 'X'])
+        .ok_or_else(|| format!("expected WIDTHxHEIGHT, got '{value}'"))?;
+    let parse = |dimension: &str| match dimension.trim().parse::<u32>() {
+        Ok(dimension) if dimension > 0 => Ok(dimension),
+        _ => Err(format!("invalid dimension '{dimension}' in '{value}'")),
+    };
+    Ok((parse(width)?, parse(height)?))
+}
+
+fn parse_time_scale(value: &str) -> Result<f32, String> {
+    let time_scale = value
+        .parse::<f32>()
+        .map_err(|error| format!("invalid time scale '{value}': {error}"))?;
+    SimulationSettings::validate_time_scale(time_scale)
+}
+
+fn parse_backends(value: &str) -> Result<wgpu::Backends, String> {
+    let backends = wgpu::util::parse_backends_from_comma_list(value);
+    if backends.is_empty() {
+        return Err(format!(
+            "no known backend in '{value}', expected vulkan, metal, dx12, gl, webgpu or primary"
+        ));
+    }
+    Ok(backends)
+}
+
+fn parse_present_mode(value: &str) -> Result<wgpu::PresentMode, String> {
+    match value.to_ascii_lowercase().replace('_', "-").as_str() {
+        "auto-vsync" => Ok(wgpu::PresentMode::AutoVsync),
+        "auto-no-vsync" => Ok(wgpu::PresentMode::AutoNoVsync),
+        "fifo" => Ok(wgpu::PresentMode::Fifo),
+        "fifo-relaxed" => Ok(wgpu::PresentMode::FifoRelaxed),
+        "mailbox" => Ok(wgpu::PresentMode::Mailbox),
+        "immediate" => Ok(wgpu::PresentMode::Immediate),
+        _ => Err(format!("unknown present mode '{value}'")),
+    }
+}
@@ -1,16 +1,20 @@
+use tracing_subscriber::filter::LevelFilter;
+
 #[cfg(not(web_platform))]
-pub fn init() {
-    use tracing_subscriber::filter::{EnvFilter, LevelFilter};
+pub fn init(level: LevelFilter) {
+    use tracing_subscriber::filter::EnvFilter;
 
     tracing_subscriber::fmt()
         .with_env_filter(
-            EnvFilter::builder().with_default_directive(LevelFilter::INFO.into()).from_env_lossy(),
+            EnvFilter::builder()
+                .with_default_directive(level.into())
+                .from_env_lossy(),
         )
         .init();
 }
 
 #[cfg(web_platform)]
-pub fn init() {
+pub fn init(level: LevelFilter) {
     use tracing_subscriber::layer::SubscriberExt;
     use tracing_subscriber::util::SubscriberInitExt;
 
@@ -21,5 +25,6 @@ pub fn init() {
                 .without_time()
                 .with_writer(tracing_web::MakeWebConsoleWriter::new()),
         )
+        .with(level)
         .init();
-}
\ No newline at end of file
+}
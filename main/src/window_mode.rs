@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+use winit::{
+    dpi::PhysicalSize,
+    monitor::{MonitorHandle, VideoModeHandle},
+    window::{Fullscreen, Window},
+};
+
+/// How a window occupies its monitor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowMode {
+    #[default]
+    Windowed,
+    /// Fullscreen window at the monitor's current resolution
+    Borderless,
+    /// Fullscreen with exclusive access to the monitor and a chosen video mode
+    Exclusive,
+}
+
+impl WindowMode {
+    /// The mode a window is currently in.
+    pub fn of(window: &Window) -> Self {
+        match window.fullscreen() {
+            None => Self::Windowed,
+            Some(Fullscreen::Borderless(_)) => Self::Borderless,
+            Some(Fullscreen::Exclusive(_)) => Self::Exclusive,
+        }
+    }
+}
+
+impl std::str::FromStr for WindowMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "windowed" => Ok(Self::Windowed),
+            "borderless" => Ok(Self::Borderless),
+            "exclusive" | "fullscreen" => Ok(Self::Exclusive),
+            _ => Err(format!(
+                "unknown window mode '{value}', expected windowed, borderless or exclusive"
+            )),
+        }
+    }
+}
+
+/// Window placement and sizing, applied when windows are created or switched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowSettings {
+    pub mode: WindowMode,
+    /// Inner size in physical pixels, also the preferred resolution in exclusive fullscreen
+    pub size: Option<(u32, u32)>,
+    /// Index into the monitors reported by the platform, the primary monitor when unset
+    pub monitor: Option<usize>,
+    /// Preferred refresh rate in exclusive fullscreen, in hertz
+    pub refresh_rate: Option<u32>,
+}
+
+impl WindowSettings {
+    /// Resolves the monitor selected by these settings.
+    pub fn select_monitor(
+        &self,
+        mut available_monitors: impl Iterator<Item = MonitorHandle>,
+        primary_monitor: Option<MonitorHandle>,
+    ) -> Option<MonitorHandle> {
+        self.monitor
+            .and_then(|index| available_monitors.nth(index))
+            .or(primary_monitor)
+            .or_else(|| available_monitors.next())
+    }
+
+    /// The fullscreen state for `mode` on `monitor`.
+    ///
+    /// Exclusive fullscreen falls back to borderless when the monitor exposes no video modes.
+    pub fn fullscreen(
+        &self,
+        mode: WindowMode,
+        monitor: Option<MonitorHandle>,
+    ) -> Option<Fullscreen> {
+        match mode {
+            WindowMode::Windowed => None,
+            WindowMode::Borderless => Some(Fullscreen::Borderless(monitor)),
+            WindowMode::Exclusive => {
+                match monitor
+                    .as_ref()
+                    .and_then(|monitor| self.select_video_mode(monitor))
+                {
+                    Some(video_mode) => {
+                        tracing::info!("Exclusive fullscreen video mode: {video_mode}");
+                        Some(Fullscreen::Exclusive(video_mode))
+                    }
+                    None => {
+                        tracing::warn!("No video modes available, using borderless fullscreen");
+                        Some(Fullscreen::Borderless(monitor))
+                    }
+                }
+            }
+        }
+    }
+
+    /// Picks the video mode closest to the requested size and refresh rate,
+    /// preferring the monitor's current resolution and the highest refresh rate otherwise.
+    pub fn select_video_mode(&self, monitor: &MonitorHandle) -> Option<VideoModeHandle> {
+        let PhysicalSize { width, height } = self
+            .size
+            .map(|(width, height)| PhysicalSize::new(width, height))
+            .unwrap_or_else(|| monitor.size());
+        let refresh_rate = self.refresh_rate.map(|hz| hz.saturating_mul(1000));
+
+        monitor.video_modes().min_by_key(|video_mode| {
+            let size = video_mode.size();
+            let size_error = size
+                .width
+                .abs_diff(width)
+                .saturating_add(size.height.abs_diff(height));
+            let refresh_error = match refresh_rate {
+                Some(millihertz) => video_mode.refresh_rate_millihertz().abs_diff(millihertz),
+                None => u32::MAX - video_mode.refresh_rate_millihertz(),
+            };
+            (size_error, refresh_error, u16::MAX - video_mode.bit_depth())
+        })
+    }
+}